@@ -1,359 +1,459 @@
-use crate::{transform::*, raster::SceneTriangle};
+use crate::{
+    broadphase::Aabb,
+    gjk::SupportMap,
+    raster::SceneTriangle,
+    raycast::*,
+    mesh::Mesh,
+    shape::Shape,
+    transform::*,
+};
 
-use tui::style::Color;
+use std::cell::OnceCell;
+
+pub const DEFAULT_RESTITUTION: f64 = 0.2;
+pub const DEFAULT_FRICTION: f64 = 0.5;
+
+// Bodies start out in the first collision group and colliding with every group
+pub const DEFAULT_COLLISION_GROUP: u32 = 1;
+pub const ALL_COLLISION_GROUPS: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BodyType {
+    // Moved by forces, gravity and contacts
+    #[default]
+    Dynamic,
+    // Moved only by its velocity, which the game sets, and pushes dynamic bodies out of its way as if it
+    // had infinite mass
+    Kinematic,
+    // Never moves, like level geometry
+    Static,
+}
 
-#[derive(Default)]
 pub struct Body {
+    pub body_type: BodyType,
     pub transform: Transform,
-    pub half_size: Vector3,
+    // Transform at the start of the last physics step, used to interpolate rendering between steps
+    pub previous_transform: Transform,
+    pub shape: Shape,
+    // Mesh of the shape, built the first time the body is drawn. Changing the shape needs a new one.
+    pub mesh: OnceCell<Mesh>,
     pub linear_velocity: Vector3,
     pub angular_velocity: Vector3,
+
+    // Inverse mass and body space inverse inertia tensor, zero for bodies that cannot be moved
+    pub inverse_mass: f64,
+    pub inverse_inertia: Matrix3,
+
+    // Force and torque accumulated since the last integration step
+    pub force: Vector3,
+    pub torque: Vector3,
+
+    // Fraction of the linear and angular velocity lost per second, like drag through air
+    pub linear_damping: f64,
+    pub angular_damping: f64,
+
+    // Surface material used when resolving contacts
+    pub restitution: f64,
+    pub friction: f64,
+
+    // Sleeping bodies are skipped by integration and act as static geometry in the solver until woken
+    pub is_sleeping: bool,
+    // How long the velocities of the body have stayed below the sleep thresholds
+    pub sleep_time: f64,
+
+    // Fast moving bodies that are swept through each step so they can't tunnel through thin geometry
+    pub is_bullet: bool,
+
+    // Bit sets of the groups the body is in and the groups it collides with. Two bodies only collide when
+    // each is in a group the other collides with.
+    pub collision_group: u32,
+    pub collision_mask: u32,
+    // Sensors only report what overlaps them as trigger events and never push anything
+    pub is_sensor: bool,
+}
+
+// Immovable until a mass is set, so the inverse inertia is zero like the inverse mass rather than the
+// identity that Matrix3 defaults to. The material and collision groups are the same as from Body::new.
+impl Default for Body {
+    fn default() -> Self {
+        Body {
+            body_type: BodyType::default(),
+            transform: Transform::default(),
+            previous_transform: Transform::default(),
+            shape: Shape::default(),
+            mesh: OnceCell::new(),
+            linear_velocity: Vector3::default(),
+            angular_velocity: Vector3::default(),
+            inverse_mass: 0.0,
+            inverse_inertia: Matrix3::ZERO,
+            force: Vector3::default(),
+            torque: Vector3::default(),
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            restitution: DEFAULT_RESTITUTION,
+            friction: DEFAULT_FRICTION,
+            is_sleeping: false,
+            sleep_time: 0.0,
+            is_bullet: false,
+            collision_group: DEFAULT_COLLISION_GROUP,
+            collision_mask: ALL_COLLISION_GROUPS,
+            is_sensor: false,
+        }
+    }
 }
 
 impl Body {
-    pub fn geometry(&self) -> impl Iterator<Item = SceneTriangle> + '_ {
-        BODY_UNIT_GEOMETRY.iter().map(|geometry| {
-            let normal = self.transform.rotation.vector_to_world_space(geometry.normal);
-            SceneTriangle {
-                normal,
-                points: geometry.points.map(|point| self.transform.point_to_world_space(point * self.half_size)),
-                color: geometry.color
+    pub fn new(transform: Transform, shape: Shape, mass: f64) -> Body {
+        let mut body = Body {
+            previous_transform: transform.clone(),
+            transform,
+            shape,
+            ..Default::default()
+        };
+        body.set_mass(mass);
+        body
+    }
+
+    pub fn new_kinematic(transform: Transform, shape: Shape) -> Body {
+        Body {
+            body_type: BodyType::Kinematic,
+            ..Body::new(transform, shape, 0.0)
+        }
+    }
+
+    pub fn new_static(transform: Transform, shape: Shape) -> Body {
+        Body {
+            body_type: BodyType::Static,
+            ..Body::new(transform, shape, 0.0)
+        }
+    }
+
+    // Sets the mass and the inertia tensor of the body's shape as a uniform solid. A mass that is not
+    // positive and finite makes the body immovable, and only dynamic bodies have mass at all.
+    pub fn set_mass(&mut self, mass: f64) {
+        if self.body_type != BodyType::Dynamic || mass <= 0.0 || !mass.is_finite() {
+            self.inverse_mass = 0.0;
+            self.inverse_inertia = Matrix3::ZERO;
+            return;
+        }
+
+        self.inverse_mass = 1.0 / mass;
+        self.inverse_inertia = self.shape.inertia(mass).inverse();
+    }
+
+    pub fn mass(&self) -> f64 {
+        if self.inverse_mass == 0.0 {
+            f64::INFINITY
+        } else {
+            1.0 / self.inverse_mass
+        }
+    }
+
+    // Inverse inertia tensor rotated into world space (R * I^-1 * R^T)
+    pub fn inverse_inertia_world(&self) -> Matrix3 {
+        let rotation = Matrix3::from_rotation(self.transform.rotation);
+        rotation * self.inverse_inertia * rotation.transpose()
+    }
+
+    // Whether the body can currently move, as opposed to being asleep or immovable. Kinematic bodies only
+    // count while they have a velocity.
+    pub fn is_active(&self) -> bool {
+        match self.body_type {
+            BodyType::Dynamic => !self.is_sleeping && self.inverse_mass > 0.0,
+            BodyType::Kinematic => {
+                self.linear_velocity.magnitude() > 0.0 || self.angular_velocity.magnitude() > 0.0
             }
-        })
+            BodyType::Static => false,
+        }
+    }
+
+    // Sets the velocities of a kinematic body so that it reaches a target transform over the next dt,
+    // which moves it there while still pushing what it meets along the way. Without any time to get
+    // there the velocities are left as they are.
+    pub fn move_kinematic(&mut self, target: &Transform, dt: f64) {
+        if dt <= 0.0 {
+            return;
+        }
+
+        self.linear_velocity = (target.position - self.transform.position) * (1.0 / dt);
+
+        let mut rotation = target.rotation * self.transform.rotation.inverse();
+        if rotation.w < 0.0 {
+            rotation = rotation * -1.0;
+        }
+        let axis = Vector3::new(rotation.x, rotation.y, rotation.z);
+        let sin = axis.magnitude();
+        self.angular_velocity = if sin > f64::EPSILON {
+            axis * (2.0 * sin.atan2(rotation.w) / (sin * dt))
+        } else {
+            Vector3::default()
+        };
+    }
+
+    // Whether the collision groups and masks of both bodies let them collide
+    pub fn can_collide(&self, other: &Body) -> bool {
+        self.collision_group & other.collision_mask != 0 && other.collision_group & self.collision_mask != 0
+    }
+
+    pub fn wake(&mut self) {
+        self.is_sleeping = false;
+        self.sleep_time = 0.0;
+    }
+
+    pub fn sleep(&mut self) {
+        self.is_sleeping = true;
+        self.linear_velocity = Vector3::default();
+        self.angular_velocity = Vector3::default();
+        self.clear_forces();
+    }
+
+    // Applies a world space force through the center of mass over the next step, waking the body so it
+    // responds to it. The test world only pushes bodies through force generators and impulses.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn apply_force(&mut self, force: Vector3) {
+        self.wake();
+        self.apply_force_no_wake(force);
+    }
+
+    // Force generators leave the body asleep, since they push every awake body each step and would
+    // otherwise keep it from ever falling asleep
+    pub fn apply_force_no_wake(&mut self, force: Vector3) {
+        self.force += force;
+    }
+
+    // Applies a world space force at a world space point, producing a torque about the center of mass
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn apply_force_at_point(&mut self, force: Vector3, point: Vector3) {
+        self.wake();
+        self.apply_force_at_point_no_wake(force, point);
+    }
+
+    pub fn apply_force_at_point_no_wake(&mut self, force: Vector3, point: Vector3) {
+        self.force += force;
+        self.torque += (point - self.transform.position).cross(force);
+    }
+
+    // Applies a world space impulse at a world space point, changing velocities immediately
+    pub fn apply_impulse(&mut self, impulse: Vector3, point: Vector3) {
+        self.wake();
+        self.linear_velocity += impulse * self.inverse_mass;
+        self.apply_angular_impulse((point - self.transform.position).cross(impulse));
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: Vector3) {
+        self.wake();
+        self.angular_velocity += self.inverse_inertia_world() * impulse;
     }
-}
 
-// SAT collision test by checking for separating planes in all 15 axes
-pub fn is_colliding(box1: &Body, box2: &Body) -> bool {
-    let r_pos = box2.transform.position - box1.transform.position;
-
-    let (box1_x_axis, box1_y_axis, box1_z_axis) = box1.transform.rotation.basis_vectors();
-    let (box2_x_axis, box2_y_axis, box2_z_axis) = box2.transform.rotation.basis_vectors();
-
-    !(get_separating_plane(r_pos, box1_x_axis, box1, box2)
-        || get_separating_plane(r_pos, box1_y_axis, box1, box2)
-        || get_separating_plane(r_pos, box1_z_axis, box1, box2)
-        || get_separating_plane(r_pos, box2_x_axis, box1, box2)
-        || get_separating_plane(r_pos, box2_y_axis, box1, box2)
-        || get_separating_plane(r_pos, box2_z_axis, box1, box2)
-        || get_separating_plane(r_pos, box1_x_axis.cross(box2_x_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_x_axis.cross(box2_y_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_x_axis.cross(box2_z_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_y_axis.cross(box2_x_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_y_axis.cross(box2_y_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_y_axis.cross(box2_z_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_z_axis.cross(box2_x_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_z_axis.cross(box2_y_axis), box1, box2)
-        || get_separating_plane(r_pos, box1_z_axis.cross(box2_z_axis), box1, box2))
+    // Scales the velocities down by the given fractions per second. The damping is implicit so that no
+    // amount of it can reverse the velocities.
+    pub fn damp(&mut self, linear: f64, angular: f64, dt: f64) {
+        self.linear_velocity = self.linear_velocity * (1.0 / (1.0 + linear * dt));
+        self.angular_velocity = self.angular_velocity * (1.0 / (1.0 + angular * dt));
+    }
+
+    pub fn clear_forces(&mut self) {
+        self.force = Vector3::default();
+        self.torque = Vector3::default();
+    }
+
+    // First half of a semi-implicit Euler step: advances velocities from gravity and the accumulated
+    // forces, which are cleared afterwards, then damps them. Contact impulses are solved against the new
+    // velocities before integrate_position moves the body with them, which keeps stiff motion stable.
+    // Gravity only affects dynamic bodies with mass and nothing affects sleeping bodies.
+    pub fn integrate_velocity(&mut self, dt: f64, gravity: Vector3) {
+        if self.body_type == BodyType::Dynamic && self.is_active() {
+            let linear_acceleration = gravity + self.force * self.inverse_mass;
+            let angular_acceleration = self.inverse_inertia_world() * self.torque;
+
+            self.linear_velocity += linear_acceleration * dt;
+            self.angular_velocity += angular_acceleration * dt;
+            self.damp(self.linear_damping, self.angular_damping, dt);
+        }
+
+        self.clear_forces();
+    }
+
+    pub fn integrate_position(&mut self, dt: f64) {
+        if self.is_sleeping || self.body_type == BodyType::Static {
+            return;
+        }
+
+        self.transform.position += self.linear_velocity * dt;
+        self.transform.rotation = self.transform.rotation.integrate(self.angular_velocity, dt);
+    }
+
+    // World space bounding box of the rotated shape
+    pub fn aabb(&self) -> Aabb {
+        let min = Vector3::new(
+            self.support(Vector3::X_AXIS.inverse()).x,
+            self.support(Vector3::Y_AXIS.inverse()).y,
+            self.support(Vector3::Z_AXIS.inverse()).z,
+        );
+        let max = Vector3::new(
+            self.support(Vector3::X_AXIS).x,
+            self.support(Vector3::Y_AXIS).y,
+            self.support(Vector3::Z_AXIS).z,
+        );
+        Aabb::new(min, max)
+    }
+
+    // First point where a world space ray hits the body, at most max_distance along the ray
+    pub fn raycast(&self, ray: &Ray, max_distance: f64) -> Option<RayHit> {
+        ray_shape(&ray.to_local_space(&self.transform), &self.shape, max_distance)
+            .map(|hit| hit.to_world_space(&self.transform))
+    }
+
+    pub fn interpolated_transform(&self, alpha: f64) -> Transform {
+        self.previous_transform.interpolate(&self.transform, alpha)
+    }
+
+    // Geometry of the body placed at a transform other than its own, such as an interpolated one
+    pub fn geometry_at<'a>(&'a self, transform: &'a Transform) -> impl Iterator<Item = SceneTriangle> + 'a {
+        self.mesh.get_or_init(|| self.shape.mesh()).geometry(transform)
+    }
 }
 
-fn get_separating_plane(r_pos: Vector3, plane: Vector3, box1: &Body, box2: &Body) -> bool {
-    let (box1_x_axis, box1_y_axis, box1_z_axis) = box1.transform.rotation.basis_vectors();
-    let (box2_x_axis, box2_y_axis, box2_z_axis) = box2.transform.rotation.basis_vectors();
-
-    return r_pos.dot(plane).abs()
-        > (box1_x_axis * box1.half_size.x).dot(plane).abs()
-            + (box1_y_axis * box1.half_size.y).dot(plane).abs()
-            + (box1_z_axis * box1.half_size.z).dot(plane).abs()
-            + (box2_x_axis * box2.half_size.x).dot(plane).abs()
-            + (box2_y_axis * box2.half_size.y).dot(plane).abs()
-            + (box2_z_axis * box2.half_size.z).dot(plane).abs();
+impl SupportMap for Body {
+    // World space point of the shape furthest along a world space direction
+    fn support(&self, direction: Vector3) -> Vector3 {
+        let rotation = self.transform.rotation;
+        self.transform.point_to_world_space(self.shape.support(rotation.vector_to_local_space(direction)))
+    }
 }
 
-pub const BODY_UNIT_GEOMETRY: [SceneTriangle; 12] = [
-    // Front 0
-    SceneTriangle {
-        normal: Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: 1.0,
-        },
-        points: [
-            Vector3 {
-                x: 1.000,
-                y: 1.000,
-                z: 1.0,
-            },
-            Vector3 {
-                x: -1.000,
-                y: 1.000,
-                z: 1.0,
-            },
-            Vector3 {
-                x: -1.000,
-                y: -1.000,
-                z: 1.0,
-            },
-        ],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Front 1
-        normal: Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: 1.0,
-        },
-        points: [
-            Vector3 {
-                x: 1.000,
-                y: 1.000,
-                z: 1.0,
-            },
-            Vector3 {
-                x: -1.000,
-                y: -1.000,
-                z: 1.0,
-            },
-            Vector3 {
-                x: 1.000,
-                y: -1.000,
-                z: 1.0,
-            },
-        ],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Back 0
-        normal: Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: -1.0,
-        },
-        points: [
-            Vector3 {
-                x: 1.000,
-                y: 1.000,
-                z: -1.0,
-            },
-            Vector3 {
-                x: -1.000,
-                y: 1.000,
-                z: -1.0,
-            },
-            Vector3 {
-                x: -1.000,
-                y: -1.000,
-                z: -1.0,
-            },
-        ],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Back 1
-        normal: Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: -1.0,
-        },
-        points: [
-        Vector3 {
-            x: 1.000,
-            y: 1.000,
-            z: -1.0,
-        },
-        Vector3 {
-            x: -1.000,
-            y: -1.000,
-            z: -1.0,
-        },
-        Vector3 {
-            x: 1.000,
-            y: -1.000,
-            z: -1.0,
-        }],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Right 0
-        normal: Vector3 {
-            x: 1.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: 1.0,
-            y: 1.000,
-            z: 1.000,
-        },
-        Vector3 {
-            x: 1.0,
-            y: -1.000,
-            z: 1.000,
-        },
-        Vector3 {
-            x: 1.0,
-            y: -1.000,
-            z: -1.000,
-        }],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Right 1
-        normal: Vector3 {
-            x: 1.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: 1.0,
-            y: 1.000,
-            z: 1.000,
-        },
-        Vector3 {
-            x: 1.0,
-            y: -1.000,
-            z: -1.000,
-        },
-        Vector3 {
-            x: 1.0,
-            y: 1.000,
-            z: -1.000,
-        },],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Left 0
-        normal: Vector3 {
-            x: -1.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: -1.0,
-            y: 1.000,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.0,
-            y: -1.000,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.0,
-            y: -1.000,
-            z: -1.000,
-        },],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Left 1
-        normal: Vector3 {
-            x: -1.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: -1.0,
-            y: 1.000,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.0,
-            y: -1.000,
-            z: -1.000,
-        },
-        Vector3 {
-            x: -1.0,
-            y: 1.000,
-            z: -1.000,
-        },],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Up 0
-        normal: Vector3 {
-            x: 0.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: 1.000,
-            y: 1.0,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.000,
-            y: 1.0,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.000,
-            y: 1.0,
-            z: -1.000,
-        },],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Up 1
-        normal: Vector3 {
-            x: 0.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: 1.000,
-            y: 1.0,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.000,
-            y: 1.0,
-            z: -1.000,
-        },
-        Vector3 {
-            x: 1.000,
-            y: 1.0,
-            z: -1.000,
-        },],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Down 0
-        normal: Vector3 {
-            x: 0.0,
-            y: -1.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: 1.000,
-            y: -1.0,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.000,
-            y: -1.0,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.000,
-            y: -1.0,
-            z: -1.000,
-        },],
-        color: Color::Rgb(255, 255, 255),
-    },
-    SceneTriangle {
-        // Down 1
-        normal: Vector3 {
-            x: 0.0,
-            y: -1.0,
-            z: 0.0,
-        },
-        points: [Vector3 {
-            x: 1.000,
-            y: -1.0,
-            z: 1.000,
-        },
-        Vector3 {
-            x: -1.000,
-            y: -1.0,
-            z: -1.000,
-        },
-        Vector3 {
-            x: 1.000,
-            y: -1.0,
-            z: -1.000,
-        },],
-        color: Color::Rgb(255, 255, 255),
-    },
-];
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAVITY: Vector3 = Vector3 { x: 0.0, y: -10.0, z: 0.0 };
+
+    fn sphere(mass: f64) -> Body {
+        Body::new(Transform::default(), Shape::Sphere { radius: 1.0 }, mass)
+    }
+
+    // Advances the body by whole steps the way the physics world does, velocity first
+    fn integrate(body: &mut Body, steps: usize, dt: f64) {
+        for _ in 0..steps {
+            body.integrate_velocity(dt, GRAVITY);
+            body.integrate_position(dt);
+        }
+    }
+
+    #[test]
+    fn falling_bodies_move_with_their_updated_velocity() {
+        let (steps, dt) = (30, 0.1);
+        let mut body = sphere(1.0);
+        integrate(&mut body, steps, dt);
+
+        // Semi-implicit Euler moves by the new velocity each step, so the drop is g dt^2 n (n + 1) / 2
+        let n = steps as f64;
+        assert!((body.linear_velocity - GRAVITY * (n * dt)).magnitude() < 1e-9);
+        assert!((body.transform.position - GRAVITY * (dt * dt * n * (n + 1.0) / 2.0)).magnitude() < 1e-9);
+
+        let mut ground = Body::new_static(Transform::default(), Shape::Sphere { radius: 1.0 });
+        let mut sleeping = sphere(1.0);
+        sleeping.sleep();
+        for body in [&mut ground, &mut sleeping] {
+            integrate(body, steps, dt);
+            assert!(body.transform.position.magnitude() == 0.0 && body.linear_velocity.magnitude() == 0.0);
+        }
+    }
+
+    #[test]
+    fn forces_act_for_one_step_and_damping_takes_a_fraction_per_second() {
+        let dt = 0.1;
+        let mut body = sphere(2.0);
+        body.apply_force(Vector3::new(4.0, 0.0, 0.0));
+        body.integrate_velocity(dt, Vector3::default());
+        assert!((body.linear_velocity - Vector3::new(0.2, 0.0, 0.0)).magnitude() < 1e-12);
+        assert!(body.force.magnitude() == 0.0 && body.torque.magnitude() == 0.0);
+
+        body.linear_velocity = Vector3::new(10.0, 0.0, 0.0);
+        body.angular_velocity = Vector3::new(0.0, 10.0, 0.0);
+        body.linear_damping = 0.5;
+        body.angular_damping = 2.0;
+        body.integrate_velocity(dt, Vector3::default());
+        assert!((body.linear_velocity.x - 10.0 / 1.05).abs() < 1e-12);
+        assert!((body.angular_velocity.y - 10.0 / 1.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn spinning_bodies_turn_by_their_angular_velocity() {
+        let mut body = sphere(1.0);
+        body.angular_velocity = Vector3::new(0.0, 0.5 * std::f64::consts::PI, 0.0);
+        integrate(&mut body, 1000, 0.001);
+
+        // A quarter turn about y takes x to -z
+        let x_axis = body.transform.rotation.vector_to_world_space(Vector3::X_AXIS);
+        assert!((x_axis - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-3, "x axis turned to {x_axis:?}");
+    }
+
+    #[test]
+    fn small_light_bodies_can_still_turn() {
+        // A marble with an inertia tensor whose determinant is far below machine epsilon
+        let (radius, mass) = (0.02, 0.01);
+        let mut body = Body::new(Transform::default(), Shape::Sphere { radius }, mass);
+        let expected = 1.0 / (0.4 * mass * radius * radius);
+        let axes = [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS];
+        for (axis, row) in axes.into_iter().zip(body.inverse_inertia.rows) {
+            assert!((row - axis * expected).magnitude() < 1e-9 * expected);
+        }
+
+        body.apply_angular_impulse(Vector3::new(0.0, 1e-6, 0.0));
+        assert!((body.angular_velocity.y - 1e-6 * expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn default_bodies_collide_like_new_ones() {
+        let default = Body::default();
+        let new = sphere(1.0);
+        assert!(default.can_collide(&new) && new.can_collide(&default));
+        assert_eq!((default.restitution, default.friction), (new.restitution, new.friction));
+    }
+
+    #[test]
+    fn kinematic_bodies_reach_the_target_they_are_moved_to() {
+        let mut body = Body::new_kinematic(
+            Transform::new(Vector3::new(1.0, 2.0, 3.0), Quaternion::default()),
+            Shape::Sphere { radius: 1.0 },
+        );
+        let axis = Vector3::new(1.0, 2.0, 2.0).unit();
+        let target = Transform::new(Vector3::new(2.0, 0.0, 3.5), Quaternion::from_axis_angle(axis, 0.1));
+
+        let dt = 0.5;
+        body.move_kinematic(&target, dt);
+        assert!((body.angular_velocity - axis * (0.1 / dt)).magnitude() < 1e-9);
+        assert!(body.is_active());
+
+        body.integrate_position(dt);
+        assert!((body.transform.position - target.position).magnitude() < 1e-9);
+        assert!(body.transform.rotation.dot(target.rotation) > 1.0 - 1e-4);
+
+        // A frame that took no time can't move the body anywhere
+        let velocity = body.linear_velocity;
+        body.move_kinematic(&Transform::default(), 0.0);
+        assert!((body.linear_velocity - velocity).magnitude() == 0.0);
+        assert!(body.angular_velocity.magnitude().is_finite());
+    }
+
+    #[test]
+    fn forces_only_wake_the_body_when_asked_to() {
+        let mut body = Body::new(Transform::default(), Shape::Sphere { radius: 1.0 }, 2.0);
+        body.sleep();
+
+        body.apply_force_no_wake(Vector3::X_AXIS);
+        body.apply_force_at_point_no_wake(Vector3::X_AXIS, Vector3::Y_AXIS);
+        assert!(body.is_sleeping);
+        assert!((body.force - Vector3::new(2.0, 0.0, 0.0)).magnitude() < 1e-9);
+        assert!((body.torque - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-9);
+
+        body.apply_force(Vector3::X_AXIS);
+        assert!(!body.is_sleeping);
+
+        body.sleep();
+        body.apply_force_at_point(Vector3::X_AXIS, Vector3::Y_AXIS);
+        assert!(!body.is_sleeping);
+        assert!((body.torque - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-9);
+    }
+}
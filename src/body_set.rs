@@ -0,0 +1,182 @@
+use crate::body::Body;
+
+use std::ops::{Index, IndexMut};
+
+// Stable reference to a body in a body set. The generation tells apart the bodies that reuse the slot
+// of a removed one, so a handle to a removed body never finds the body that replaced it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BodyHandle {
+    slot: usize,
+    generation: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    generation: u32,
+    // Position of the body in the dense list, None while the slot is free
+    index: Option<usize>,
+}
+
+// Slot map of bodies. The bodies themselves are kept packed together in a list so the solver and the
+// broadphase can work on a plain slice, with the slots mapping handles to positions in that list.
+// Removing a body moves the last body into its place, so positions are only stable until the next
+// removal, while handles stay valid until their own body is removed.
+#[derive(Default)]
+pub struct BodySet {
+    bodies: Vec<Body>,
+    // Handle of the body at each position of the dense list
+    handles: Vec<BodyHandle>,
+    slots: Vec<Slot>,
+    // Free slots, the most recently freed last so it's reused first
+    free_slots: Vec<usize>,
+}
+
+impl BodySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, body: Body) -> BodyHandle {
+        let index = self.bodies.len();
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot].index = Some(index);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    index: Some(index),
+                });
+                self.slots.len() - 1
+            }
+        };
+
+        let handle = BodyHandle {
+            slot,
+            generation: self.slots[slot].generation,
+        };
+        self.bodies.push(body);
+        self.handles.push(handle);
+        handle
+    }
+
+    // Removes the body and returns it, or None if the handle's body was already removed
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<Body> {
+        let index = self.index_of(handle)?;
+
+        let slot = &mut self.slots[handle.slot];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.index = None;
+        self.free_slots.push(handle.slot);
+
+        // The last body moves into the gap
+        self.handles.swap_remove(index);
+        if let Some(moved) = self.handles.get(index) {
+            self.slots[moved.slot].index = Some(index);
+        }
+        Some(self.bodies.swap_remove(index))
+    }
+
+    pub fn get(&self, handle: BodyHandle) -> Option<&Body> {
+        self.index_of(handle).map(|index| &self.bodies[index])
+    }
+
+    pub fn get_mut(&mut self, handle: BodyHandle) -> Option<&mut Body> {
+        self.index_of(handle).map(|index| &mut self.bodies[index])
+    }
+
+    // Position of the body in the dense list, valid until the next removal
+    pub fn index_of(&self, handle: BodyHandle) -> Option<usize> {
+        self.slots
+            .get(handle.slot)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.index)
+    }
+
+    // Handle of the body at a position of the dense list
+    pub fn handle(&self, index: usize) -> BodyHandle {
+        self.handles[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn as_slice(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [Body] {
+        &mut self.bodies
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BodyHandle, &Body)> + '_ {
+        self.handles.iter().copied().zip(self.bodies.iter())
+    }
+}
+
+// Indexing by a handle whose body was removed panics, like indexing a slice out of bounds
+impl Index<BodyHandle> for BodySet {
+    type Output = Body;
+
+    fn index(&self, handle: BodyHandle) -> &Body {
+        self.get(handle).expect("body handle should refer to a body in the set")
+    }
+}
+
+impl IndexMut<BodyHandle> for BodySet {
+    fn index_mut(&mut self, handle: BodyHandle) -> &mut Body {
+        self.get_mut(handle).expect("body handle should refer to a body in the set")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{shape::Shape, transform::*};
+
+    fn body_at(x: f64) -> Body {
+        Body::new(
+            Transform::new(Vector3::new(x, 0.0, 0.0), Quaternion::default()),
+            Shape::Sphere { radius: 1.0 },
+            1.0,
+        )
+    }
+
+    #[test]
+    fn handles_of_removed_bodies_go_stale() {
+        let mut bodies = BodySet::new();
+        let [first, second, third] = [0.0, 1.0, 2.0].map(|x| bodies.insert(body_at(x)));
+
+        assert_eq!(bodies.remove(first).map(|body| body.transform.position.x), Some(0.0));
+        assert!(bodies.remove(first).is_none());
+        assert!(bodies.get(first).is_none() && bodies.index_of(first).is_none());
+
+        // The last body moved into the gap and keeps its handle
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies.index_of(third), Some(0));
+        assert_eq!(bodies.handle(0), third);
+        assert_eq!(bodies[third].transform.position.x, 2.0);
+        assert_eq!(bodies[second].transform.position.x, 1.0);
+
+        // A new body reuses the freed slot without reviving the old handle
+        let fourth = bodies.insert(body_at(3.0));
+        assert_ne!(fourth, first);
+        assert!(bodies.get_mut(first).is_none());
+        assert_eq!(bodies[fourth].transform.position.x, 3.0);
+
+        let handles: Vec<BodyHandle> = bodies.iter().map(|(handle, _)| handle).collect();
+        assert_eq!(handles, [third, second, fourth]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn indexing_by_a_stale_handle_panics() {
+        let mut bodies = BodySet::new();
+        let handle = bodies.insert(body_at(0.0));
+        bodies.remove(handle);
+        let _ = &bodies[handle];
+    }
+}
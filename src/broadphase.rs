@@ -0,0 +1,246 @@
+use crate::transform::*;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    pub fn contains_point(&self, point: Vector3) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    // Distance along the ray at which it enters the box, zero when it starts inside
+    pub fn ray_distance(&self, ray: &Ray, max_distance: f64) -> Option<f64> {
+        let (mut enter, mut exit) = (0.0_f64, max_distance);
+        for (origin, direction, min, max) in [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ] {
+            if direction == 0.0 {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+
+            let (t1, t2) = ((min - origin) / direction, (max - origin) / direction);
+            enter = enter.max(t1.min(t2));
+            exit = exit.min(t1.max(t2));
+            if enter > exit {
+                return None;
+            }
+        }
+
+        Some(enter)
+    }
+
+    pub fn expand(&self, margin: f64) -> Aabb {
+        let margin = Vector3::new(margin, margin, margin);
+        Aabb {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+}
+
+// Sweep and prune along the x axis. The sorted order is kept between calls so that, as bodies only move
+// a little each step, the insertion sort that restores it runs in close to linear time.
+#[derive(Default)]
+pub struct SweepAndPrune {
+    order: Vec<usize>,
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Writes every pair (i, j) with i < j whose boxes overlap, sorted so the result doesn't depend on
+    // the order the sweep visits them in
+    pub fn find_pairs(&mut self, aabbs: &[Aabb], pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+        sort_by_start(&mut self.order, aabbs);
+
+        for (i, &a) in self.order.iter().enumerate() {
+            for &b in self.order[i + 1..].iter() {
+                // Every later box starts past the end of this one on the sweep axis
+                if aabbs[b].min.x > aabbs[a].max.x {
+                    break;
+                }
+
+                if aabbs[a].overlaps(&aabbs[b]) {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+
+        pairs.sort_unstable();
+    }
+
+    // Writes every box the ray passes through within max_distance as the distance at which the ray enters
+    // it and its index, nearest first. The kept order is sorted as a copy, which is cheap since it is
+    // already close to sorted, so rays can be cast without changing the broadphase.
+    pub fn find_ray_hits(&self, aabbs: &[Aabb], ray: &Ray, max_distance: f64, hits: &mut Vec<(f64, usize)>) {
+        hits.clear();
+        let mut order = self.order.clone();
+        sort_by_start(&mut order, aabbs);
+
+        // Only boxes overlapping the extent of the ray on the sweep axis can be hit
+        let end = ray.at(max_distance).x;
+        let (low, high) = (ray.origin.x.min(end), ray.origin.x.max(end));
+        for &index in order.iter() {
+            if aabbs[index].min.x > high {
+                break;
+            }
+
+            if aabbs[index].max.x >= low {
+                if let Some(distance) = aabbs[index].ray_distance(ray, max_distance) {
+                    hits.push((distance, index));
+                }
+            }
+        }
+
+        hits.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    }
+}
+
+// Restores an order of the boxes by their start on the sweep axis. Ties are broken by index so the order
+// is fully determined by the boxes.
+fn sort_by_start(order: &mut Vec<usize>, aabbs: &[Aabb]) {
+    if order.len() != aabbs.len() {
+        *order = (0..aabbs.len()).collect();
+    }
+
+    let is_before = |a: usize, b: usize| {
+        let (a_min, b_min) = (aabbs[a].min.x, aabbs[b].min.x);
+        a_min < b_min || a_min == b_min && a < b
+    };
+
+    for i in 1..order.len() {
+        let mut j = i;
+        while j > 0 && is_before(order[j], order[j - 1]) {
+            order.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small xorshift generator, so the boxes are scattered the same way on every run
+    struct Random(u64);
+
+    impl Random {
+        // Uniform in [-range, range)
+        fn next(&mut self, range: f64) -> f64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            ((self.0 >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0) * range
+        }
+
+        fn vector(&mut self, range: f64) -> Vector3 {
+            Vector3::new(self.next(range), self.next(range), self.next(range))
+        }
+
+        fn aabb(&mut self) -> Aabb {
+            let center = self.vector(10.0);
+            let half_size = self.vector(3.0);
+            let half_size = Vector3::new(half_size.x.abs(), half_size.y.abs(), half_size.z.abs());
+            Aabb::new(center - half_size, center + half_size)
+        }
+    }
+
+    fn brute_force_pairs(aabbs: &[Aabb]) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..aabbs.len() {
+            for j in i + 1..aabbs.len() {
+                if aabbs[i].overlaps(&aabbs[j]) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn sweep_and_prune_finds_the_same_pairs_and_ray_hits_as_testing_every_box() {
+        let mut random = Random(0x2545f4914f6cdd1d);
+        let mut aabbs: Vec<Aabb> = (0..40).map(|_| random.aabb()).collect();
+        let mut broadphase = SweepAndPrune::new();
+        let (mut pairs, mut hits) = (Vec::new(), Vec::new());
+        let (mut pair_count, mut hit_count) = (0, 0);
+
+        for round in 0..30 {
+            // Boxes move a little most rounds and jump across the world on some, and bodies are removed by
+            // swapping the last one into their place and added at the end like in the body set
+            for aabb in aabbs.iter_mut() {
+                let offset = if round % 7 == 3 { random.vector(10.0) } else { random.vector(1.0) };
+                *aabb = Aabb::new(aabb.min + offset, aabb.max + offset);
+            }
+            if round % 3 == 1 {
+                let index = (random.next(1.0).abs() * aabbs.len() as f64) as usize;
+                aabbs.swap_remove(index.min(aabbs.len() - 1));
+            }
+            if round % 5 == 2 {
+                aabbs.push(random.aabb());
+            }
+
+            broadphase.find_pairs(&aabbs, &mut pairs);
+            assert_eq!(pairs, brute_force_pairs(&aabbs), "pairs differ in round {round}");
+            pair_count += pairs.len();
+
+            // Rays start around the boxes and head through the middle of them
+            for _ in 0..4 {
+                let origin = random.vector(20.0);
+                let ray = Ray::new(origin, random.vector(5.0) - origin);
+                let max_distance = 40.0;
+                broadphase.find_ray_hits(&aabbs, &ray, max_distance, &mut hits);
+                let mut expected: Vec<(f64, usize)> = (0..aabbs.len())
+                    .filter_map(|index| Some((aabbs[index].ray_distance(&ray, max_distance)?, index)))
+                    .collect();
+                expected.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+                assert_eq!(hits, expected, "ray hits differ in round {round}");
+                hit_count += hits.len();
+            }
+        }
+
+        // The boxes are packed closely enough for the comparison to cover plenty of overlaps
+        assert!(pair_count > 100 && hit_count > 100, "{pair_count} pairs and {hit_count} hits");
+    }
+}
@@ -0,0 +1,391 @@
+use crate::{
+    body::{Body, BodyType},
+    broadphase::Aabb,
+    collision::Plane,
+    gjk::{self, GjkResult, SupportMap},
+    shape::Shape,
+    solver::combine_materials,
+    transform::*,
+};
+
+// Gap left between a bullet and what it hits, so the discrete contact generation picks the pair up on the
+// next step without the bullet having passed into it
+const CCD_TOLERANCE: f64 = 0.05;
+const CCD_MAX_ITERATIONS: usize = 32;
+// Bodies that start a step touching still impact when they close by more than this fraction of the
+// narrowest width of the bullet over the step, which the discrete contacts would only catch once the
+// bullet is already through thin geometry
+const CCD_MAX_STEP_PENETRATION: f64 = 0.25;
+
+// What a bullet hits, by index
+#[derive(Clone, Copy, Debug)]
+enum Obstacle {
+    Body(usize),
+    Plane(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TimeOfImpact {
+    // Fraction of the timestep at which the bodies touch
+    pub time: f64,
+    // World space normal from the bullet towards what it hits
+    pub normal: Vector3,
+    pub point: Vector3,
+}
+
+// Shape placed at the transform a body reaches partway through a step
+struct Posed<'a> {
+    shape: &'a Shape,
+    transform: Transform,
+}
+
+impl<'a> Posed<'a> {
+    fn new(body: &'a Body, dt: f64) -> Posed<'a> {
+        Posed {
+            shape: &body.shape,
+            transform: Transform {
+                position: body.transform.position + body.linear_velocity * dt,
+                rotation: body.transform.rotation.integrate(body.angular_velocity, dt),
+            },
+        }
+    }
+}
+
+impl SupportMap for Posed<'_> {
+    fn support(&self, direction: Vector3) -> Vector3 {
+        let rotation = self.transform.rotation;
+        self.transform.point_to_world_space(self.shape.support(rotation.vector_to_local_space(direction)))
+    }
+}
+
+// Earliest time within the step at which a bullet moving with its current velocities touches another
+// body, by conservative advancement: both bodies are advanced by the largest time in which they cannot
+// close the gap between them, until the gap is within tolerance. Returns None when they don't meet
+// within the step or already touch at its start slowly enough for the discrete contacts to handle.
+pub fn time_of_impact(bullet: &Body, other: &Body, dt: f64) -> Option<TimeOfImpact> {
+    // Upper bound on how fast rotation can move a surface point towards the other body
+    let angular_bound = bullet.angular_velocity.magnitude() * bullet.shape.bounding_radius()
+        + other.angular_velocity.magnitude() * other.shape.bounding_radius();
+    let relative_velocity = bullet.linear_velocity - other.linear_velocity;
+    let max_step_penetration = max_step_penetration(bullet);
+
+    let mut time = 0.0;
+    let mut last = None;
+    for _ in 0..CCD_MAX_ITERATIONS {
+        let (bullet_posed, other_posed) = (Posed::new(bullet, time * dt), Posed::new(other, time * dt));
+        let (point_a, point_b) = match gjk::gjk(&bullet_posed, &other_posed) {
+            GjkResult::Separated { point_a, point_b } => (point_a, point_b),
+            GjkResult::Intersecting(_) => return last,
+        };
+
+        let distance = (point_b - point_a).magnitude();
+        if distance <= f64::EPSILON {
+            return last;
+        }
+
+        let impact = TimeOfImpact {
+            time,
+            normal: (point_b - point_a) * (1.0 / distance),
+            point: (point_a + point_b) * 0.5,
+        };
+        let linear_speed = relative_velocity.dot(impact.normal);
+        if distance <= CCD_TOLERANCE {
+            return (time > 0.0 || linear_speed * dt > max_step_penetration).then_some(impact);
+        }
+
+        let closing_speed = linear_speed + angular_bound;
+        if closing_speed <= 0.0 {
+            return None;
+        }
+
+        time += (distance - 0.5 * CCD_TOLERANCE) / (closing_speed * dt);
+        if time >= 1.0 {
+            return None;
+        }
+        last = Some(impact);
+    }
+
+    last
+}
+
+// Earliest time within the step at which a bullet touches a plane, found the same way as between bodies
+pub fn time_of_impact_plane(bullet: &Body, plane: &Plane, dt: f64) -> Option<TimeOfImpact> {
+    let angular_bound = bullet.angular_velocity.magnitude() * bullet.shape.bounding_radius();
+    let max_step_penetration = max_step_penetration(bullet);
+
+    let mut time = 0.0;
+    for _ in 0..CCD_MAX_ITERATIONS {
+        let point = Posed::new(bullet, time * dt).support(plane.normal.inverse());
+        let distance = plane.signed_distance(point);
+        let linear_speed = -bullet.linear_velocity.dot(plane.normal);
+        if distance <= CCD_TOLERANCE {
+            return (time > 0.0 || linear_speed * dt > max_step_penetration).then_some(TimeOfImpact {
+                time,
+                normal: plane.normal.inverse(),
+                point,
+            });
+        }
+
+        let closing_speed = linear_speed + angular_bound;
+        if closing_speed <= 0.0 {
+            return None;
+        }
+
+        time += (distance - 0.5 * CCD_TOLERANCE) / (closing_speed * dt);
+        if time >= 1.0 {
+            return None;
+        }
+    }
+
+    None
+}
+
+// How far a bullet that starts a step touching something can close in on it before that counts as an
+// impact, from the width of the bullet along the narrowest of its local axes
+fn max_step_penetration(bullet: &Body) -> f64 {
+    let shape = &bullet.shape;
+    let width = |axis: Vector3| (shape.support(axis) - shape.support(axis.inverse())).dot(axis);
+    let narrowest = [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS]
+        .into_iter()
+        .map(width)
+        .fold(f64::INFINITY, f64::min);
+    CCD_MAX_STEP_PENETRATION * narrowest
+}
+
+// Finds the first impact of every awake bullet during the step, moves the bullet up to it and bounces it
+// off what it hits, writing the fraction of the step each body still has to move to fractions. A bullet
+// that hit something stays at the impact for the rest of the step instead of moving on, which loses a
+// little time but never tunnels.
+pub fn resolve_bullet_impacts(bodies: &mut [Body], planes: &[Plane], dt: f64, fractions: &mut Vec<f64>) {
+    fractions.clear();
+    fractions.resize(bodies.len(), 1.0);
+
+    // Bounding boxes of the bodies swept over the step, grown by how far rotation can move them
+    let swept: Vec<Aabb> = bodies
+        .iter()
+        .map(|body| {
+            let aabb = body.aabb();
+            let offset = body.linear_velocity * dt;
+            let rotation = body.angular_velocity.magnitude() * body.shape.bounding_radius() * dt;
+            aabb.union(&Aabb::new(aabb.min + offset, aabb.max + offset))
+                .expand(rotation + CCD_TOLERANCE)
+        })
+        .collect();
+
+    for index in 0..bodies.len() {
+        let bullet = &bodies[index];
+        let dynamic = bullet.body_type == BodyType::Dynamic;
+        if !bullet.is_bullet || !dynamic || !bullet.is_active() || bullet.is_sensor {
+            continue;
+        }
+
+        let mut earliest: Option<(TimeOfImpact, Obstacle)> = None;
+        let mut consider = |impact: Option<TimeOfImpact>, other: Obstacle| {
+            if let Some(impact) = impact {
+                if earliest.is_none_or(|(earliest, _)| impact.time < earliest.time) {
+                    earliest = Some((impact, other));
+                }
+            }
+        };
+
+        for (plane_index, plane) in planes.iter().enumerate() {
+            consider(time_of_impact_plane(bullet, plane, dt), Obstacle::Plane(plane_index));
+        }
+        for other in 0..bodies.len() {
+            let obstacle = &bodies[other];
+            if other != index
+                && !obstacle.is_sensor
+                && bullet.can_collide(obstacle)
+                && swept[index].overlaps(&swept[other])
+            {
+                consider(time_of_impact(bullet, obstacle, dt), Obstacle::Body(other));
+            }
+        }
+
+        if let Some((impact, other)) = earliest {
+            bodies[index].integrate_position(impact.time * dt);
+            fractions[index] = 0.0;
+            apply_impact_impulse(bodies, planes, index, other, &impact, impact.time * dt);
+        }
+    }
+}
+
+// Bounces a bullet that has been moved to its impact off the body or plane it hits, as a single contact
+// with the combined restitution. The body hit is still where it was the given time before the impact.
+fn apply_impact_impulse(
+    bodies: &mut [Body],
+    planes: &[Plane],
+    bullet: usize,
+    other: Obstacle,
+    impact: &TimeOfImpact,
+    time: f64,
+) {
+    let normal = impact.normal;
+    let other_body = match other {
+        Obstacle::Body(other) => Some(&bodies[other]),
+        Obstacle::Plane(_) => None,
+    };
+
+    // Offsets of the impact point from the centers of mass at the time of impact
+    let bullet_arm = impact.point - bodies[bullet].transform.position;
+    let other_arm = other_body.map_or(Vector3::default(), |body| {
+        impact.point - (body.transform.position + body.linear_velocity * time)
+    });
+
+    // Speed at which the bodies close along the normal at the given offsets, and the inverse effective
+    // mass of the pair along the normal there
+    let closing = |bullet_arm: Vector3, other_arm: Vector3| {
+        let bullet_body = &bodies[bullet];
+        let mut velocity = bullet_body.linear_velocity + bullet_body.angular_velocity.cross(bullet_arm);
+        let mut inverse_mass = bullet_body.inverse_mass
+            + (bullet_body.inverse_inertia_world() * bullet_arm.cross(normal)).cross(bullet_arm).dot(normal);
+        if let Some(body) = other_body {
+            velocity -= body.linear_velocity + body.angular_velocity.cross(other_arm);
+            let angular = (body.inverse_inertia_world() * other_arm.cross(normal)).cross(other_arm);
+            inverse_mass += body.inverse_mass + angular.dot(normal);
+        }
+        (velocity.dot(normal), inverse_mass)
+    };
+
+    // A spinning bullet can touch with a point that moves away while its center still closes in, in
+    // which case the impulse goes through the centers so the bullet can't carry on through
+    let (mut bullet_arm, mut other_arm) = (bullet_arm, other_arm);
+    let (mut closing_speed, mut inverse_mass) = closing(bullet_arm, other_arm);
+    if closing_speed <= 0.0 {
+        (bullet_arm, other_arm) = (Vector3::default(), Vector3::default());
+        (closing_speed, inverse_mass) = closing(bullet_arm, other_arm);
+    }
+    if closing_speed <= 0.0 || inverse_mass <= 0.0 {
+        return;
+    }
+
+    let bullet_material = (bodies[bullet].restitution, bodies[bullet].friction);
+    let other_material = match other {
+        Obstacle::Body(other) => (bodies[other].restitution, bodies[other].friction),
+        Obstacle::Plane(plane) => (planes[plane].restitution, planes[plane].friction),
+    };
+    let (restitution, _) =
+        combine_materials(bullet_material.0, bullet_material.1, other_material.0, other_material.1);
+
+    let impulse = normal * ((1.0 + restitution) * closing_speed / inverse_mass);
+    let bullet_position = bodies[bullet].transform.position;
+    bodies[bullet].apply_impulse(impulse.inverse(), bullet_position + bullet_arm);
+    if let Obstacle::Body(other) = other {
+        if bodies[other].inverse_mass > 0.0 {
+            let other_position = bodies[other].transform.position;
+            bodies[other].apply_impulse(impulse, other_position + other_arm);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::physics::PhysicsWorld;
+
+    const DT: f64 = 1.0 / 60.0;
+
+    fn bullet(position: Vector3, radius: f64, velocity: Vector3) -> Body {
+        Body {
+            linear_velocity: velocity,
+            is_bullet: true,
+            ..Body::new(
+                Transform::new(position, Quaternion::default()),
+                Shape::Sphere { radius },
+                1.0,
+            )
+        }
+    }
+
+    fn slab(half_thickness: f64) -> Body {
+        Body::new_static(
+            Transform::default(),
+            Shape::Box {
+                half_size: Vector3::new(10.0, half_thickness, 10.0),
+            },
+        )
+    }
+
+    // Lowest height the body reaches over a second of steps in a world without gravity
+    fn lowest_height(mut world: PhysicsWorld, body: Body) -> f64 {
+        world.gravity = Vector3::default();
+        let handle = world.add_body(body);
+        (0..60)
+            .map(|_| {
+                world.step();
+                world.bodies[handle].transform.position.y
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn bullets_stop_at_thin_slabs_instead_of_passing_through() {
+        // The bullet covers fifteen times the thickness of the slab in a step
+        let velocity = Vector3::new(0.0, -900.0, 0.0);
+        let shot = bullet(Vector3::new(0.0, 5.0, 0.0), 0.5, velocity);
+
+        let impact = time_of_impact(&shot, &slab(0.5), DT).expect("the bullet should hit the slab");
+        assert!((impact.time - 4.0 / 15.0).abs() < CCD_TOLERANCE / 15.0, "hits at {}", impact.time);
+        assert!(impact.normal.dot(Vector3::Y_AXIS) < -1.0 + 1e-6);
+
+        let mut world = PhysicsWorld::new();
+        world.add_body(slab(0.5));
+        assert!(lowest_height(world, shot) > 1.0 - CCD_TOLERANCE);
+
+        // Without continuous collision detection the same shot ends up under the slab
+        let mut world = PhysicsWorld::new();
+        world.add_body(slab(0.5));
+        let shot = bullet(Vector3::new(0.0, 5.0, 0.0), 0.5, velocity);
+        assert!(lowest_height(world, Body { is_bullet: false, ..shot }) < -1.0);
+    }
+
+    #[test]
+    fn bullets_stop_at_planes() {
+        let plane = Plane::new(Vector3::Y_AXIS, Vector3::default());
+        let shot = bullet(Vector3::new(0.0, 5.0, 0.0), 0.5, Vector3::new(300.0, -900.0, 0.0));
+
+        let impact = time_of_impact_plane(&shot, &plane, DT).expect("the bullet should hit the plane");
+        assert!((impact.time - 0.3).abs() < CCD_TOLERANCE / 15.0, "hits at {}", impact.time);
+        assert!(impact.normal.dot(Vector3::Y_AXIS) < -1.0 + 1e-9);
+        assert!(impact.point.y.abs() < CCD_TOLERANCE + 1e-9);
+
+        let mut world = PhysicsWorld::new();
+        world.add_plane(plane);
+        assert!(lowest_height(world, shot) > 0.5 - CCD_TOLERANCE);
+    }
+
+    #[test]
+    fn bullets_that_glance_past_an_edge_carry_on() {
+        // Passes a tenth of a unit beyond the edge of the slab, twice the gap bullets are stopped at
+        let velocity = Vector3::new(0.0, -900.0, 0.0);
+        let shot = bullet(Vector3::new(10.6, 5.0, 0.0), 0.5, velocity);
+        assert!(time_of_impact(&shot, &slab(0.5), DT).is_none());
+
+        let mut world = PhysicsWorld::new();
+        world.gravity = Vector3::default();
+        world.add_body(slab(0.5));
+        let handle = world.add_body(shot);
+        for _ in 0..3 {
+            world.step();
+        }
+        let body = &world.bodies[handle];
+        assert!((body.transform.position - Vector3::new(10.6, 5.0 - 45.0, 0.0)).magnitude() < 1e-9);
+        assert!((body.linear_velocity - velocity).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn touching_bullets_only_impact_when_they_close_in_by_a_fraction_of_their_size() {
+        // Both bullets start within the contact gap of the slab and close in on it by the same distance
+        // over the step, which is a lot for the small one and little for the large one
+        let velocity = Vector3::new(0.0, -24.0, 0.0);
+        for (radius, impacts) in [(0.5, true), (2.0, false)] {
+            let gap = 0.5 * CCD_TOLERANCE;
+            let shot = bullet(Vector3::new(0.0, 0.5 + radius + gap, 0.0), radius, velocity);
+            assert_eq!(time_of_impact(&shot, &slab(0.5), DT).is_some(), impacts, "radius {radius}");
+
+            let plane = Plane::new(Vector3::Y_AXIS, Vector3::new(0.0, 0.5, 0.0));
+            assert_eq!(time_of_impact_plane(&shot, &plane, DT).is_some(), impacts, "radius {radius}");
+        }
+    }
+}
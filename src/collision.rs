@@ -0,0 +1,1332 @@
+use crate::{
+    body::*,
+    broadphase::*,
+    gjk::{self, GjkResult, SupportMap},
+    raster::SceneTriangle,
+    shape::*,
+    solver::*,
+    transform::*,
+};
+
+use tui::style::Color;
+
+// Relative tolerance that makes the SAT prefer face axes over nearly equal edge axes, and the faces of
+// the first box over the second. This keeps the chosen axis from flickering between frames when boxes
+// rest on each other, which would otherwise make the contact points jitter.
+const FACE_AXIS_BIAS: f64 = 0.95;
+const FACE_AXIS_ABSOLUTE_BIAS: f64 = 0.01;
+
+// Cross products of nearly parallel edges are too short to be a meaningful separating axis
+const EDGE_AXIS_EPSILON: f64 = 1e-6;
+
+const MAX_CONTACT_POINTS: usize = 4;
+
+// How far to each side of a plane normal shapes without vertices are probed for points touching the plane
+const SUPPORT_TILT: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ContactPoint {
+    // World space point halfway between the two surfaces
+    pub position: Vector3,
+    pub depth: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContactManifold {
+    // Unit normal pointing from the first body towards the second
+    pub normal: Vector3,
+    pub points: Vec<ContactPoint>,
+}
+
+// Oriented box with its basis vectors computed once for every axis test
+struct OrientedBox {
+    center: Vector3,
+    axes: [Vector3; 3],
+    half_size: [f64; 3],
+}
+
+impl OrientedBox {
+    fn new(transform: &Transform, half_size: Vector3) -> OrientedBox {
+        let (x, y, z) = transform.rotation.basis_vectors();
+        OrientedBox {
+            center: transform.position,
+            axes: [x, y, z],
+            half_size: [half_size.x, half_size.y, half_size.z],
+        }
+    }
+
+    // Half length of the projection of the box onto an axis
+    fn projected_radius(&self, axis: Vector3) -> f64 {
+        (0..3)
+            .map(|i| self.half_size[i] * self.axes[i].dot(axis).abs())
+            .sum()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SeparatingAxis {
+    Box1Face(usize),
+    Box2Face(usize),
+    Edges(usize, usize),
+}
+
+// Narrowphase for any pair of shapes, dispatching to the test for the kind of each shape: boxes use the
+// dedicated box SAT, spheres and capsules against each other the segment at their core, hulls a general
+// polyhedron SAT and everything else GJK/EPA
+pub fn collide(body1: &Body, body2: &Body) -> Option<ContactManifold> {
+    if let (Shape::Box { half_size: half_size1 }, Shape::Box { half_size: half_size2 }) =
+        (&body1.shape, &body2.shape)
+    {
+        return collide_boxes(
+            &OrientedBox::new(&body1.transform, *half_size1),
+            &OrientedBox::new(&body2.transform, *half_size2),
+        );
+    }
+
+    let (round1, round2) = (RoundCore::new(body1), RoundCore::new(body2));
+    let (polyhedron1, polyhedron2) = (Polyhedron::new(body1), Polyhedron::new(body2));
+    match (round1, round2, polyhedron1, polyhedron2) {
+        (Some(a), Some(b), ..) => collide_rounds(&a, &b),
+        (Some(a), None, _, Some(b)) => collide_round_polyhedron(&a, &b),
+        (None, Some(b), Some(a), _) => collide_round_polyhedron(&b, &a).map(flip_manifold),
+        (None, None, Some(a), Some(b)) => collide_polyhedra(&a, &b),
+        // Custom shapes, keeping spheres and capsules rounded around their core
+        (Some(a), None, ..) => collide_convex(&a, a.radius, body2, 0.0),
+        (None, Some(b), ..) => collide_convex(body1, 0.0, &b, b.radius),
+        (None, None, ..) => collide_convex(body1, 0.0, body2, 0.0),
+    }
+}
+
+fn flip_manifold(manifold: ContactManifold) -> ContactManifold {
+    ContactManifold {
+        normal: manifold.normal.inverse(),
+        ..manifold
+    }
+}
+
+// Finds the axis of minimum penetration among the 15 SAT axes and builds the contact manifold for it.
+// Face axes clip the incident face against the side planes of the reference face, edge axes use the
+// closest points between the two edges.
+fn collide_boxes(a: &OrientedBox, b: &OrientedBox) -> Option<ContactManifold> {
+    let r_pos = b.center - a.center;
+
+    // Penetration along an axis, or None when the axis separates the boxes
+    let overlap = |axis: Vector3| {
+        let overlap = a.projected_radius(axis) + b.projected_radius(axis) - r_pos.dot(axis).abs();
+        (overlap >= 0.0).then_some(overlap)
+    };
+
+    let mut best_axis = SeparatingAxis::Box1Face(0);
+    let mut best_normal = a.axes[0];
+    let mut best_overlap = f64::MAX;
+
+    for i in 0..3 {
+        let overlap = overlap(a.axes[i])?;
+        if overlap < best_overlap {
+            best_axis = SeparatingAxis::Box1Face(i);
+            best_normal = a.axes[i];
+            best_overlap = overlap;
+        }
+    }
+
+    // Axis of least penetration without the bias, to fall back on
+    let mut minimum = (best_axis, best_normal, best_overlap);
+
+    for i in 0..3 {
+        let overlap = overlap(b.axes[i])?;
+        if overlap < minimum.2 {
+            minimum = (SeparatingAxis::Box2Face(i), b.axes[i], overlap);
+        }
+        if overlap < FACE_AXIS_BIAS * best_overlap - FACE_AXIS_ABSOLUTE_BIAS {
+            best_axis = SeparatingAxis::Box2Face(i);
+            best_normal = b.axes[i];
+            best_overlap = overlap;
+        }
+    }
+
+    for i in 0..3 {
+        for j in 0..3 {
+            let axis = a.axes[i].cross(b.axes[j]);
+            let length = axis.magnitude();
+            if length < EDGE_AXIS_EPSILON {
+                continue;
+            }
+
+            let axis = axis * (1.0 / length);
+            let overlap = overlap(axis)?;
+            if overlap < minimum.2 {
+                minimum = (SeparatingAxis::Edges(i, j), axis, overlap);
+            }
+            if overlap < FACE_AXIS_BIAS * best_overlap - FACE_AXIS_ABSOLUTE_BIAS {
+                best_axis = SeparatingAxis::Edges(i, j);
+                best_normal = axis;
+                best_overlap = overlap;
+            }
+        }
+    }
+
+    let manifold = |(axis, normal, overlap): (SeparatingAxis, Vector3, f64)| {
+        // Orient the normal from the first box towards the second
+        let normal = if normal.dot(r_pos) < 0.0 { normal.inverse() } else { normal };
+        let points = match axis {
+            SeparatingAxis::Box1Face(i) => face_contact_points(a, i, b, normal),
+            SeparatingAxis::Box2Face(i) => face_contact_points(b, i, a, normal.inverse()),
+            SeparatingAxis::Edges(i, j) => edge_contact_points(a, i, b, j, normal, overlap),
+        };
+        (!points.is_empty()).then_some(ContactManifold { normal, points })
+    };
+
+    // The biased axis can be a face that the other box only reaches past its edges, leaving nothing
+    // after clipping, in which case the least penetrating axis still has contacts
+    manifold((best_axis, best_normal, best_overlap)).or_else(|| manifold(minimum))
+}
+
+// Contact points of the incident box against face `face` of the reference box, where `normal` points
+// away from the reference box towards the incident box
+fn face_contact_points(
+    reference: &OrientedBox,
+    face: usize,
+    incident: &OrientedBox,
+    normal: Vector3,
+) -> Vec<ContactPoint> {
+    let (u, v) = ((face + 1) % 3, (face + 2) % 3);
+    let reference_center = reference.center + normal * reference.half_size[face];
+
+    // The incident face is the face of the other box most anti-parallel to the reference normal
+    let incident_face = (0..3)
+        .max_by(|&i, &j| {
+            incident.axes[i]
+                .dot(normal)
+                .abs()
+                .total_cmp(&incident.axes[j].dot(normal).abs())
+        })
+        .unwrap();
+    let incident_normal = if incident.axes[incident_face].dot(normal) > 0.0 {
+        incident.axes[incident_face].inverse()
+    } else {
+        incident.axes[incident_face]
+    };
+    let incident_center = incident.center + incident_normal * incident.half_size[incident_face];
+    let (iu, iv) = ((incident_face + 1) % 3, (incident_face + 2) % 3);
+    let incident_u = incident.axes[iu] * incident.half_size[iu];
+    let incident_v = incident.axes[iv] * incident.half_size[iv];
+
+    let mut polygon = vec![
+        incident_center + incident_u + incident_v,
+        incident_center - incident_u + incident_v,
+        incident_center - incident_u - incident_v,
+        incident_center + incident_u - incident_v,
+    ];
+
+    // Clip the incident face against the four side planes of the reference face
+    for (axis, extent) in [
+        (reference.axes[u], reference.half_size[u]),
+        (reference.axes[v], reference.half_size[v]),
+    ] {
+        for side in [axis, axis.inverse()] {
+            polygon = clip_polygon(&polygon, side, side.dot(reference_center) + extent);
+            if polygon.is_empty() {
+                return Vec::new();
+            }
+        }
+    }
+
+    let points = polygon
+        .into_iter()
+        .filter_map(|point| {
+            let separation = normal.dot(point - reference_center);
+            (separation <= 0.0).then(|| ContactPoint {
+                position: point - normal * (0.5 * separation),
+                depth: -separation,
+            })
+        })
+        .collect();
+
+    reduce_contact_points(points, normal)
+}
+
+// Single contact point between the closest points of the two edges that produced the separating axis
+fn edge_contact_points(
+    a: &OrientedBox,
+    a_axis: usize,
+    b: &OrientedBox,
+    b_axis: usize,
+    normal: Vector3,
+    depth: f64,
+) -> Vec<ContactPoint> {
+    // The supporting edge of each box is the one furthest along the normal towards the other box
+    let supporting_edge_center = |body: &OrientedBox, edge_axis: usize, direction: Vector3| {
+        (0..3)
+            .filter(|&i| i != edge_axis)
+            .fold(body.center, |center, i| {
+                let sign = if body.axes[i].dot(direction) > 0.0 { 1.0 } else { -1.0 };
+                center + body.axes[i] * (sign * body.half_size[i])
+            })
+    };
+
+    let a_center = supporting_edge_center(a, a_axis, normal);
+    let b_center = supporting_edge_center(b, b_axis, normal.inverse());
+    let (a_point, b_point) = closest_points_between_segments(
+        a_center,
+        a.axes[a_axis],
+        a.half_size[a_axis],
+        b_center,
+        b.axes[b_axis],
+        b.half_size[b_axis],
+    );
+
+    vec![ContactPoint {
+        position: 0.5 * (a_point + b_point),
+        depth,
+    }]
+}
+
+// Sphere or capsule as the world space segment at its core inflated by a radius. A sphere's segment
+// has both ends at its center.
+struct RoundCore {
+    start: Vector3,
+    end: Vector3,
+    radius: f64,
+}
+
+impl RoundCore {
+    fn new(body: &Body) -> Option<RoundCore> {
+        let center = body.transform.position;
+        match body.shape {
+            Shape::Sphere { radius } => Some(RoundCore {
+                start: center,
+                end: center,
+                radius,
+            }),
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => {
+                let axis = body.transform.rotation.vector_to_world_space(Vector3::Y_AXIS) * half_height;
+                Some(RoundCore {
+                    start: center - axis,
+                    end: center + axis,
+                    radius,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn center(&self) -> Vector3 {
+        0.5 * (self.start + self.end)
+    }
+
+    fn half_length(&self) -> f64 {
+        0.5 * (self.end - self.start).magnitude()
+    }
+
+    fn direction(&self) -> Vector3 {
+        if self.half_length() > 0.0 {
+            (self.end - self.start).unit()
+        } else {
+            Vector3::Y_AXIS
+        }
+    }
+
+    // Both ends of the core, or just one for a sphere
+    fn ends(&self) -> Vec<Vector3> {
+        if self.half_length() > 0.0 {
+            vec![self.start, self.end]
+        } else {
+            vec![self.start]
+        }
+    }
+}
+
+// The core segment alone, with the radius added on after GJK
+impl SupportMap for RoundCore {
+    fn support(&self, direction: Vector3) -> Vector3 {
+        if self.start.dot(direction) > self.end.dot(direction) {
+            self.start
+        } else {
+            self.end
+        }
+    }
+}
+
+// Cores closer to parallel than this touch along a line rather than at a point
+const PARALLEL_CORE_THRESHOLD: f64 = 0.995;
+
+fn collide_rounds(a: &RoundCore, b: &RoundCore) -> Option<ContactManifold> {
+    let (point_a, point_b) = closest_points_between_segments(
+        a.center(),
+        a.direction(),
+        a.half_length(),
+        b.center(),
+        b.direction(),
+        b.half_length(),
+    );
+
+    let offset = point_b - point_a;
+    let distance = offset.magnitude();
+    let radius = a.radius + b.radius;
+    if distance > radius {
+        return None;
+    }
+
+    // Cores that intersect have no closest direction, so separate them perpendicular to the first core
+    let normal = if distance > f64::EPSILON {
+        offset * (1.0 / distance)
+    } else {
+        tangent_basis(a.direction())[0]
+    };
+
+    let contact = |point_a: Vector3, point_b: Vector3| {
+        let depth = radius - (point_b - point_a).dot(normal);
+        (depth >= 0.0).then(|| ContactPoint {
+            position: 0.5 * (point_a + normal * a.radius + point_b - normal * b.radius),
+            depth,
+        })
+    };
+
+    // Capsules lying along each other touch along a line, which is represented by its two ends
+    let mut points = Vec::new();
+    if a.half_length() > 0.0
+        && b.half_length() > 0.0
+        && a.direction().dot(b.direction()).abs() > PARALLEL_CORE_THRESHOLD
+    {
+        let project = |point: Vector3| a.direction().dot(point - a.center());
+        let (t0, t1) = (project(b.start), project(b.end));
+        let (low, high) = (t0.min(t1).max(-a.half_length()), t0.max(t1).min(a.half_length()));
+        if high > low {
+            for t in [low, high] {
+                let on_a = a.center() + a.direction() * t;
+                let on_b = closest_point_on_segment(on_a, b.start, b.end);
+                points.extend(contact(on_a, on_b));
+            }
+        }
+    }
+
+    if points.is_empty() {
+        points.extend(contact(point_a, point_b));
+    }
+
+    Some(ContactManifold {
+        normal,
+        points,
+    })
+}
+
+fn closest_point_on_segment(point: Vector3, start: Vector3, end: Vector3) -> Vector3 {
+    let segment = end - start;
+    let length_squared = segment.dot(segment);
+    if length_squared <= 0.0 {
+        return start;
+    }
+
+    start + segment * ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0)
+}
+
+// Corners of a box are numbered by the signs of their coordinates, with x as the highest bit and a set
+// bit for the positive side. Faces list their corners counter-clockwise seen from outside.
+const BOX_FACES: [(Vector3, [usize; 4]); 6] = [
+    (Vector3::new(1.0, 0.0, 0.0), [4, 6, 7, 5]),
+    (Vector3::new(-1.0, 0.0, 0.0), [0, 1, 3, 2]),
+    (Vector3::new(0.0, 1.0, 0.0), [2, 3, 7, 6]),
+    (Vector3::new(0.0, -1.0, 0.0), [0, 4, 5, 1]),
+    (Vector3::new(0.0, 0.0, 1.0), [1, 5, 7, 3]),
+    (Vector3::new(0.0, 0.0, -1.0), [0, 2, 6, 4]),
+];
+
+// Four edges along each of the x, y and z axes
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+];
+
+// Convex polyhedron in world space built from a box or a convex hull
+struct Polyhedron {
+    center: Vector3,
+    vertices: Vec<Vector3>,
+    faces: Vec<HullFace>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Polyhedron {
+    fn new(body: &Body) -> Option<Polyhedron> {
+        let transform = &body.transform;
+        match &body.shape {
+            Shape::Box { half_size } => Some(Polyhedron::from_box(transform, *half_size)),
+            Shape::ConvexHull(hull) => Some(Polyhedron {
+                center: transform.position,
+                vertices: hull
+                    .vertices
+                    .iter()
+                    .map(|&vertex| transform.point_to_world_space(vertex))
+                    .collect(),
+                faces: hull
+                    .faces
+                    .iter()
+                    .map(|face| HullFace {
+                        normal: transform.rotation.vector_to_world_space(face.normal),
+                        vertices: face.vertices.clone(),
+                    })
+                    .collect(),
+                edges: hull.edges.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    // Boxes are common enough that their faces and edges are written out rather than found as a hull
+    fn from_box(transform: &Transform, half_size: Vector3) -> Polyhedron {
+        let sign = |corner: usize, bit: usize| if corner & bit == 0 { -1.0 } else { 1.0 };
+        Polyhedron {
+            center: transform.position,
+            vertices: (0..8)
+                .map(|corner| {
+                    let local = Vector3::new(
+                        sign(corner, 4) * half_size.x,
+                        sign(corner, 2) * half_size.y,
+                        sign(corner, 1) * half_size.z,
+                    );
+                    transform.point_to_world_space(local)
+                })
+                .collect(),
+            faces: BOX_FACES
+                .iter()
+                .map(|&(normal, vertices)| HullFace {
+                    normal: transform.rotation.vector_to_world_space(normal),
+                    vertices: vertices.to_vec(),
+                })
+                .collect(),
+            edges: BOX_EDGES.to_vec(),
+        }
+    }
+
+    // Largest projection of the vertices onto a direction
+    fn max_projection(&self, direction: Vector3) -> f64 {
+        self.vertices
+            .iter()
+            .fold(f64::MIN, |max, vertex| max.max(vertex.dot(direction)))
+    }
+
+    fn face_point(&self, face: &HullFace) -> Vector3 {
+        self.vertices[face.vertices[0]]
+    }
+
+    fn face_polygon(&self, face: &HullFace) -> Vec<Vector3> {
+        face.vertices.iter().map(|&index| self.vertices[index]).collect()
+    }
+
+    fn edge_direction(&self, (a, b): (usize, usize)) -> Vector3 {
+        (self.vertices[b] - self.vertices[a]).unit()
+    }
+
+    // Outward normals and offsets of the planes through the edges of a face, perpendicular to it
+    fn face_side_planes(&self, face: &HullFace) -> Vec<(Vector3, f64)> {
+        let polygon = self.face_polygon(face);
+        (0..polygon.len())
+            .map(|i| {
+                let side = (polygon[(i + 1) % polygon.len()] - polygon[i]).cross(face.normal).unit();
+                (side, side.dot(polygon[i]))
+            })
+            .collect()
+    }
+}
+
+impl SupportMap for Polyhedron {
+    fn support(&self, direction: Vector3) -> Vector3 {
+        self.vertices
+            .iter()
+            .copied()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap_or(self.center)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PolyhedronAxis {
+    Face1(usize),
+    Face2(usize),
+    Edges(usize, usize),
+}
+
+// General polyhedron SAT over the face normals of both polyhedra and the cross products of every pair
+// of edges, producing contacts the same way as the box-box test
+fn collide_polyhedra(a: &Polyhedron, b: &Polyhedron) -> Option<ContactManifold> {
+    let mut best_axis = PolyhedronAxis::Face1(0);
+    let mut best_normal = Vector3::Y_AXIS;
+    let mut best_overlap = f64::MAX;
+
+    for (i, face) in a.faces.iter().enumerate() {
+        let overlap = a.max_projection(face.normal) + b.max_projection(face.normal.inverse());
+        if overlap < 0.0 {
+            return None;
+        }
+        if overlap < best_overlap {
+            best_axis = PolyhedronAxis::Face1(i);
+            best_normal = face.normal;
+            best_overlap = overlap;
+        }
+    }
+
+    // Axis of least penetration without the bias, to fall back on
+    let mut minimum = (best_axis, best_normal, best_overlap);
+
+    for (i, face) in b.faces.iter().enumerate() {
+        let overlap = b.max_projection(face.normal) + a.max_projection(face.normal.inverse());
+        if overlap < 0.0 {
+            return None;
+        }
+        if overlap < minimum.2 {
+            minimum = (PolyhedronAxis::Face2(i), face.normal.inverse(), overlap);
+        }
+        if overlap < FACE_AXIS_BIAS * best_overlap - FACE_AXIS_ABSOLUTE_BIAS {
+            best_axis = PolyhedronAxis::Face2(i);
+            best_normal = face.normal.inverse();
+            best_overlap = overlap;
+        }
+    }
+
+    for (i, &edge_a) in a.edges.iter().enumerate() {
+        for (j, &edge_b) in b.edges.iter().enumerate() {
+            let axis = a.edge_direction(edge_a).cross(b.edge_direction(edge_b));
+            let length = axis.magnitude();
+            if length < EDGE_AXIS_EPSILON {
+                continue;
+            }
+
+            // Overlap when pushing b along the axis and against it
+            let axis = axis * (1.0 / length);
+            let forward = a.max_projection(axis) + b.max_projection(axis.inverse());
+            let backward = b.max_projection(axis) + a.max_projection(axis.inverse());
+            let (overlap, axis) = if forward < backward {
+                (forward, axis)
+            } else {
+                (backward, axis.inverse())
+            };
+
+            if overlap < 0.0 {
+                return None;
+            }
+            if overlap < minimum.2 {
+                minimum = (PolyhedronAxis::Edges(i, j), axis, overlap);
+            }
+            if overlap < FACE_AXIS_BIAS * best_overlap - FACE_AXIS_ABSOLUTE_BIAS {
+                best_axis = PolyhedronAxis::Edges(i, j);
+                best_normal = axis;
+                best_overlap = overlap;
+            }
+        }
+    }
+
+    let manifold = |(axis, normal, overlap): (PolyhedronAxis, Vector3, f64)| {
+        let points = match axis {
+            PolyhedronAxis::Face1(i) => polyhedron_face_contact_points(a, &a.faces[i], b),
+            PolyhedronAxis::Face2(i) => polyhedron_face_contact_points(b, &b.faces[i], a),
+            PolyhedronAxis::Edges(i, j) => {
+                polyhedron_edge_contact_points(a, a.edges[i], b, b.edges[j], normal, overlap)
+            }
+        };
+        (!points.is_empty()).then_some(ContactManifold { normal, points })
+    };
+
+    // Like the box test, fall back on the least penetrating axis when the biased one has no contacts
+    manifold((best_axis, best_normal, best_overlap)).or_else(|| manifold(minimum))
+}
+
+// Clips the incident face of one polyhedron against the side planes of a reference face of the other
+fn polyhedron_face_contact_points(
+    reference: &Polyhedron,
+    reference_face: &HullFace,
+    incident: &Polyhedron,
+) -> Vec<ContactPoint> {
+    let normal = reference_face.normal;
+    let reference_point = reference.face_point(reference_face);
+
+    let Some(incident_face) = incident
+        .faces
+        .iter()
+        .min_by(|a, b| a.normal.dot(normal).total_cmp(&b.normal.dot(normal)))
+    else {
+        return Vec::new();
+    };
+
+    let mut polygon = incident.face_polygon(incident_face);
+    for (side, offset) in reference.face_side_planes(reference_face) {
+        polygon = clip_polygon(&polygon, side, offset);
+        if polygon.is_empty() {
+            return Vec::new();
+        }
+    }
+
+    let points = polygon
+        .into_iter()
+        .filter_map(|point| {
+            let separation = normal.dot(point - reference_point);
+            (separation <= 0.0).then(|| ContactPoint {
+                position: point - normal * (0.5 * separation),
+                depth: -separation,
+            })
+        })
+        .collect();
+
+    reduce_contact_points(points, normal)
+}
+
+// Closest points between the supporting edges of each polyhedron parallel to the edges of the axis
+fn polyhedron_edge_contact_points(
+    a: &Polyhedron,
+    edge_a: (usize, usize),
+    b: &Polyhedron,
+    edge_b: (usize, usize),
+    normal: Vector3,
+    depth: f64,
+) -> Vec<ContactPoint> {
+    let supporting_edge = |polyhedron: &Polyhedron, edge: (usize, usize), direction: Vector3| {
+        let edge_direction = polyhedron.edge_direction(edge);
+        polyhedron
+            .edges
+            .iter()
+            .copied()
+            .filter(|&other| polyhedron.edge_direction(other).dot(edge_direction).abs() > 1.0 - 1e-6)
+            .max_by(|&e1, &e2| {
+                let midpoint = |(i, j): (usize, usize)| {
+                    0.5 * (polyhedron.vertices[i] + polyhedron.vertices[j]).dot(direction)
+                };
+                midpoint(e1).total_cmp(&midpoint(e2))
+            })
+            .unwrap_or(edge)
+    };
+
+    let segment = |polyhedron: &Polyhedron, (i, j): (usize, usize)| {
+        let (start, end) = (polyhedron.vertices[i], polyhedron.vertices[j]);
+        (0.5 * (start + end), (end - start).unit(), 0.5 * (end - start).magnitude())
+    };
+
+    let (a_center, a_direction, a_half_length) = segment(a, supporting_edge(a, edge_a, normal));
+    let (b_center, b_direction, b_half_length) = segment(b, supporting_edge(b, edge_b, normal.inverse()));
+    let (a_point, b_point) = closest_points_between_segments(
+        a_center,
+        a_direction,
+        a_half_length,
+        b_center,
+        b_direction,
+        b_half_length,
+    );
+
+    vec![ContactPoint {
+        position: 0.5 * (a_point + b_point),
+        depth,
+    }]
+}
+
+// General test between any two convex shapes, each inflated by a radius. GJK finds the closest points
+// while the shapes are apart and EPA the penetration once they overlap, with the radii added on top, so
+// rounded shapes are handled through the cores that GJK converges on exactly.
+pub fn collide_convex(
+    a: &impl SupportMap,
+    radius_a: f64,
+    b: &impl SupportMap,
+    radius_b: f64,
+) -> Option<ContactManifold> {
+    let radius = radius_a + radius_b;
+    let (normal, depth, point_a, point_b) = match gjk::gjk(a, b) {
+        GjkResult::Separated { point_a, point_b } => {
+            let offset = point_b - point_a;
+            let distance = offset.magnitude();
+            if distance > radius || distance <= f64::EPSILON {
+                return None;
+            }
+            (offset * (1.0 / distance), radius - distance, point_a, point_b)
+        }
+        GjkResult::Intersecting(simplex) => {
+            let penetration = gjk::epa(a, b, simplex)?;
+            (
+                penetration.normal,
+                penetration.depth + radius,
+                penetration.point_a,
+                penetration.point_b,
+            )
+        }
+    };
+
+    Some(ContactManifold {
+        normal,
+        points: vec![ContactPoint {
+            position: 0.5 * (point_a + normal * radius_a + point_b - normal * radius_b),
+            depth,
+        }],
+    })
+}
+
+// The normal of the manifold points from the round body towards the polyhedron
+fn collide_round_polyhedron(round: &RoundCore, polyhedron: &Polyhedron) -> Option<ContactManifold> {
+    let manifold = collide_convex(round, round.radius, polyhedron, 0.0)?;
+
+    // A capsule lying on a face touches it along a line, represented by the two ends of the part of the
+    // core above the face
+    if round.half_length() > 0.0
+        && round.direction().dot(manifold.normal).abs() < 1.0 - PARALLEL_CORE_THRESHOLD
+    {
+        if let Some(face) = polyhedron
+            .faces
+            .iter()
+            .find(|face| face.normal.dot(manifold.normal) < -PARALLEL_CORE_THRESHOLD)
+        {
+            let points = round_face_contact_points(round, polyhedron, face);
+            if !points.is_empty() {
+                return Some(ContactManifold {
+                    normal: face.normal.inverse(),
+                    points,
+                });
+            }
+        }
+    }
+
+    Some(manifold)
+}
+
+// Contacts at both ends of the part of a capsule core that lies over a face
+fn round_face_contact_points(
+    round: &RoundCore,
+    polyhedron: &Polyhedron,
+    face: &HullFace,
+) -> Vec<ContactPoint> {
+    let (mut start, mut end) = (0.0_f64, 1.0_f64);
+    let segment = round.end - round.start;
+    for (side, offset) in polyhedron.face_side_planes(face) {
+        let distance_start = side.dot(round.start) - offset;
+        let distance_end = distance_start + side.dot(segment);
+        if distance_start > 0.0 && distance_end > 0.0 {
+            return Vec::new();
+        }
+        if distance_start > 0.0 {
+            start = start.max(distance_start / (distance_start - distance_end));
+        } else if distance_end > 0.0 {
+            end = end.min(distance_start / (distance_start - distance_end));
+        }
+    }
+
+    if start > end {
+        return Vec::new();
+    }
+
+    let face_point = polyhedron.face_point(face);
+    [start, end]
+        .into_iter()
+        .filter_map(|t| {
+            let point = round.start + segment * t;
+            let distance = face.normal.dot(point - face_point);
+            (distance < round.radius).then(|| ContactPoint {
+                position: point - face.normal * (0.5 * (round.radius + distance)),
+                depth: round.radius - distance,
+            })
+        })
+        .collect()
+}
+
+// Closest points between segments given by a center, unit direction and half length
+pub fn closest_points_between_segments(
+    a_center: Vector3,
+    a_direction: Vector3,
+    a_half_length: f64,
+    b_center: Vector3,
+    b_direction: Vector3,
+    b_half_length: f64,
+) -> (Vector3, Vector3) {
+    let r = b_center - a_center;
+    let directions_dot = a_direction.dot(b_direction);
+    let a_dot_r = a_direction.dot(r);
+    let b_dot_r = b_direction.dot(r);
+    let denominator = 1.0 - directions_dot * directions_dot;
+
+    // Parameter along the first segment of the closest point between the infinite lines
+    let mut s = if denominator > f64::EPSILON {
+        ((a_dot_r - directions_dot * b_dot_r) / denominator).clamp(-a_half_length, a_half_length)
+    } else {
+        0.0
+    };
+
+    // Closest point on the second segment to it, then back onto the first segment
+    let t = (directions_dot * s - b_dot_r).clamp(-b_half_length, b_half_length);
+    s = (directions_dot * t + a_dot_r).clamp(-a_half_length, a_half_length);
+
+    (a_center + a_direction * s, b_center + b_direction * t)
+}
+
+// Sutherland-Hodgman clipping of a convex polygon, keeping the part where normal.p <= offset
+fn clip_polygon(polygon: &[Vector3], normal: Vector3, offset: f64) -> Vec<Vector3> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for (i, &point) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        let distance = offset - normal.dot(point);
+        let next_distance = offset - normal.dot(next);
+
+        if distance >= 0.0 {
+            clipped.push(point);
+        }
+
+        if (distance >= 0.0) != (next_distance >= 0.0) {
+            let t = distance / (distance - next_distance);
+            clipped.push(point + t * (next - point));
+        }
+    }
+
+    clipped
+}
+
+// Keeps at most four contact points, preferring the deepest point and then the points that span the
+// largest area, which is what keeps a resting box stable
+fn reduce_contact_points(mut points: Vec<ContactPoint>, normal: Vector3) -> Vec<ContactPoint> {
+    if points.len() <= MAX_CONTACT_POINTS {
+        return points;
+    }
+
+    let deepest = (0..points.len())
+        .max_by(|&i, &j| points[i].depth.total_cmp(&points[j].depth))
+        .unwrap();
+    let first = points.swap_remove(deepest);
+
+    let furthest = (0..points.len())
+        .max_by(|&i, &j| {
+            let distance = |k: usize| (points[k].position - first.position).magnitude();
+            distance(i).total_cmp(&distance(j))
+        })
+        .unwrap();
+    let second = points.swap_remove(furthest);
+
+    // Signed area of the triangle each remaining point makes with the first two
+    let area = |point: &ContactPoint| {
+        (second.position - first.position)
+            .cross(point.position - first.position)
+            .dot(normal)
+    };
+
+    let third = (0..points.len())
+        .max_by(|&i, &j| area(&points[i]).total_cmp(&area(&points[j])))
+        .unwrap();
+    let third = points.swap_remove(third);
+
+    let fourth = (0..points.len())
+        .min_by(|&i, &j| area(&points[i]).total_cmp(&area(&points[j])))
+        .unwrap();
+    let fourth = points.swap_remove(fourth);
+
+    vec![first, second, third, fourth]
+}
+
+// Infinite static plane containing the points p where normal.p = distance, solid below the normal
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    pub normal: Vector3,
+    pub distance: f64,
+    pub restitution: f64,
+    pub friction: f64,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3, point: Vector3) -> Plane {
+        let normal = normal.unit();
+        Plane {
+            normal,
+            distance: normal.dot(point),
+            restitution: DEFAULT_RESTITUTION,
+            friction: DEFAULT_FRICTION,
+        }
+    }
+
+    pub fn signed_distance(&self, point: Vector3) -> f64 {
+        self.normal.dot(point) - self.distance
+    }
+
+    // Square of the plane with the given half extent around the point closest to the origin
+    pub fn geometry(&self, half_extent: f64, color: Color) -> [SceneTriangle; 2] {
+        let center = self.normal * self.distance;
+        let [tangent1, tangent2] = tangent_basis(self.normal);
+        let (u, v) = (tangent1 * half_extent, tangent2 * half_extent);
+        let corners = [center + u + v, center - u + v, center - u - v, center + u - v];
+        [
+            SceneTriangle {
+                normal: self.normal,
+                points: [corners[0], corners[1], corners[2]],
+                color,
+            },
+            SceneTriangle {
+                normal: self.normal,
+                points: [corners[2], corners[3], corners[0]],
+                color,
+            },
+        ]
+    }
+}
+
+// Contact points are the vertices of a polyhedron below the plane, the ends of the core of a sphere or
+// capsule within its radius of the plane, or the support points of a custom shape towards it. The normal
+// points from the body into the plane so the body is the first body of the contact.
+pub fn collide_plane(body: &Body, plane: &Plane) -> Option<ContactManifold> {
+    // Cheap rejection using the deepest point of the shape before visiting its vertices
+    if plane.signed_distance(body.support(plane.normal.inverse())) > 0.0 {
+        return None;
+    }
+
+    let (vertices, radius) = match (RoundCore::new(body), Polyhedron::new(body)) {
+        (Some(round), _) => (round.ends(), round.radius),
+        (None, Some(polyhedron)) => (polyhedron.vertices, 0.0),
+        (None, None) => (support_points_towards(body, plane.normal.inverse()), 0.0),
+    };
+
+    let mut points = Vec::new();
+    for vertex in vertices {
+        let separation = plane.signed_distance(vertex) - radius;
+        if separation <= 0.0 {
+            let surface_point = vertex - plane.normal * radius;
+            points.push(ContactPoint {
+                position: surface_point - plane.normal * (0.5 * separation),
+                depth: -separation,
+            });
+        }
+    }
+
+    if points.is_empty() {
+        return None;
+    }
+
+    let normal = plane.normal.inverse();
+    Some(ContactManifold {
+        normal,
+        points: reduce_contact_points(points, normal),
+    })
+}
+
+// Support points of a shape without vertices for a direction and for directions tilted a little to each
+// side of it, which land on the corners of a face lying flat against a plane facing the direction so it
+// rests on more than one point
+fn support_points_towards(shape: &impl SupportMap, direction: Vector3) -> Vec<Vector3> {
+    let [tangent1, tangent2] = tangent_basis(direction);
+    let mut points = vec![shape.support(direction)];
+    for tangent in [tangent1, tangent2, tangent1.inverse(), tangent2.inverse()] {
+        points.push(shape.support(direction + tangent * SUPPORT_TILT));
+    }
+    points
+}
+
+// Runs the broadphase over the bodies and the narrowphase on each candidate pair, then tests every
+// body against the static planes. Pairs whose collision masks exclude each other are skipped, as are pairs
+// without a dynamic body and pairs where neither body can move, which is what saves the work for static
+// and sleeping bodies. Pairs with one sensor are only tested for overlap, whether or not anything moves
+// so a body resting in a sensor stays in it, and appended to overlaps as (sensor, body) so the overlaps
+// of every sub-step of a step add up.
+pub fn find_contacts(
+    bodies: &[Body],
+    planes: &[Plane],
+    broadphase: &mut SweepAndPrune,
+    contacts: &mut Vec<Contact>,
+    overlaps: &mut Vec<(usize, usize)>,
+) {
+    let aabbs: Vec<Aabb> = bodies.iter().map(Body::aabb).collect();
+    let mut pairs = Vec::new();
+    broadphase.find_pairs(&aabbs, &mut pairs);
+
+    contacts.clear();
+    contacts.extend(pairs.into_iter().filter_map(|(body1, body2)| {
+        let (a, b) = (&bodies[body1], &bodies[body2]);
+        if !a.can_collide(b) {
+            return None;
+        }
+
+        if a.is_sensor || b.is_sensor {
+            if a.is_sensor != b.is_sensor && gjk::intersects(a, b) {
+                overlaps.push(if a.is_sensor { (body1, body2) } else { (body2, body1) });
+            }
+            return None;
+        }
+
+        let is_dynamic = |body: &Body| body.body_type == BodyType::Dynamic;
+        if (!is_dynamic(a) && !is_dynamic(b)) || (!a.is_active() && !b.is_active()) {
+            return None;
+        }
+
+        collide(a, b).map(|manifold| {
+            let (restitution, friction) =
+                combine_materials(a.restitution, a.friction, b.restitution, b.friction);
+            Contact {
+                body1,
+                body2: Some(body2),
+                manifold,
+                restitution,
+                friction,
+                normal_impulse: 0.0,
+                point_impulses: Vec::new(),
+            }
+        })
+    }));
+
+    // Planes are static world geometry that every dynamic body except sensors collides with
+    let collides_with_planes =
+        |body: &Body| body.body_type == BodyType::Dynamic && body.is_active() && !body.is_sensor;
+    for (index, body) in bodies.iter().enumerate().filter(|(_, body)| collides_with_planes(body)) {
+        for plane in planes {
+            if let Some(manifold) = collide_plane(body, plane) {
+                let (restitution, friction) =
+                    combine_materials(body.restitution, body.friction, plane.restitution, plane.friction);
+                contacts.push(Contact {
+                    body1: index,
+                    body2: None,
+                    manifold,
+                    restitution,
+                    friction,
+                    normal_impulse: 0.0,
+                    point_impulses: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mesh::Mesh;
+
+    use std::rc::Rc;
+
+    // Cube given to the engine as a custom shape, so it goes through GJK and EPA instead of the box SAT
+    #[derive(Debug)]
+    struct CustomCube(f64);
+
+    impl SupportMap for CustomCube {
+        fn support(&self, direction: Vector3) -> Vector3 {
+            let sign = |value: f64| if value < 0.0 { -self.0 } else { self.0 };
+            Vector3::new(sign(direction.x), sign(direction.y), sign(direction.z))
+        }
+    }
+
+    impl CustomShape for CustomCube {
+        fn bounding_radius(&self) -> f64 {
+            self.0 * 3.0_f64.sqrt()
+        }
+
+        fn volume(&self) -> f64 {
+            8.0 * self.0.powi(3)
+        }
+
+        fn unit_inertia(&self) -> Matrix3 {
+            let inertia = 2.0 / 3.0 * self.0 * self.0;
+            Matrix3::from_diagonal(Vector3::new(inertia, inertia, inertia))
+        }
+
+        fn mesh(&self) -> Mesh {
+            Mesh::cuboid(Vector3::new(self.0, self.0, self.0))
+        }
+    }
+
+    fn body_at(position: Vector3, shape: Shape) -> Body {
+        Body::new(Transform::new(position, Quaternion::default()), shape, 1.0)
+    }
+
+    #[test]
+    fn custom_shapes_collide_with_other_shapes_through_gjk() {
+        let custom = body_at(Vector3::default(), Shape::Custom(Rc::new(CustomCube(1.0))));
+        let others = [
+            Shape::Box {
+                half_size: Vector3::new(1.0, 1.0, 1.0),
+            },
+            Shape::Sphere { radius: 1.0 },
+            Shape::Custom(Rc::new(CustomCube(1.0))),
+        ];
+
+        for shape in others {
+            let other = body_at(Vector3::new(0.2, 1.75, -0.1), shape);
+            let manifold = collide(&custom, &other).expect("overlapping shapes should collide");
+            assert!(manifold.normal.dot(Vector3::Y_AXIS) > 1.0 - 1e-6);
+            let depth = manifold.points[0].depth;
+            assert!((depth - 0.25).abs() < 1e-2, "depth {depth}");
+
+            let flipped = collide(&other, &custom).expect("overlapping shapes should collide");
+            assert!(flipped.normal.dot(Vector3::Y_AXIS) < -1.0 + 1e-6);
+
+            let apart = body_at(Vector3::new(0.2, 2.25, -0.1), other.shape.clone());
+            assert!(collide(&custom, &apart).is_none());
+        }
+    }
+
+    #[test]
+    fn every_pair_of_shapes_collides_along_the_axis_they_overlap_on() {
+        // Triangular prism with flat ends, so that each shape meets the others on a face or a round side
+        let mut points = Vec::new();
+        for y in [-0.75, 0.75] {
+            for degrees in [0.0_f64, 120.0, 240.0] {
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                points.push(Vector3::new(cos, y, sin));
+            }
+        }
+        let (prism, _) = ConvexHull::new(&points).unwrap();
+        let shapes = [
+            Shape::Box {
+                half_size: Vector3::new(1.0, 0.5, 2.0),
+            },
+            Shape::Sphere { radius: 1.0 },
+            Shape::Capsule {
+                radius: 0.5,
+                half_height: 1.0,
+            },
+            Shape::ConvexHull(prism),
+            Shape::Custom(Rc::new(CustomCube(1.0))),
+        ];
+
+        for shape1 in shapes.iter() {
+            for shape2 in shapes.iter() {
+                // Stack the second shape on the first, a quarter into it
+                let bottom = body_at(Vector3::default(), shape1.clone());
+                let depth = 0.25;
+                let height = bottom.support(Vector3::Y_AXIS).y + shape2.support(Vector3::Y_AXIS).y - depth;
+                let top = body_at(Vector3::new(0.0, height, 0.0), shape2.clone());
+                let pair = format!("{shape1:?} and {shape2:?}");
+
+                // Custom shapes go through EPA, which only converges to within a tolerance
+                let is_custom = |shape: &Shape| matches!(shape, Shape::Custom(_));
+                let tolerance = if is_custom(shape1) || is_custom(shape2) { 1e-2 } else { 1e-6 };
+
+                let manifold = collide(&bottom, &top).unwrap_or_else(|| panic!("{pair} should collide"));
+                assert!(manifold.normal.dot(Vector3::Y_AXIS) > 1.0 - tolerance, "{pair}");
+                let deepest = manifold.points.iter().map(|point| point.depth).fold(0.0, f64::max);
+                assert!((deepest - depth).abs() < tolerance, "{pair} are {deepest} deep");
+
+                let flipped = collide(&top, &bottom).unwrap_or_else(|| panic!("{pair} should collide"));
+                assert!(flipped.normal.dot(Vector3::Y_AXIS) < -1.0 + tolerance, "{pair}");
+
+                let apart = body_at(Vector3::new(0.0, height + 2.0 * depth, 0.0), shape2.clone());
+                assert!(collide(&bottom, &apart).is_none(), "{pair} shouldn't collide apart");
+            }
+        }
+    }
+
+    #[test]
+    fn custom_shapes_rest_on_planes_on_several_points() {
+        let plane = Plane::new(Vector3::Y_AXIS, Vector3::default());
+        let custom = body_at(Vector3::new(3.0, 0.9, 4.0), Shape::Custom(Rc::new(CustomCube(1.0))));
+
+        let manifold = collide_plane(&custom, &plane).expect("a cube sunk into a plane should touch it");
+        assert!(manifold.points.len() >= 3);
+        for point in &manifold.points {
+            assert!((point.depth - 0.1).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn planes_push_every_shape_out_along_their_normal_by_its_depth() {
+        let normal = Vector3::new(0.3, 1.0, -0.2).unit();
+        let plane = Plane::new(normal, Vector3::new(1.0, -2.0, 3.0));
+        let rotation = Quaternion::from_axis_angle(Vector3::new(1.0, 0.5, 0.0), 0.4);
+        let (hull, _) = ConvexHull::new(&[
+            Vector3::new(0.0, 1.5, 0.0),
+            Vector3::new(1.0, -0.5, 1.0),
+            Vector3::new(-1.0, -0.5, 1.0),
+            Vector3::new(0.0, -0.5, -1.0),
+        ])
+        .unwrap();
+        let shapes = [
+            Shape::Box {
+                half_size: Vector3::new(1.0, 0.5, 2.0),
+            },
+            Shape::Sphere { radius: 1.0 },
+            Shape::Capsule {
+                radius: 0.5,
+                half_height: 1.0,
+            },
+            Shape::ConvexHull(hull),
+            Shape::Custom(Rc::new(CustomCube(1.0))),
+        ];
+
+        for shape in shapes {
+            // Sink the deepest point of the shape a quarter below the plane
+            let mut body = Body::new(Transform::new(Vector3::default(), rotation), shape, 1.0);
+            let depth = 0.25;
+            let extent = -plane.signed_distance(body.support(normal.inverse()));
+            body.transform.position = normal * (extent - depth);
+
+            let manifold = collide_plane(&body, &plane).expect("a shape sunk into a plane should touch it");
+            assert!(manifold.normal.dot(normal) < -1.0 + 1e-9, "{:?}", body.shape);
+            let deepest = manifold.points.iter().map(|point| point.depth).fold(0.0, f64::max);
+            assert!((deepest - depth).abs() < 1e-6, "{:?} is {deepest} deep", body.shape);
+
+            // Points lie halfway between the plane and the surface of the shape
+            for point in &manifold.points {
+                assert!(point.depth > 0.0 && point.depth <= deepest);
+                assert!((plane.signed_distance(point.position) + 0.5 * point.depth).abs() < 1e-6);
+            }
+
+            body.transform.position += normal * (2.0 * depth);
+            assert!(collide_plane(&body, &plane).is_none(), "{:?}", body.shape);
+        }
+    }
+
+    #[test]
+    fn boxes_fall_back_to_the_least_penetrating_axis() {
+        // The biased choice for this pair is a face of the second box that clips to nothing
+        let a = OrientedBox::new(
+            &Transform::new(
+                Vector3::default(),
+                Quaternion {
+                    x: -0.5021290818404319,
+                    y: 0.09806355868485267,
+                    z: 0.8435354809455408,
+                    w: 0.16339466335876,
+                },
+            ),
+            Vector3::new(4.313053998989812, 1.126552613549657, 4.969414224411834),
+        );
+        let b = OrientedBox::new(
+            &Transform::new(
+                Vector3::new(0.1692759800572472, 4.682176379211365, -3.40876920443507),
+                Quaternion {
+                    x: 0.1935356815034263,
+                    y: -0.24943650136394713,
+                    z: 0.22718543353666015,
+                    w: 0.9212557465552537,
+                },
+            ),
+            Vector3::new(2.3060156364624818, 2.079964267908806, 1.4008055454531891),
+        );
+
+        let manifold = collide_boxes(&a, &b).expect("overlapping boxes should collide");
+        assert!(!manifold.points.is_empty());
+        assert!(manifold.normal.dot(b.center - a.center) > 0.0);
+        assert!(manifold.points.iter().all(|point| point.depth >= 0.0));
+    }
+
+    #[test]
+    fn box_polyhedron_faces_wind_outwards_around_their_normal() {
+        let transform = Transform::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            Quaternion::from_axis_angle(Vector3::new(1.0, 2.0, 0.5), 0.7),
+        );
+        let polyhedron = Polyhedron::from_box(&transform, Vector3::new(1.0, 2.0, 3.0));
+
+        for face in &polyhedron.faces {
+            let polygon = polyhedron.face_polygon(face);
+            let winding = (polygon[1] - polygon[0]).cross(polygon[2] - polygon[1]).unit();
+            assert!(winding.dot(face.normal) > 1.0 - 1e-9);
+            for &point in &polygon {
+                let distance = face.normal.dot(point - polygon[0]);
+                assert!(distance.abs() < 1e-9);
+            }
+            assert!(face.normal.dot(polygon[0] - polyhedron.center) > 0.0);
+        }
+
+        for &(i, j) in &polyhedron.edges {
+            let length = (polyhedron.vertices[j] - polyhedron.vertices[i]).magnitude();
+            assert!([2.0, 4.0, 6.0].iter().any(|&expected| (length - expected).abs() < 1e-9));
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{body::Body, body_set::BodyHandle, transform::*, world::World};
+
+use std::fmt;
+
+// First difference found between two runs that should have been identical
+#[derive(Clone, Debug, PartialEq)]
+pub enum Divergence {
+    // The runs ended up with different bodies in their body sets
+    Bodies { frame: usize },
+    // A body's state differs in at least one bit
+    State { frame: usize, body: BodyHandle, field: &'static str },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Bodies { frame } => write!(f, "body sets differ after frame {frame}"),
+            Divergence::State { frame, body, field } => {
+                write!(f, "{field} of {body:?} differs after frame {frame}")
+            }
+        }
+    }
+}
+
+// Builds the same world twice and runs both copies through the given number of frames, one physics step
+// each, comparing the transforms, velocities and sleep state of every body bit for bit after each frame.
+// Any difference means something in the step depends on more than its inputs, such as iteration order
+// over a hash map or uninitialized state.
+pub fn check_determinism<W: World>(build: impl Fn() -> W, frames: usize) -> Result<(), Divergence> {
+    let (mut first, mut second) = (build(), build());
+
+    for frame in 1..=frames {
+        // A frame exactly one timestep long takes exactly one step, so the accumulator never carries
+        // a remainder that could round differently
+        first.update(first.physics().timestep);
+        second.update(second.physics().timestep);
+
+        let (bodies1, bodies2) = (&first.physics().bodies, &second.physics().bodies);
+        if bodies1.len() != bodies2.len() {
+            return Err(Divergence::Bodies { frame });
+        }
+
+        for ((handle1, body1), (handle2, body2)) in bodies1.iter().zip(bodies2.iter()) {
+            if handle1 != handle2 {
+                return Err(Divergence::Bodies { frame });
+            }
+            if let Some(field) = first_difference(body1, body2) {
+                return Err(Divergence::State {
+                    frame,
+                    body: handle1,
+                    field,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Name of the first part of the simulated state that differs between two bodies, comparing the bits of
+// every component so that even a difference in the last place or in the sign of zero counts
+fn first_difference(body1: &Body, body2: &Body) -> Option<&'static str> {
+    let vector_bits = |vector: Vector3| [vector.x, vector.y, vector.z].map(f64::to_bits);
+    let rotation_bits =
+        |rotation: Quaternion| [rotation.x, rotation.y, rotation.z, rotation.w].map(f64::to_bits);
+
+    if vector_bits(body1.transform.position) != vector_bits(body2.transform.position) {
+        Some("position")
+    } else if rotation_bits(body1.transform.rotation) != rotation_bits(body2.transform.rotation) {
+        Some("rotation")
+    } else if vector_bits(body1.linear_velocity) != vector_bits(body2.linear_velocity) {
+        Some("linear velocity")
+    } else if vector_bits(body1.angular_velocity) != vector_bits(body2.angular_velocity) {
+        Some("angular velocity")
+    } else if body1.is_sleeping != body2.is_sleeping {
+        Some("sleeping")
+    } else if body1.sleep_time.to_bits() != body2.sleep_time.to_bits() {
+        Some("sleep time")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::world::test_world::TestWorld;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn test_world_is_deterministic() {
+        assert_eq!(check_determinism(TestWorld::new, 120), Ok(()));
+    }
+
+    #[test]
+    fn differences_between_the_runs_are_found() {
+        // The second world starts with its first body nudged by the smallest amount there is
+        let builds = Cell::new(0);
+        let build = || {
+            let mut world = TestWorld::new();
+            if builds.replace(builds.get() + 1) == 1 {
+                let body = &mut world.physics.bodies.as_mut_slice()[0];
+                body.transform.position.x = body.transform.position.x.next_up();
+            }
+            world
+        };
+
+        let body = TestWorld::new().physics.bodies.handle(0);
+        let field = "position";
+        assert_eq!(check_determinism(build, 10), Err(Divergence::State { frame: 1, body, field }));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    body_set::{BodyHandle, BodySet},
+    solver::Contact,
+    trigger::TriggerEvent,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContactEventKind {
+    // The bodies started touching this step. The impulse is the normal impulse that pushed them apart,
+    // which tells how hard they hit.
+    Began { impulse: f64 },
+    // The bodies were already touching and still are
+    Persisted { impulse: f64 },
+    // The bodies stopped touching this step
+    Ended,
+}
+
+// Two bodies in contact, or a body and the static world when the second is None. Pairs of bodies are
+// ordered by handle so the same pair always looks the same.
+pub type BodyPair = (BodyHandle, Option<BodyHandle>);
+
+// Change in contact between two bodies, or between a body and the static world when body2 is None
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContactEvent {
+    pub body1: BodyHandle,
+    pub body2: Option<BodyHandle>,
+    pub kind: ContactEventKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhysicsEvent {
+    Contact(ContactEvent),
+    Trigger(TriggerEvent),
+}
+
+// How an entry of a sorted list changed from one step to the next, with its index in the current list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added(usize),
+    Kept(usize),
+    Removed,
+}
+
+// Walks two sorted lists of unique keys together, visiting every key in either in order
+pub fn diff_sorted<T: Ord + Copy>(previous: &[T], current: &[T], mut visit: impl FnMut(T, Change)) {
+    let (mut i, mut j) = (0, 0);
+    while i < previous.len() || j < current.len() {
+        match (previous.get(i), current.get(j)) {
+            (Some(&old), Some(&new)) if old == new => {
+                visit(new, Change::Kept(j));
+                i += 1;
+                j += 1;
+            }
+            (Some(&old), Some(&new)) if old < new => {
+                visit(old, Change::Removed);
+                i += 1;
+            }
+            (Some(&old), None) => {
+                visit(old, Change::Removed);
+                i += 1;
+            }
+            (_, Some(&new)) => {
+                visit(new, Change::Added(j));
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+}
+
+// Adds the normal impulse of every contact of a sub-step to impulses under its pair of handles, so that
+// a step sees every pair that touched in any of its sub-steps along with the impulse of each sub-step
+pub fn accumulate_contact_impulses(
+    bodies: &BodySet,
+    contacts: &[Contact],
+    impulses: &mut Vec<(BodyPair, f64)>,
+) {
+    impulses.extend(contacts.iter().map(|contact| {
+        let (body1, body2) = (bodies.handle(contact.body1), contact.body2.map(|body2| bodies.handle(body2)));
+        let pair = match body2 {
+            Some(body2) => (body1.min(body2), Some(body1.max(body2))),
+            None => (body1, None),
+        };
+        (pair, contact.normal_impulse)
+    }));
+}
+
+// Compares the pairs that touched during this step, with their impulses accumulated over its sub-steps,
+// with the pairs that touched in the last one, writing an event for every pair that touches in either
+// and replacing touching with this step's pairs. Contacts aren't generated between bodies that can't
+// move, so pairs that went to sleep touching are carried over silently instead of ending and beginning
+// again when they wake.
+pub fn find_contact_events(
+    bodies: &BodySet,
+    impulses: &[(BodyPair, f64)],
+    touching: &mut Vec<BodyPair>,
+    events: &mut Vec<PhysicsEvent>,
+) {
+    // Impulses of a pair over several sub-steps, and of a body with several planes, add up to one
+    let mut current: Vec<(BodyPair, Option<f64>)> =
+        impulses.iter().map(|&(pair, impulse)| (pair, Some(impulse))).collect();
+    let is_resting = |(body1, body2): BodyPair| {
+        !bodies[body1].is_active() && body2.is_none_or(|body2| !bodies[body2].is_active())
+    };
+    current.extend(touching.iter().filter(|&&pair| is_resting(pair)).map(|&pair| (pair, None)));
+    current.sort_by_key(|&(pair, _)| pair);
+    current.dedup_by(|(pair, impulse), (kept_pair, kept_impulse)| {
+        if pair != kept_pair {
+            return false;
+        }
+        if let (Some(kept_impulse), Some(impulse)) = (kept_impulse, impulse) {
+            *kept_impulse += *impulse;
+        }
+        true
+    });
+
+    let pairs: Vec<BodyPair> = current.iter().map(|&(pair, _)| pair).collect();
+    diff_sorted(touching, &pairs, |(body1, body2), change| {
+        let kind = match change {
+            Change::Added(index) => current[index].1.map(|impulse| ContactEventKind::Began { impulse }),
+            Change::Kept(index) => current[index].1.map(|impulse| ContactEventKind::Persisted { impulse }),
+            Change::Removed => Some(ContactEventKind::Ended),
+        };
+        if let Some(kind) = kind {
+            events.push(PhysicsEvent::Contact(ContactEvent { body1, body2, kind }));
+        }
+    });
+
+    *touching = pairs;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{body::Body, collision::Plane, physics::PhysicsWorld, shape::Shape, transform::*};
+
+    fn floor_events(world: &mut PhysicsWorld) -> Vec<ContactEventKind> {
+        world.step();
+        world
+            .drain_events()
+            .filter_map(|event| match event {
+                PhysicsEvent::Contact(ContactEvent { body2: None, kind, .. }) => Some(kind),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn world_with_sphere(height: f64, velocity: f64, restitution: f64) -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        world.sub_steps = 4;
+        world.sleep.enabled = false;
+        world.add_plane(Plane::new(Vector3::Y_AXIS, Vector3::default()));
+        world.add_body(Body {
+            linear_velocity: Vector3::new(0.0, velocity, 0.0),
+            restitution,
+            ..Body::new(
+                Transform::new(Vector3::new(0.0, height, 0.0), Quaternion::default()),
+                Shape::Sphere { radius: 1.0 },
+                1.0,
+            )
+        });
+        world
+    }
+
+    #[test]
+    fn resting_contacts_report_the_impulse_of_the_whole_step() {
+        let mut world = world_with_sphere(1.0, 0.0, 0.0);
+        for _ in 0..60 {
+            world.step();
+        }
+        world.drain_events().count();
+
+        // Holding the sphere up for a step takes its weight over the whole timestep, not just the last
+        // sub-step's share of it
+        let expected = world.gravity.magnitude() * world.timestep;
+        let [ContactEventKind::Persisted { impulse }] = floor_events(&mut world)[..] else {
+            panic!("resting sphere should persist its contact");
+        };
+        assert!((impulse - expected).abs() < 0.01 * expected, "impulse {impulse} instead of {expected}");
+    }
+
+    #[test]
+    fn impacts_in_early_sub_steps_are_reported() {
+        // The sphere hits the floor in the first sub-step and has bounced off it by the last one
+        let mut world = world_with_sphere(0.99, -100.0, 1.0);
+        let [ContactEventKind::Began { impulse }] = floor_events(&mut world)[..] else {
+            panic!("bounce should begin a contact");
+        };
+        assert!(impulse > 150.0, "impulse {impulse} understates the bounce");
+        assert_eq!(floor_events(&mut world), [ContactEventKind::Ended]);
+    }
+}
@@ -0,0 +1,320 @@
+use crate::{
+    body::{Body, BodyType},
+    broadphase::Aabb,
+    collision::Plane,
+    shape::Shape,
+    transform::*,
+};
+
+use std::f64::consts::PI;
+
+// Shapes other than spheres are split into this many cells along each axis of their bounds to estimate
+// how much of them is under water
+const BUOYANCY_CELLS: usize = 6;
+
+// Force applied to every awake dynamic body each physics step, on top of gravity
+#[derive(Clone, Debug)]
+pub enum ForceGenerator {
+    // Slows every body down by the given fraction of its velocities per second, like the per-body
+    // damping of Body but for the whole world
+    Damping { linear: f64, angular: f64 },
+    // Pulls bodies within the radius towards the center with an acceleration that fades out linearly to
+    // zero at the radius. A negative strength pushes them away instead.
+    Radial {
+        center: Vector3,
+        radius: f64,
+        strength: f64,
+    },
+    // Drags bodies whose center is inside the volume towards the velocity of the wind, in proportion to
+    // how much slower than the wind they move
+    Wind {
+        volume: Aabb,
+        velocity: Vector3,
+        drag: f64,
+    },
+    // Pushes bodies whose center is inside the volume up against gravity by the weight of the water they
+    // displace below the surface, and slows them down in proportion to how much of them is submerged
+    Buoyancy {
+        volume: Aabb,
+        surface: Plane,
+        density: f64,
+        linear_drag: f64,
+        angular_drag: f64,
+    },
+}
+
+impl ForceGenerator {
+    pub fn apply(&self, body: &mut Body, gravity: Vector3, dt: f64) {
+        match self {
+            ForceGenerator::Damping { linear, angular } => body.damp(*linear, *angular, dt),
+            ForceGenerator::Radial {
+                center,
+                radius,
+                strength,
+            } => {
+                let offset = *center - body.transform.position;
+                let distance = offset.magnitude();
+                if distance < *radius && distance > f64::EPSILON {
+                    let acceleration = strength * (1.0 - distance / radius);
+                    body.apply_force_no_wake(offset * (acceleration * body.mass() / distance));
+                }
+            }
+            ForceGenerator::Wind {
+                volume,
+                velocity,
+                drag,
+            } => {
+                if volume.contains_point(body.transform.position) {
+                    body.apply_force_no_wake((*velocity - body.linear_velocity) * *drag);
+                }
+            }
+            ForceGenerator::Buoyancy {
+                volume,
+                surface,
+                density,
+                linear_drag,
+                angular_drag,
+            } => {
+                if !volume.contains_point(body.transform.position) {
+                    return;
+                }
+
+                let (submerged, center) = submerged_volume(body, surface);
+                if submerged > 0.0 {
+                    body.apply_force_at_point_no_wake(gravity * -(density * submerged), center);
+
+                    // A shape without volume can't be partly submerged, so it takes the full drag
+                    let volume = body.shape.volume();
+                    let fraction = if volume > 0.0 { submerged / volume } else { 1.0 };
+                    body.damp(linear_drag * fraction, angular_drag * fraction, dt);
+                }
+            }
+        }
+    }
+}
+
+// Applies the force generators to the awake dynamic bodies. Sleeping bodies are left alone, since a
+// force that never lets up would keep them from ever falling asleep.
+pub fn apply_force_generators(generators: &[ForceGenerator], bodies: &mut [Body], gravity: Vector3, dt: f64) {
+    if generators.is_empty() {
+        return;
+    }
+
+    for body in bodies
+        .iter_mut()
+        .filter(|body| body.body_type == BodyType::Dynamic && body.is_active())
+    {
+        for generator in generators {
+            generator.apply(body, gravity, dt);
+        }
+    }
+}
+
+// Gives every dynamic body within the radius an impulse away from the center that fades out linearly to
+// zero at the radius, waking the bodies it reaches
+pub fn explode(bodies: &mut [Body], center: Vector3, radius: f64, impulse: f64) {
+    for body in bodies.iter_mut().filter(|body| body.body_type == BodyType::Dynamic) {
+        let offset = body.transform.position - center;
+        let distance = offset.magnitude();
+        if distance < radius && distance > f64::EPSILON {
+            let magnitude = impulse * (1.0 - distance / radius);
+            body.apply_impulse(offset * (magnitude / distance), body.transform.position);
+        }
+    }
+}
+
+// Volume of the body below the surface and its center. Spheres use the exact volume of the submerged cap.
+// Other shapes have their bounds split into cells, and each cell whose center is inside the shape stands
+// for an equal share of its volume, counting as submerged in proportion to how far below the surface it
+// reaches.
+fn submerged_volume(body: &Body, surface: &Plane) -> (f64, Vector3) {
+    let position = body.transform.position;
+    let shape = &body.shape;
+    if let Shape::Sphere { radius } = *shape {
+        let height = (radius - surface.signed_distance(position)).clamp(0.0, 2.0 * radius);
+        return (PI * height * height * (3.0 * radius - height) / 3.0, position);
+    }
+
+    let min = Vector3::new(
+        shape.support(Vector3::X_AXIS.inverse()).x,
+        shape.support(Vector3::Y_AXIS.inverse()).y,
+        shape.support(Vector3::Z_AXIS.inverse()).z,
+    );
+    let max = Vector3::new(
+        shape.support(Vector3::X_AXIS).x,
+        shape.support(Vector3::Y_AXIS).y,
+        shape.support(Vector3::Z_AXIS).z,
+    );
+    let cells = BUOYANCY_CELLS as f64;
+    let cell_size = (max - min) * (1.0 / cells);
+
+    // Extent of a cell along the surface normal, the same for every cell of the body
+    let (x_axis, y_axis, z_axis) = body.transform.rotation.basis_vectors();
+    let extent = 0.5
+        * ((x_axis * cell_size.x).dot(surface.normal).abs()
+            + (y_axis * cell_size.y).dot(surface.normal).abs()
+            + (z_axis * cell_size.z).dot(surface.normal).abs());
+
+    // Center of the cell at an index along an axis of the bounds
+    let offset = |index: usize, min: f64, size: f64| min + (index as f64 + 0.5) * size;
+
+    let (mut inside, mut submerged, mut moment) = (0, 0.0, Vector3::default());
+    for i in 0..BUOYANCY_CELLS {
+        for j in 0..BUOYANCY_CELLS {
+            for k in 0..BUOYANCY_CELLS {
+                let local = Vector3::new(
+                    offset(i, min.x, cell_size.x),
+                    offset(j, min.y, cell_size.y),
+                    offset(k, min.z, cell_size.z),
+                );
+                if !shape.contains_point(local) {
+                    continue;
+                }
+
+                let center = body.transform.point_to_world_space(local);
+                let depth = -surface.signed_distance(center);
+                let fraction = if extent > 0.0 {
+                    ((depth + extent) / (2.0 * extent)).clamp(0.0, 1.0)
+                } else if depth > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                };
+
+                inside += 1;
+                submerged += fraction;
+                moment += center * fraction;
+            }
+        }
+    }
+
+    if submerged > 0.0 {
+        (shape.volume() * submerged / inside as f64, moment * (1.0 / submerged))
+    } else {
+        (0.0, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::shape::ConvexHull;
+
+    fn body(shape: Shape, position: Vector3, rotation: Quaternion) -> Body {
+        Body::new(Transform::new(position, rotation), shape, 1.0)
+    }
+
+    fn submerged_fraction(shape: Shape, height: f64, rotation: Quaternion) -> f64 {
+        let surface = Plane::new(Vector3::Y_AXIS, Vector3::new(0.0, height, 0.0));
+        let volume = shape.volume();
+        submerged_volume(&body(shape, Vector3::default(), rotation), &surface).0 / volume
+    }
+
+    #[test]
+    fn submerged_volume_follows_the_shape_below_the_surface() {
+        let upright = Quaternion::default();
+        let on_its_side = Quaternion::from_axis_angle(Vector3::Z_AXIS, 0.5 * PI);
+        let sphere = Shape::Sphere { radius: 2.0 };
+        let cube = Shape::Box {
+            half_size: Vector3::new(1.0, 1.0, 1.0),
+        };
+        let capsule = Shape::Capsule {
+            radius: 1.0,
+            half_height: 4.0,
+        };
+        let (hull, _) = ConvexHull::new(&[
+            Vector3::new(-1.0, -1.0, -1.0),
+            Vector3::new(1.0, -1.0, -1.0),
+            Vector3::new(0.0, -1.0, 1.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ])
+        .unwrap();
+
+        for shape in [sphere, cube, capsule.clone(), Shape::ConvexHull(hull)] {
+            assert!(submerged_fraction(shape.clone(), 10.0, upright) > 1.0 - 1e-9);
+            assert!(submerged_fraction(shape.clone(), -10.0, upright) == 0.0);
+            assert!((submerged_fraction(shape.clone(), 0.0, on_its_side) - 0.5).abs() < 0.05);
+        }
+
+        // Water covering the lower cap and a short length of the side of a standing capsule, which a sphere
+        // of the same volume around its center would not even reach
+        let expected = (2.0 / 3.0 * PI + PI) / capsule.volume();
+        let fraction = submerged_fraction(capsule, -3.0, upright);
+        assert!(
+            (fraction - expected).abs() < 0.2 * expected,
+            "{fraction} of the capsule submerged instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn generators_push_awake_dynamic_bodies() {
+        let gravity = Vector3::new(0.0, -10.0, 0.0);
+        let mut bodies = [
+            body(Shape::Sphere { radius: 1.0 }, Vector3::new(5.0, 0.0, 0.0), Quaternion::default()),
+            body(Shape::Sphere { radius: 1.0 }, Vector3::new(5.0, 0.0, 0.0), Quaternion::default()),
+        ];
+        bodies[1].sleep();
+
+        let radial = ForceGenerator::Radial {
+            center: Vector3::default(),
+            radius: 10.0,
+            strength: 4.0,
+        };
+        apply_force_generators(&[radial], &mut bodies, gravity, 0.1);
+        assert!((bodies[0].force - Vector3::new(-2.0, 0.0, 0.0)).magnitude() < 1e-9);
+        assert!(bodies[1].is_sleeping && bodies[1].force.magnitude() == 0.0);
+
+        let wind = ForceGenerator::Wind {
+            volume: Aabb::new(Vector3::new(0.0, -1.0, -1.0), Vector3::new(10.0, 1.0, 1.0)),
+            velocity: Vector3::new(0.0, 0.0, 3.0),
+            drag: 0.5,
+        };
+        bodies[0].clear_forces();
+        bodies[0].linear_velocity = Vector3::new(1.0, 0.0, 0.0);
+        apply_force_generators(&[wind], &mut bodies, gravity, 0.1);
+        assert!((bodies[0].force - Vector3::new(-0.5, 0.0, 1.5)).magnitude() < 1e-9);
+
+        let damping = ForceGenerator::Damping {
+            linear: 1.0,
+            angular: 1.0,
+        };
+        apply_force_generators(&[damping], &mut bodies, gravity, 0.5);
+        assert!((bodies[0].linear_velocity.x - 1.0 / 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn buoyancy_keeps_bodies_without_volume_finite() {
+        let buoyancy = ForceGenerator::Buoyancy {
+            volume: Aabb::new(Vector3::new(-10.0, -10.0, -10.0), Vector3::new(10.0, 10.0, 10.0)),
+            surface: Plane::new(Vector3::Y_AXIS, Vector3::new(0.0, 5.0, 0.0)),
+            density: 0.5,
+            linear_drag: 1.0,
+            angular_drag: 1.0,
+        };
+        let flat = Shape::Box {
+            half_size: Vector3::new(1.0, 0.0, 1.0),
+        };
+        let mut bodies = [body(flat, Vector3::default(), Quaternion::default())];
+        bodies[0].linear_velocity = Vector3::new(1.0, 2.0, 3.0);
+
+        apply_force_generators(&[buoyancy], &mut bodies, Vector3::new(0.0, -10.0, 0.0), 0.1);
+        assert!(bodies[0].force.magnitude().is_finite() && bodies[0].torque.magnitude().is_finite());
+        assert!(bodies[0].linear_velocity.magnitude().is_finite());
+    }
+
+    #[test]
+    fn explosions_push_bodies_away_and_fade_with_distance() {
+        let sphere = |x: f64| {
+            body(Shape::Sphere { radius: 1.0 }, Vector3::new(x, 0.0, 0.0), Quaternion::default())
+        };
+        let mut bodies = [sphere(2.0), sphere(-6.0), sphere(12.0)];
+        bodies[1].sleep();
+
+        explode(&mut bodies, Vector3::default(), 10.0, 5.0);
+        assert!((bodies[0].linear_velocity - Vector3::new(4.0, 0.0, 0.0)).magnitude() < 1e-9);
+        assert!((bodies[1].linear_velocity - Vector3::new(-2.0, 0.0, 0.0)).magnitude() < 1e-9);
+        assert!(!bodies[1].is_sleeping);
+        assert!(bodies[2].linear_velocity.magnitude() == 0.0);
+    }
+}
@@ -0,0 +1,490 @@
+use crate::transform::*;
+
+// GJK stops once a new support point gets the simplex closer to the origin by less than this fraction of
+// the squared distance, and EPA once the polytope can't be expanded by more than this fraction of depth
+const GJK_RELATIVE_TOLERANCE: f64 = 1e-9;
+const EPA_RELATIVE_TOLERANCE: f64 = 1e-6;
+
+// Curved shapes never converge exactly, so both algorithms give up after this many iterations
+const GJK_MAX_ITERATIONS: usize = 64;
+const EPA_MAX_ITERATIONS: usize = 64;
+
+// Squared distances below this count as the origin lying on the simplex
+const ORIGIN_EPSILON: f64 = 1e-12;
+
+// Convex shape described by the world space point of the shape furthest along any direction. This is all
+// GJK and EPA need, so any convex shape implementing it can collide with any other.
+pub trait SupportMap {
+    fn support(&self, direction: Vector3) -> Vector3;
+}
+
+// A single point, for finding the distance from a point to a shape
+impl SupportMap for Vector3 {
+    fn support(&self, _: Vector3) -> Vector3 {
+        *self
+    }
+}
+
+// Point of the Minkowski difference a - b along with the points of each shape it came from
+#[derive(Clone, Copy, Debug)]
+struct SupportPoint {
+    point: Vector3,
+    a: Vector3,
+    b: Vector3,
+}
+
+impl SupportPoint {
+    fn new(
+        a: &(impl SupportMap + ?Sized),
+        b: &(impl SupportMap + ?Sized),
+        direction: Vector3,
+    ) -> SupportPoint {
+        let (a, b) = (a.support(direction), b.support(direction.inverse()));
+        SupportPoint { point: a - b, a, b }
+    }
+}
+
+// Simplex of the Minkowski difference that encloses the origin, the starting polytope for EPA
+#[derive(Clone, Debug)]
+pub struct Simplex(Vec<SupportPoint>);
+
+pub enum GjkResult {
+    // Closest points on the surface of each shape
+    Separated { point_a: Vector3, point_b: Vector3 },
+    Intersecting(Simplex),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Penetration {
+    // Unit normal pointing from the first shape towards the second
+    pub normal: Vector3,
+    pub depth: f64,
+    // Deepest point of each shape inside the other
+    pub point_a: Vector3,
+    pub point_b: Vector3,
+}
+
+// Distance GJK: walks a simplex of the Minkowski difference a - b towards the origin. The shapes
+// intersect when the simplex ends up containing the origin, otherwise the point of the simplex closest
+// to the origin gives the closest points between the shapes.
+pub fn gjk(a: &(impl SupportMap + ?Sized), b: &(impl SupportMap + ?Sized)) -> GjkResult {
+    let mut simplex = vec![SupportPoint::new(a, b, Vector3::X_AXIS)];
+    let mut weights = vec![1.0];
+    let mut closest = simplex[0].point;
+
+    for _ in 0..GJK_MAX_ITERATIONS {
+        let distance_squared = closest.dot(closest);
+        if distance_squared <= ORIGIN_EPSILON {
+            return GjkResult::Intersecting(Simplex(simplex));
+        }
+
+        let new_point = SupportPoint::new(a, b, closest.inverse());
+        let is_duplicate = simplex.iter().any(|point| {
+            let offset = point.point - new_point.point;
+            offset.dot(offset) <= ORIGIN_EPSILON
+        });
+        let progress = distance_squared - closest.dot(new_point.point);
+        if is_duplicate || progress <= GJK_RELATIVE_TOLERANCE * distance_squared {
+            break;
+        }
+
+        simplex.push(new_point);
+        match closest_on_simplex(&simplex) {
+            Some(reduced) => {
+                simplex = reduced.iter().map(|&(point, _)| point).collect();
+                weights = reduced.iter().map(|&(_, weight)| weight).collect();
+                closest = simplex
+                    .iter()
+                    .zip(weights.iter())
+                    .fold(Vector3::default(), |sum, (point, &weight)| sum + point.point * weight);
+            }
+            None => return GjkResult::Intersecting(Simplex(simplex)),
+        }
+    }
+
+    let combine = |shape_point: fn(&SupportPoint) -> Vector3| {
+        simplex
+            .iter()
+            .zip(weights.iter())
+            .fold(Vector3::default(), |sum, (point, &weight)| sum + shape_point(point) * weight)
+    };
+
+    GjkResult::Separated {
+        point_a: combine(|point| point.a),
+        point_b: combine(|point| point.b),
+    }
+}
+
+pub fn intersects(a: &(impl SupportMap + ?Sized), b: &(impl SupportMap + ?Sized)) -> bool {
+    matches!(gjk(a, b), GjkResult::Intersecting(_))
+}
+
+// Expanding polytope algorithm: grows the simplex GJK ended with towards the surface of the Minkowski
+// difference until the face closest to the origin lies on it, which gives the direction and depth of the
+// smallest translation separating the shapes. Returns None for shapes that only touch or are flat.
+pub fn epa(
+    a: &(impl SupportMap + ?Sized),
+    b: &(impl SupportMap + ?Sized),
+    simplex: Simplex,
+) -> Option<Penetration> {
+    let mut vertices = blow_up_simplex(a, b, simplex.0)?;
+
+    // Wind the tetrahedron so the faces below all point outwards
+    if (vertices[1].point - vertices[0].point)
+        .cross(vertices[2].point - vertices[0].point)
+        .dot(vertices[3].point - vertices[0].point)
+        > 0.0
+    {
+        vertices.swap(1, 2);
+    }
+
+    let mut faces: Vec<EpaFace> = [[0, 1, 2], [0, 2, 3], [0, 3, 1], [1, 3, 2]]
+        .into_iter()
+        .filter_map(|indices| EpaFace::new(&vertices, indices))
+        .collect();
+
+    for _ in 0..EPA_MAX_ITERATIONS {
+        let closest = faces.iter().min_by(|a, b| a.distance.total_cmp(&b.distance))?.clone();
+
+        let new_point = SupportPoint::new(a, b, closest.normal);
+        let expansion = new_point.point.dot(closest.normal) - closest.distance;
+        if expansion <= EPA_RELATIVE_TOLERANCE * closest.distance.abs().max(1.0) {
+            return penetration_from_face(&vertices, &closest);
+        }
+
+        // Remove every face the new point can see and patch the hole with faces to the new point along
+        // the horizon, the boundary between the visible and hidden faces
+        let new_index = vertices.len();
+        vertices.push(new_point);
+
+        let mut horizon: Vec<(usize, usize)> = Vec::new();
+        faces.retain(|face| {
+            let is_visible = face.normal.dot(new_point.point - vertices[face.indices[0]].point) > 0.0;
+            if is_visible {
+                for i in 0..3 {
+                    let edge = (face.indices[i], face.indices[(i + 1) % 3]);
+                    match horizon.iter().position(|&other| other == (edge.1, edge.0)) {
+                        Some(shared) => {
+                            horizon.swap_remove(shared);
+                        }
+                        None => horizon.push(edge),
+                    }
+                }
+            }
+            !is_visible
+        });
+
+        faces.extend(
+            horizon
+                .into_iter()
+                .filter_map(|(start, end)| EpaFace::new(&vertices, [start, end, new_index])),
+        );
+    }
+
+    // Out of iterations, the closest face found so far is the best estimate
+    let closest = faces.iter().min_by(|a, b| a.distance.total_cmp(&b.distance))?;
+    penetration_from_face(&vertices, closest)
+}
+
+#[derive(Clone, Debug)]
+struct EpaFace {
+    indices: [usize; 3],
+    normal: Vector3,
+    // Distance of the face plane from the origin
+    distance: f64,
+}
+
+impl EpaFace {
+    fn new(vertices: &[SupportPoint], indices: [usize; 3]) -> Option<EpaFace> {
+        let [a, b, c] = indices.map(|index| vertices[index].point);
+        let normal = (b - a).cross(c - a);
+        if normal.magnitude() <= f64::EPSILON {
+            return None;
+        }
+
+        let normal = normal.unit();
+        Some(EpaFace {
+            indices,
+            normal,
+            distance: normal.dot(a),
+        })
+    }
+}
+
+fn penetration_from_face(vertices: &[SupportPoint], face: &EpaFace) -> Option<Penetration> {
+    let points = face.indices.map(|index| vertices[index]);
+    let weights = closest_on_triangle(points.map(|point| point.point), face.normal * face.distance);
+    let combine = |shape_point: fn(&SupportPoint) -> Vector3| {
+        (0..3).fold(Vector3::default(), |sum, i| sum + shape_point(&points[i]) * weights[i])
+    };
+
+    Some(Penetration {
+        normal: face.normal,
+        depth: face.distance,
+        point_a: combine(|point| point.a),
+        point_b: combine(|point| point.b),
+    })
+}
+
+// GJK can stop with fewer than four points when the origin lies on the surface of a simplex, so extra
+// support points are added in directions that give the simplex volume
+fn blow_up_simplex(
+    a: &(impl SupportMap + ?Sized),
+    b: &(impl SupportMap + ?Sized),
+    mut simplex: Vec<SupportPoint>,
+) -> Option<Vec<SupportPoint>> {
+    const AXES: [Vector3; 3] = [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS];
+
+    let adds_volume = |simplex: &[SupportPoint], point: &SupportPoint| match simplex.len() {
+        1 => (point.point - simplex[0].point).magnitude() > f64::EPSILON,
+        2 => (simplex[1].point - simplex[0].point)
+            .cross(point.point - simplex[0].point)
+            .magnitude()
+            > f64::EPSILON,
+        _ => (simplex[1].point - simplex[0].point)
+            .cross(simplex[2].point - simplex[0].point)
+            .dot(point.point - simplex[0].point)
+            .abs()
+            > f64::EPSILON,
+    };
+
+    while simplex.len() < 4 {
+        let directions: Vec<Vector3> = match simplex.len() {
+            1 => AXES.to_vec(),
+            2 => {
+                let segment = simplex[1].point - simplex[0].point;
+                AXES.iter().map(|&axis| segment.cross(axis)).collect()
+            }
+            _ => vec![(simplex[1].point - simplex[0].point).cross(simplex[2].point - simplex[0].point)],
+        };
+
+        let new_point = directions
+            .into_iter()
+            .filter(|direction| direction.magnitude() > f64::EPSILON)
+            .flat_map(|direction| [direction, direction.inverse()])
+            .map(|direction| SupportPoint::new(a, b, direction))
+            .find(|point| adds_volume(&simplex, point))?;
+        simplex.push(new_point);
+    }
+
+    Some(simplex)
+}
+
+// Point of the simplex closest to the origin, as the points of the smallest sub-simplex containing it
+// with their barycentric weights. None when the simplex is a tetrahedron containing the origin.
+fn closest_on_simplex(simplex: &[SupportPoint]) -> Option<Vec<(SupportPoint, f64)>> {
+    let reduce = |points: &[SupportPoint], weights: &[f64]| {
+        points
+            .iter()
+            .copied()
+            .zip(weights.iter().copied())
+            .filter(|&(_, weight)| weight > 0.0)
+            .collect::<Vec<_>>()
+    };
+
+    match simplex {
+        [a] => Some(vec![(*a, 1.0)]),
+        [a, b] => Some(reduce(simplex, &closest_on_segment(a.point, b.point))),
+        [a, b, c] => {
+            let closest = closest_on_triangle([a.point, b.point, c.point], Vector3::default());
+            Some(reduce(simplex, &closest))
+        }
+        [_, _, _, _] => {
+            // Faces of the tetrahedron with the origin on the other side from the remaining vertex
+            let mut best: Option<(Vec<(SupportPoint, f64)>, f64)> = None;
+            for [i, j, k, opposite] in [[0, 1, 2, 3], [0, 2, 3, 1], [0, 3, 1, 2], [1, 3, 2, 0]] {
+                let face = [simplex[i], simplex[j], simplex[k]];
+                let normal = (face[1].point - face[0].point).cross(face[2].point - face[0].point);
+                let origin_side = normal.dot(face[0].point.inverse());
+                let opposite_side = normal.dot(simplex[opposite].point - face[0].point);
+                if origin_side * opposite_side > 0.0 {
+                    continue;
+                }
+
+                let weights = closest_on_triangle(face.map(|point| point.point), Vector3::default());
+                let closest = (0..3).fold(Vector3::default(), |sum, i| sum + face[i].point * weights[i]);
+                let distance = closest.dot(closest);
+                if best.as_ref().is_none_or(|(_, best_distance)| distance < *best_distance) {
+                    best = Some((reduce(&face, &weights), distance));
+                }
+            }
+
+            best.map(|(reduced, _)| reduced)
+        }
+        _ => None,
+    }
+}
+
+fn closest_on_segment(a: Vector3, b: Vector3) -> [f64; 2] {
+    let segment = b - a;
+    let length_squared = segment.dot(segment);
+    if length_squared <= 0.0 {
+        return [1.0, 0.0];
+    }
+
+    let t = (a.inverse().dot(segment) / length_squared).clamp(0.0, 1.0);
+    [1.0 - t, t]
+}
+
+// Barycentric weights of the point of a triangle closest to p, by finding the Voronoi region of the
+// triangle that p lies in (from Ericson's Real-Time Collision Detection)
+fn closest_on_triangle([a, b, c]: [Vector3; 3], p: Vector3) -> [f64; 3] {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return [1.0, 0.0, 0.0];
+    }
+
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return [0.0, 1.0, 0.0];
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return [1.0 - v, v, 0.0];
+    }
+
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return [0.0, 0.0, 1.0];
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return [1.0 - w, 0.0, w];
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return [0.0, 1.0 - w, w];
+    }
+
+    // Inside the face. A degenerate triangle has no interior, so fall back to its closest edge.
+    let total = va + vb + vc;
+    if total.abs() <= f64::EPSILON {
+        let edge_weights = |[u, v]: [f64; 2], [i, j]: [usize; 2]| {
+            let mut weights = [0.0; 3];
+            weights[i] = u;
+            weights[j] = v;
+            weights
+        };
+        let distance = |weights: &[f64; 3]| {
+            let point = a * weights[0] + b * weights[1] + c * weights[2] - p;
+            point.dot(point)
+        };
+        return [
+            edge_weights(closest_on_segment(a - p, b - p), [0, 1]),
+            edge_weights(closest_on_segment(b - p, c - p), [1, 2]),
+            edge_weights(closest_on_segment(c - p, a - p), [2, 0]),
+        ]
+        .into_iter()
+        .min_by(|x, y| distance(x).total_cmp(&distance(y)))
+        .unwrap();
+    }
+
+    let (v, w) = (vb / total, vc / total);
+    [1.0 - v - w, v, w]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ball {
+        center: Vector3,
+        radius: f64,
+    }
+
+    impl SupportMap for Ball {
+        fn support(&self, direction: Vector3) -> Vector3 {
+            self.center + direction.unit() * self.radius
+        }
+    }
+
+    struct Cube {
+        center: Vector3,
+        half_size: f64,
+    }
+
+    impl SupportMap for Cube {
+        fn support(&self, direction: Vector3) -> Vector3 {
+            let sign = |value: f64| if value < 0.0 { -self.half_size } else { self.half_size };
+            self.center + Vector3::new(sign(direction.x), sign(direction.y), sign(direction.z))
+        }
+    }
+
+    fn assert_near(actual: Vector3, expected: Vector3, tolerance: f64) {
+        assert!(
+            (actual - expected).magnitude() < tolerance,
+            "{actual:?} is not within {tolerance} of {expected:?}"
+        );
+    }
+
+    #[test]
+    fn separated_balls_give_closest_points() {
+        let a = Ball { center: Vector3::default(), radius: 1.0 };
+        let b = Ball { center: Vector3::new(5.0, 0.0, 0.0), radius: 2.0 };
+
+        let GjkResult::Separated { point_a, point_b } = gjk(&a, &b) else {
+            panic!("balls 2 apart should be separated");
+        };
+        assert_near(point_a, Vector3::new(1.0, 0.0, 0.0), 1e-3);
+        assert_near(point_b, Vector3::new(3.0, 0.0, 0.0), 1e-3);
+        assert!(!intersects(&a, &b));
+    }
+
+    #[test]
+    fn separated_cubes_give_the_gap_between_faces() {
+        let a = Cube { center: Vector3::default(), half_size: 1.0 };
+        let b = Cube { center: Vector3::new(3.0, 0.5, -0.25), half_size: 1.0 };
+
+        let GjkResult::Separated { point_a, point_b } = gjk(&a, &b) else {
+            panic!("cubes 1 apart should be separated");
+        };
+        assert!((point_a.x - 1.0).abs() < 1e-9 && (point_b.x - 2.0).abs() < 1e-9);
+        assert!(((point_b - point_a).magnitude() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn point_distance_to_a_cube() {
+        let cube = Cube { center: Vector3::default(), half_size: 1.0 };
+        let GjkResult::Separated { point_b, .. } = gjk(&Vector3::new(3.0, 3.0, 0.5), &cube) else {
+            panic!("point outside of the cube should be separated");
+        };
+        assert_near(point_b, Vector3::new(1.0, 1.0, 0.5), 1e-9);
+    }
+
+    #[test]
+    fn overlapping_cubes_give_the_smallest_penetration() {
+        let a = Cube { center: Vector3::default(), half_size: 1.0 };
+        let b = Cube { center: Vector3::new(1.5, 0.2, -0.1), half_size: 1.0 };
+
+        let GjkResult::Intersecting(simplex) = gjk(&a, &b) else {
+            panic!("cubes 0.5 deep in each other should intersect");
+        };
+        let penetration = epa(&a, &b, simplex).expect("overlapping cubes should have a penetration");
+        assert_near(penetration.normal, Vector3::X_AXIS, 1e-6);
+        assert!((penetration.depth - 0.5).abs() < 1e-6);
+        assert!(((penetration.point_a - penetration.point_b).dot(penetration.normal) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn overlapping_balls_give_the_penetration_along_their_centers() {
+        let a = Ball { center: Vector3::default(), radius: 2.0 };
+        let b = Ball { center: Vector3::new(0.0, 3.0, 0.0), radius: 2.0 };
+
+        let GjkResult::Intersecting(simplex) = gjk(&a, &b) else {
+            panic!("balls 1 deep in each other should intersect");
+        };
+        let penetration = epa(&a, &b, simplex).expect("overlapping balls should have a penetration");
+        assert_near(penetration.normal, Vector3::Y_AXIS, 1e-2);
+        assert!((penetration.depth - 1.0).abs() < 1e-2);
+        assert!(intersects(&a, &b));
+    }
+}
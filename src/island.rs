@@ -0,0 +1,179 @@
+use crate::{
+    body::{Body, BodyType},
+    body_set::BodySet,
+    event::BodyPair,
+    joint::Joint,
+    solver::Contact,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SleepSettings {
+    pub enabled: bool,
+    // Speeds below which a body counts as resting
+    pub linear_threshold: f64,
+    pub angular_threshold: f64,
+    // How long every body of an island has to rest before the island goes to sleep
+    pub time_to_sleep: f64,
+}
+
+impl Default for SleepSettings {
+    fn default() -> Self {
+        SleepSettings {
+            enabled: true,
+            linear_threshold: 1.0,
+            angular_threshold: 0.1,
+            time_to_sleep: 0.5,
+        }
+    }
+}
+
+// Groups the bodies that touch or are jointed, directly or through other bodies, with a union-find.
+// Sleeping bodies have no contacts with each other, so the pairs still touching as of the last step hold
+// a sleeping stack together as one island that wakes as a whole. Immovable bodies don't join islands
+// together, so everything resting on the same floor isn't one island. Islands are listed by their lowest
+// body index with their bodies in index order, where indices are positions in the body set.
+pub fn find_islands(
+    body_set: &BodySet,
+    contacts: &[Contact],
+    touching: &[BodyPair],
+    joints: &[Joint],
+    islands: &mut Vec<Vec<usize>>,
+) {
+    let bodies = body_set.as_slice();
+    let mut parents: Vec<usize> = (0..bodies.len()).collect();
+
+    fn find(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+
+    let links = contacts
+        .iter()
+        .map(|contact| (contact.body1, contact.body2))
+        .chain(touching.iter().filter_map(|&(body1, body2)| {
+            Some((body_set.index_of(body1)?, Some(body_set.index_of(body2?)?)))
+        }))
+        .chain(joints.iter().filter_map(|joint| {
+            let body2 = joint.body2.and_then(|body2| body_set.index_of(body2));
+            Some((body_set.index_of(joint.body1)?, body2))
+        }));
+
+    for (body1, body2) in links {
+        let Some(body2) = body2 else {
+            continue;
+        };
+        if bodies[body1].inverse_mass == 0.0 || bodies[body2].inverse_mass == 0.0 {
+            continue;
+        }
+
+        let (root1, root2) = (find(&mut parents, body1), find(&mut parents, body2));
+        parents[root1.max(root2)] = root1.min(root2);
+    }
+
+    islands.clear();
+    let mut island_of_root = vec![usize::MAX; bodies.len()];
+    for index in 0..bodies.len() {
+        let root = find(&mut parents, index);
+        if island_of_root[root] == usize::MAX {
+            island_of_root[root] = islands.len();
+            islands.push(Vec::new());
+        }
+        islands[island_of_root[root]].push(index);
+    }
+}
+
+// Advances the rest timers of the bodies, then puts islands whose bodies have all rested long enough to
+// sleep together and wakes every body of an island in which something is moving, so a body landing on a
+// sleeping stack wakes the whole stack
+pub fn update_sleep(bodies: &mut [Body], islands: &[Vec<usize>], dt: f64, settings: &SleepSettings) {
+    for body in bodies.iter_mut().filter(|body| body.body_type == BodyType::Dynamic && body.is_active()) {
+        if body.linear_velocity.magnitude() < settings.linear_threshold
+            && body.angular_velocity.magnitude() < settings.angular_threshold
+        {
+            body.sleep_time += dt;
+        } else {
+            body.sleep_time = 0.0;
+        }
+    }
+
+    for island in islands {
+        let movable = || island.iter().copied().filter(|&index| bodies[index].inverse_mass > 0.0);
+
+        let is_moving = movable().any(|index| !bodies[index].is_sleeping && bodies[index].sleep_time == 0.0);
+        let is_resting = movable()
+            .all(|index| bodies[index].is_sleeping || bodies[index].sleep_time >= settings.time_to_sleep);
+
+        if is_moving {
+            for index in island.iter().copied() {
+                if bodies[index].is_sleeping {
+                    bodies[index].wake();
+                }
+            }
+        } else if is_resting {
+            for index in island.iter().copied() {
+                if bodies[index].inverse_mass > 0.0 && !bodies[index].is_sleeping {
+                    bodies[index].sleep();
+                }
+            }
+        }
+    }
+}
+
+// Wakes sleeping bodies that a moving kinematic body touches, which islands miss since kinematic bodies
+// don't join them, so a moving platform carries what sleeps on it instead of passing through
+pub fn wake_touched_by_kinematic(bodies: &mut [Body], contacts: &[Contact]) {
+    for contact in contacts {
+        let Some(body2) = contact.body2 else {
+            continue;
+        };
+
+        for (pusher, pushed) in [(contact.body1, body2), (body2, contact.body1)] {
+            if bodies[pusher].body_type == BodyType::Kinematic && bodies[pushed].is_sleeping {
+                bodies[pushed].wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{body::Body, collision::Plane, physics::PhysicsWorld, shape::Shape, transform::*};
+
+    #[test]
+    fn a_body_landing_on_a_sleeping_stack_wakes_the_whole_stack() {
+        let mut world = PhysicsWorld::new();
+        world.add_plane(Plane::new(Vector3::Y_AXIS, Vector3::default()));
+        let cube = |y: f64| {
+            Body::new(
+                Transform::new(Vector3::new(0.0, y, 0.0), Quaternion::default()),
+                Shape::Box {
+                    half_size: Vector3::new(5.0, 5.0, 5.0),
+                },
+                1.0,
+            )
+        };
+        let stack: Vec<_> = (0..3).map(|i| world.add_body(cube(5.0 + 10.0 * i as f64))).collect();
+
+        for _ in 0..300 {
+            world.step();
+        }
+        assert!(stack.iter().all(|&body| world.bodies[body].is_sleeping), "stack never fell asleep");
+
+        world.add_body(Body {
+            linear_velocity: Vector3::new(0.0, -20.0, 0.0),
+            ..cube(40.0)
+        });
+        for _ in 0..60 {
+            world.step();
+            let awake = stack.iter().filter(|&&body| !world.bodies[body].is_sleeping).count();
+            if awake > 0 {
+                assert_eq!(awake, stack.len(), "only {awake} of the stack woke up");
+                return;
+            }
+        }
+        panic!("the stack slept through the landing");
+    }
+}
@@ -0,0 +1,517 @@
+use crate::{
+    body_set::{BodyHandle, BodySet},
+    solver::*,
+    transform::*,
+};
+
+// Range of a hinge angle in radians or of a slider translation
+#[derive(Clone, Copy, Debug)]
+pub struct JointLimit {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+// Drives a hinge or slider towards a relative speed, pushing with at most max_force (a torque for hinges)
+#[derive(Clone, Copy, Debug)]
+pub struct JointMotor {
+    pub speed: f64,
+    pub max_force: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Spring {
+    pub stiffness: f64,
+    pub damping: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum JointKind {
+    // Anchors held together, free rotation
+    BallSocket,
+    // Anchors held together, rotation only about the axis
+    Hinge {
+        limit: Option<JointLimit>,
+        motor: Option<JointMotor>,
+    },
+    // Rotation held, translation only along the axis
+    Slider {
+        limit: Option<JointLimit>,
+        motor: Option<JointMotor>,
+    },
+    // Anchors and rotation held
+    Fixed,
+    // Anchors kept at a distance, rigidly or through a spring
+    Distance { length: f64, spring: Option<Spring> },
+}
+
+// Constraint between two bodies, referenced by their handles. The second body is None when the first body
+// is attached to the world, in which case its anchor, axis and rotation are in world space.
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub body1: BodyHandle,
+    pub body2: Option<BodyHandle>,
+    // Anchor point of each body in its own space
+    pub anchor1: Vector3,
+    pub anchor2: Vector3,
+    // Unit joint axis of each body in its own space
+    pub axis1: Vector3,
+    pub axis2: Vector3,
+    // Rotation of the second body relative to the first when the joint was made, which fixed and slider
+    // joints hold and hinges measure their angle from
+    pub reference_rotation: Quaternion,
+    pub kind: JointKind,
+    // Whether the two bodies still collide with each other
+    pub collide_connected: bool,
+}
+
+impl Joint {
+    // Builds a joint from world space anchors and axis at the current transforms of the bodies
+    pub fn new(
+        bodies: &BodySet,
+        body1: BodyHandle,
+        body2: Option<BodyHandle>,
+        anchor1: Vector3,
+        anchor2: Vector3,
+        axis: Vector3,
+        kind: JointKind,
+    ) -> Joint {
+        let transform1 = &bodies[body1].transform;
+        let transform2 = body2.map_or(Transform::default(), |body2| bodies[body2].transform.clone());
+        let axis = axis.unit();
+
+        Joint {
+            body1,
+            body2,
+            anchor1: transform1.point_to_local_space(anchor1),
+            anchor2: transform2.point_to_local_space(anchor2),
+            axis1: transform1.rotation.vector_to_local_space(axis),
+            axis2: transform2.rotation.vector_to_local_space(axis),
+            reference_rotation: (transform1.rotation.inverse() * transform2.rotation).unit(),
+            kind,
+            collide_connected: false,
+        }
+    }
+
+    pub fn ball_socket(
+        bodies: &BodySet,
+        body1: BodyHandle,
+        body2: Option<BodyHandle>,
+        anchor: Vector3,
+    ) -> Joint {
+        Joint::new(bodies, body1, body2, anchor, anchor, Vector3::Y_AXIS, JointKind::BallSocket)
+    }
+
+    pub fn hinge(
+        bodies: &BodySet,
+        body1: BodyHandle,
+        body2: Option<BodyHandle>,
+        anchor: Vector3,
+        axis: Vector3,
+    ) -> Joint {
+        let kind = JointKind::Hinge {
+            limit: None,
+            motor: None,
+        };
+        Joint::new(bodies, body1, body2, anchor, anchor, axis, kind)
+    }
+
+    // The anchor of a slider is the first body's position, so its translation starts at zero
+    pub fn slider(bodies: &BodySet, body1: BodyHandle, body2: Option<BodyHandle>, axis: Vector3) -> Joint {
+        let anchor = bodies[body1].transform.position;
+        let kind = JointKind::Slider {
+            limit: None,
+            motor: None,
+        };
+        Joint::new(bodies, body1, body2, anchor, anchor, axis, kind)
+    }
+
+    pub fn fixed(bodies: &BodySet, body1: BodyHandle, body2: Option<BodyHandle>) -> Joint {
+        let anchor = bodies[body1].transform.position;
+        Joint::new(bodies, body1, body2, anchor, anchor, Vector3::Y_AXIS, JointKind::Fixed)
+    }
+
+    // Keeps the anchors at their current distance
+    pub fn distance(
+        bodies: &BodySet,
+        body1: BodyHandle,
+        body2: Option<BodyHandle>,
+        anchor1: Vector3,
+        anchor2: Vector3,
+    ) -> Joint {
+        let kind = JointKind::Distance {
+            length: (anchor2 - anchor1).magnitude(),
+            spring: None,
+        };
+        Joint::new(bodies, body1, body2, anchor1, anchor2, Vector3::Y_AXIS, kind)
+    }
+
+    pub fn with_limit(mut self, lower: f64, upper: f64) -> Self {
+        if let JointKind::Hinge { limit, .. } | JointKind::Slider { limit, .. } = &mut self.kind {
+            *limit = Some(JointLimit { lower, upper });
+        }
+        self
+    }
+
+    pub fn with_motor(mut self, speed: f64, max_force: f64) -> Self {
+        if let JointKind::Hinge { motor, .. } | JointKind::Slider { motor, .. } = &mut self.kind {
+            *motor = Some(JointMotor { speed, max_force });
+        }
+        self
+    }
+
+    pub fn with_spring(mut self, stiffness: f64, damping: f64) -> Self {
+        if let JointKind::Distance { spring, .. } = &mut self.kind {
+            *spring = Some(Spring { stiffness, damping });
+        }
+        self
+    }
+
+    pub fn with_collide_connected(mut self, collide_connected: bool) -> Self {
+        self.collide_connected = collide_connected;
+        self
+    }
+
+    // Whether the joint connects the two bodies, in either order
+    pub fn connects(&self, body1: BodyHandle, body2: BodyHandle) -> bool {
+        (self.body1, self.body2) == (body1, Some(body2)) || (self.body1, self.body2) == (body2, Some(body1))
+    }
+
+    // Rotation of the second body relative to the first about the hinge axis, away from the reference
+    // rotation, in radians. Motors drive the same relative rotation.
+    pub fn angle(&self, bodies: &BodySet) -> f64 {
+        let frame = JointFrame::new(self, bodies);
+        let error = frame.rotation_error();
+        2.0 * Vector3::new(error.x, error.y, error.z).dot(frame.axis).atan2(error.w)
+    }
+
+    // Translation of the second body relative to the first along the slider axis
+    pub fn translation(&self, bodies: &BodySet) -> f64 {
+        let frame = JointFrame::new(self, bodies);
+        (frame.point2 - frame.point1).dot(frame.axis)
+    }
+
+    // Writes the scalar constraints that make up the joint, with biases that correct a fraction of the
+    // position error each step like contacts do. The rows refer to bodies by their position in the set and
+    // a joint whose bodies aren't all in the set writes none.
+    pub fn constraint_rows(
+        &self,
+        bodies: &BodySet,
+        dt: f64,
+        settings: &SolverSettings,
+        rows: &mut Vec<ConstraintRow>,
+    ) {
+        let Some(body1) = bodies.index_of(self.body1) else {
+            return;
+        };
+        let body2 = match self.body2 {
+            Some(body2) => match bodies.index_of(body2) {
+                Some(body2) => Some(body2),
+                None => return,
+            },
+            None => None,
+        };
+
+        let frame = JointFrame::new(self, bodies);
+        let bias_rate = settings.baumgarte / dt;
+        let offset = frame.point2 - frame.point1;
+
+        let row = |linear: Vector3, angular1: Vector3, angular2: Vector3, error: f64| ConstraintRow {
+            body1,
+            body2,
+            linear,
+            angular1,
+            angular2,
+            bias: -bias_rate * error,
+            softness: 0.0,
+            min_impulse: f64::MIN,
+            max_impulse: f64::MAX,
+        };
+
+        // Holds the anchors together along each world axis
+        let point_rows = |rows: &mut Vec<ConstraintRow>| {
+            for axis in [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS] {
+                rows.push(row(axis, frame.r1.cross(axis), frame.r2.cross(axis), offset.dot(axis)));
+            }
+        };
+
+        // Holds the relative rotation along each world axis
+        let rotation_rows = |rows: &mut Vec<ConstraintRow>| {
+            let error = frame.rotation_error();
+            let error = 2.0 * Vector3::new(error.x, error.y, error.z);
+            for axis in [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS] {
+                rows.push(row(Vector3::default(), axis, axis, error.dot(axis)));
+            }
+        };
+
+        // Limit and motor along the axis, where the error of a violated limit is how far past it the
+        // joint is and the impulse may only push back towards the allowed range
+        let limit_and_motor_rows = |rows: &mut Vec<ConstraintRow>,
+                                    limit: Option<JointLimit>,
+                                    motor: Option<JointMotor>,
+                                    position: f64,
+                                    axis_row: &dyn Fn(f64) -> ConstraintRow| {
+            if let Some(limit) = limit {
+                if position <= limit.lower {
+                    rows.push(ConstraintRow {
+                        min_impulse: 0.0,
+                        ..axis_row(position - limit.lower)
+                    });
+                }
+                if position >= limit.upper {
+                    rows.push(ConstraintRow {
+                        max_impulse: 0.0,
+                        ..axis_row(position - limit.upper)
+                    });
+                }
+            }
+
+            if let Some(motor) = motor {
+                let max_impulse = motor.max_force * dt;
+                rows.push(ConstraintRow {
+                    bias: motor.speed,
+                    min_impulse: -max_impulse,
+                    max_impulse,
+                    ..axis_row(0.0)
+                });
+            }
+        };
+
+        match self.kind {
+            JointKind::BallSocket => point_rows(rows),
+            JointKind::Hinge { limit, motor } => {
+                point_rows(rows);
+
+                // Keep the axes of both bodies aligned by holding rotation about the two perpendicular axes
+                let error = frame.axis.cross(frame.axis2);
+                for tangent in tangent_basis(frame.axis) {
+                    rows.push(row(Vector3::default(), tangent, tangent, error.dot(tangent)));
+                }
+
+                let axis_row = |error: f64| row(Vector3::default(), frame.axis, frame.axis, error);
+                limit_and_motor_rows(rows, limit, motor, self.angle(bodies), &axis_row);
+            }
+            JointKind::Slider { limit, motor } => {
+                rotation_rows(rows);
+
+                // Offsets are measured from the first anchor, so push the first body at the second one
+                let r1 = frame.r1 + offset;
+                for tangent in tangent_basis(frame.axis) {
+                    rows.push(row(tangent, r1.cross(tangent), frame.r2.cross(tangent), offset.dot(tangent)));
+                }
+
+                let axis_row =
+                    |error: f64| row(frame.axis, r1.cross(frame.axis), frame.r2.cross(frame.axis), error);
+                limit_and_motor_rows(rows, limit, motor, offset.dot(frame.axis), &axis_row);
+            }
+            JointKind::Fixed => {
+                point_rows(rows);
+                rotation_rows(rows);
+            }
+            JointKind::Distance { length, spring } => {
+                let distance = offset.magnitude();
+                if distance <= f64::EPSILON {
+                    return;
+                }
+
+                let direction = offset * (1.0 / distance);
+                let error = distance - length;
+                let distance_row =
+                    row(direction, frame.r1.cross(direction), frame.r2.cross(direction), error);
+
+                // A spring is a soft constraint: the softness lets the impulse give way in proportion to
+                // how much has already been applied, which behaves like an implicit spring and damper
+                match spring {
+                    Some(spring) if spring.damping + dt * spring.stiffness > 0.0 => {
+                        let softness = 1.0 / (dt * (spring.damping + dt * spring.stiffness));
+                        rows.push(ConstraintRow {
+                            bias: -error * dt * spring.stiffness * softness,
+                            softness,
+                            ..distance_row
+                        });
+                    }
+                    _ => rows.push(distance_row),
+                }
+            }
+        }
+    }
+}
+
+// World space anchors and axes of a joint at the current transforms of its bodies
+struct JointFrame {
+    point1: Vector3,
+    point2: Vector3,
+    // Offsets of the anchors from the centers of their bodies
+    r1: Vector3,
+    r2: Vector3,
+    axis: Vector3,
+    axis2: Vector3,
+    rotation1: Quaternion,
+    rotation2: Quaternion,
+    reference_rotation: Quaternion,
+}
+
+impl JointFrame {
+    fn new(joint: &Joint, bodies: &BodySet) -> JointFrame {
+        let transform1 = &bodies[joint.body1].transform;
+        let transform2 = joint
+            .body2
+            .map_or(Transform::default(), |body2| bodies[body2].transform.clone());
+
+        let point1 = transform1.point_to_world_space(joint.anchor1);
+        let point2 = transform2.point_to_world_space(joint.anchor2);
+        JointFrame {
+            point1,
+            point2,
+            r1: point1 - transform1.position,
+            r2: point2 - transform2.position,
+            axis: transform1.rotation.vector_to_world_space(joint.axis1),
+            axis2: transform2.rotation.vector_to_world_space(joint.axis2),
+            rotation1: transform1.rotation,
+            rotation2: transform2.rotation,
+            reference_rotation: joint.reference_rotation,
+        }
+    }
+
+    // World space rotation taking the second body from where the reference rotation wants it to where it
+    // is, on the shorter arc
+    fn rotation_error(&self) -> Quaternion {
+        let error = self.rotation2 * (self.rotation1 * self.reference_rotation).inverse();
+        if error.w < 0.0 { error * -1.0 } else { error }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{body::Body, physics::PhysicsWorld, shape::Shape};
+
+    fn cube(position: Vector3, half_size: f64) -> Body {
+        Body::new(
+            Transform::new(position, Quaternion::default()),
+            Shape::Box {
+                half_size: Vector3::new(half_size, half_size, half_size),
+            },
+            1.0,
+        )
+    }
+
+    fn world() -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        world.sleep.enabled = false;
+        world
+    }
+
+    #[test]
+    fn hinges_keep_their_anchor_and_stop_at_their_limit() {
+        let mut world = world();
+        let door = world.add_body(Body {
+            angular_velocity: Vector3::Y_AXIS * 5.0,
+            ..Body::new(
+                Transform::new(Vector3::new(5.0, 0.0, 0.0), Quaternion::default()),
+                Shape::Box {
+                    half_size: Vector3::new(5.0, 3.0, 0.5),
+                },
+                1.0,
+            )
+        });
+        let joint = Joint::hinge(&world.bodies, door, None, Vector3::default(), Vector3::Y_AXIS)
+            .with_limit(-0.5, 0.5);
+        let joint = world.add_joint(joint);
+
+        let mut widest: f64 = 0.0;
+        for _ in 0..120 {
+            world.step();
+            let angle = world.joints[joint].angle(&world.bodies);
+            assert!(angle.abs() < 0.55, "hinge swung to {angle}");
+            widest = widest.max(angle.abs());
+
+            let hinge = world.bodies[door].transform.point_to_world_space(Vector3::new(-5.0, 0.0, 0.0));
+            assert!(hinge.magnitude() < 0.1, "hinge drifted to {hinge:?}");
+        }
+        assert!(widest > 0.45, "hinge only swung to {widest}");
+    }
+
+    #[test]
+    fn slider_motors_drive_the_body_along_the_axis_up_to_the_limit() {
+        let mut world = world();
+        let shuttle = world.add_body(cube(Vector3::default(), 1.0));
+        let joint = Joint::slider(&world.bodies, shuttle, None, Vector3::X_AXIS)
+            .with_limit(-3.0, 3.0)
+            .with_motor(2.0, 1_000.0);
+        let joint = world.add_joint(joint);
+
+        for _ in 0..60 {
+            world.step();
+        }
+        let translation = world.joints[joint].translation(&world.bodies);
+        assert!((translation - 2.0).abs() < 0.1, "slid {translation} in a second");
+
+        for _ in 0..180 {
+            world.step();
+        }
+        let translation = world.joints[joint].translation(&world.bodies);
+        assert!((translation - 3.0).abs() < 0.1, "slid {translation} against the limit");
+
+        // Held against gravity and kept from turning, give or take the sag that the position correction
+        // leaves, so the motor only moves it along the axis
+        let body = &world.bodies[shuttle];
+        assert!(body.transform.position.y.abs() < 0.2 && body.transform.position.z.abs() < 0.2);
+        assert!(body.transform.rotation.w.abs() > 1.0 - 1e-3);
+    }
+
+    #[test]
+    fn fixed_joints_move_welded_bodies_as_one() {
+        let mut world = world();
+        world.gravity = Vector3::default();
+        let body1 = world.add_body(Body {
+            linear_velocity: Vector3::new(1.0, 2.0, 0.0),
+            angular_velocity: Vector3::new(0.0, 1.0, 2.0),
+            ..cube(Vector3::default(), 1.0)
+        });
+        let body2 = world.add_body(cube(Vector3::new(2.0, 1.0, 0.0), 1.0));
+        world.add_joint(Joint::fixed(&world.bodies, body1, Some(body2)));
+
+        for _ in 0..120 {
+            world.step();
+        }
+
+        let (transform1, transform2) = (&world.bodies[body1].transform, &world.bodies[body2].transform);
+        let offset = transform1.point_to_local_space(transform2.position) - Vector3::new(2.0, 1.0, 0.0);
+        assert!(offset.magnitude() < 0.05, "welded bodies drifted apart by {offset:?}");
+        let rotation = transform1.rotation.inverse() * transform2.rotation;
+        assert!(rotation.w.abs() > 1.0 - 1e-3, "welded bodies turned apart by {rotation:?}");
+
+        // The spin of the first body is shared with the second
+        let (spin1, spin2) = (world.bodies[body1].angular_velocity, world.bodies[body2].angular_velocity);
+        assert!(spin2.magnitude() > 0.1 && (spin1 - spin2).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn distance_joints_hold_their_length_or_stretch_like_a_spring() {
+        let mut world = world();
+        let anchor = Vector3::new(0.0, 10.0, 0.0);
+        let rod = world.add_body(cube(Vector3::new(3.0, 6.0, 0.0), 0.5));
+        let rod_position = world.bodies[rod].transform.position;
+        world.add_joint(Joint::distance(&world.bodies, rod, None, rod_position, anchor));
+
+        let stiffness = 50.0;
+        let bob = world.add_body(cube(Vector3::new(10.0, 5.0, 0.0), 0.5));
+        let bob_position = world.bodies[bob].transform.position;
+        let bob_anchor = anchor + Vector3::X_AXIS * 10.0;
+        let spring = Joint::distance(&world.bodies, bob, None, bob_position, bob_anchor)
+            .with_spring(stiffness, 10.0);
+        world.add_joint(spring);
+
+        for _ in 0..600 {
+            world.step();
+            let length = (world.bodies[rod].transform.position - anchor).magnitude();
+            assert!((length - 5.0).abs() < 0.1, "rod stretched to {length}");
+        }
+
+        // The spring settles where it pulls up with the weight of the bob
+        let stretch = (world.bodies[bob].transform.position - bob_anchor).magnitude() - 5.0;
+        let expected = world.gravity.magnitude() / stiffness;
+        assert!((stretch - expected).abs() < 0.05 * expected, "spring stretched {stretch}, not {expected}");
+    }
+}
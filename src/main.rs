@@ -4,34 +4,109 @@ mod raster;
 mod transform;
 mod world;
 mod body;
+mod body_set;
+mod broadphase;
+mod ccd;
+mod collision;
+mod determinism;
+mod event;
+mod force;
+mod gjk;
+mod island;
+mod joint;
+mod mesh;
+mod model;
+mod particle;
+mod physics;
+mod raycast;
+mod shape;
+mod solver;
+mod trigger;
 
 use world::*;
 use raster::*;
 
 use tui_logger::{TuiLoggerWidget, TuiLoggerLevelOutput};
-use tui::{backend::CrosstermBackend, layout::{Layout, Direction, Constraint}, widgets::{Borders, Block}, style::{Style, Color}};
+use tui::{backend::CrosstermBackend, layout::{Layout, Direction, Constraint, Rect}, widgets::{Borders, Block}, style::{Style, Color}};
 use crossterm::{
-    event::{poll, read, Event, KeyCode, KeyModifiers},
+    event::{
+        poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
-use std::{io::stdout, time::{Duration, Instant}};
+use std::{io::stdout, path::Path, time::{Duration, Instant}};
+
+// Frames run by the determinism check when no count is given
+const DETERMINISM_CHECK_FRAMES: usize = 600;
+
+const USAGE: &str = "Usage: d6 [--model <path to .obj or .stl file>]\n       d6 --check-determinism [frames]";
 
 fn main() {
+    // `--model <path>` shows an OBJ or STL model in the test world and `--check-determinism [frames]` runs
+    // the test world twice without the interface and compares the runs
+    let mut args = std::env::args().skip(1).peekable();
+    let (mut model_path, mut determinism_frames) = (None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--model" => match args.next() {
+                Some(path) => model_path = Some(path),
+                None => exit_with_usage("Missing path after --model"),
+            },
+            "--check-determinism" => {
+                determinism_frames = Some(match args.next_if(|frames| !frames.starts_with("--")) {
+                    Some(frames) => frames.parse().unwrap_or_else(|_| {
+                        exit_with_usage(&format!("Invalid frame count {frames}, expected a whole number"))
+                    }),
+                    None => DETERMINISM_CHECK_FRAMES,
+                });
+            }
+            _ => exit_with_usage(&format!("Unknown argument {arg}")),
+        }
+    }
+
+    if let Some(frames) = determinism_frames {
+        if model_path.is_some() {
+            exit_with_usage("The determinism check only runs the test world and can't show a model");
+        }
+        match determinism::check_determinism(test_world::TestWorld::new, frames) {
+            Ok(()) => println!("Simulation is deterministic over {frames} frames"),
+            Err(divergence) => {
+                eprintln!("Simulation is not deterministic: {divergence}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut test_world = test_world::TestWorld::new();
+    if let Some(path) = model_path {
+        match model::load_model(Path::new(&path)) {
+            Ok(mesh) => test_world.add_model(mesh),
+            Err(error) => {
+                eprintln!("Failed to load model {path}: {error}");
+                std::process::exit(1);
+            }
+        }
+    }
+
     tui_logger::init_logger(log::LevelFilter::Trace).unwrap();
     tui_logger::set_default_level(log::LevelFilter::Trace);
 
     enable_raw_mode().expect("Failed to enable terminal raw mode");
 
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen).expect("Failed to enter alternate terminal screen");
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture).expect("Failed to enter alternate terminal screen");
 
     let mut terminal = tui::Terminal::new(CrosstermBackend::new(stdout))
         .expect("Failed to create interface to terminal backend");
 
-    let mut test_world = test_world::TestWorld::new();
     let mut raster = Raster::default();
+    let mut orthographic = false;
+    // where the raster was last drawn, to turn clicks into rays
+    let mut last_raster_area = Rect::default();
     let mut then = Instant::now();
 
     loop {
@@ -45,13 +120,35 @@ fn main() {
                                 || key_event.code == KeyCode::Char('C'))
                     {
                         disable_raw_mode().expect("Failed to disable terminal raw mode");
-                        execute!(terminal.backend_mut(), LeaveAlternateScreen)
+                        execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)
                             .expect("Failed to leave alternate terminal screen");
                         terminal
                             .show_cursor()
                             .expect("Failed to show terminal cursor");
                         return;
                     }
+
+                    match key_event.code {
+                        // O switches between the perspective and orthographic cameras
+                        KeyCode::Char('o') => orthographic = !orthographic,
+                        // X sets off an explosion among the boxes
+                        KeyCode::Char('x') => test_world.explode(),
+                        _ => (),
+                    }
+                }
+                // Left click pokes whatever is under the cursor and right click removes it
+                Event::Mouse(MouseEvent { kind: MouseEventKind::Down(button), column, row, .. }) => {
+                    let camera_transform = test_world.camera_transform();
+                    let ray = if orthographic {
+                        cell_ray::<OrthographicCamera>(last_raster_area, column, row, camera_transform)
+                    } else {
+                        cell_ray::<PerspectiveCamera>(last_raster_area, column, row, camera_transform)
+                    };
+                    match (button, ray) {
+                        (MouseButton::Left, Some(ray)) => test_world.poke(&ray),
+                        (MouseButton::Right, Some(ray)) => test_world.remove(&ray),
+                        _ => (),
+                    }
                 }
                 _ => (),
             }
@@ -70,17 +167,31 @@ fn main() {
                     ])
                     .direction(Direction::Horizontal)
                     .split(frame.size())[..] else { unreachable!() }; 
+                last_raster_area = raster_area;
 
-                frame.render_widget(
-                    RasterWidget::<'_, _, OrthographicCamera>::new(&mut raster, &test_world, 4),
-                    raster_area,
-                );
+                if orthographic {
+                    frame.render_widget(
+                        RasterWidget::<'_, _, OrthographicCamera>::new(&mut raster, &test_world, 4),
+                        raster_area,
+                    );
+                } else {
+                    frame.render_widget(
+                        RasterWidget::<'_, _, PerspectiveCamera>::new(&mut raster, &test_world, 4),
+                        raster_area,
+                    );
+                }
                 frame.render_widget(logger_widget(Borders::ALL), logger_area);
             })
             .expect("Failed to draw to terminal");
     }
 }
 
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("{USAGE}");
+    std::process::exit(1);
+}
+
 pub fn logger_widget(borders: Borders) -> TuiLoggerWidget<'static> {
     TuiLoggerWidget::default()
         .block(
@@ -0,0 +1,601 @@
+use crate::{broadphase::Aabb, raster::SceneTriangle, transform::*};
+
+use tui::style::Color;
+
+use std::{collections::HashMap, f64::consts::PI};
+
+const DEFAULT_MESH_COLOR: Color = Color::Rgb(255, 255, 255);
+
+// Point of the outline of a surface of revolution about the y axis, with the outward normal of the
+// surface there given by its radial and vertical parts
+#[derive(Clone, Copy)]
+struct ProfilePoint {
+    radius: f64,
+    height: f64,
+    normal: (f64, f64),
+}
+
+// Indexed triangle mesh. Triangles share vertices instead of repeating their points, and each vertex has
+// its own normal, so a vertex is only repeated where faces meet at a hard edge and need different
+// normals there.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vector3>,
+    // Unit normal of each vertex
+    pub normals: Vec<Vector3>,
+    // Color and texture coordinates of each vertex, or empty when the mesh has none
+    pub colors: Vec<Color>,
+    pub uvs: Vec<[f64; 2]>,
+    // Vertex indices of each triangle, wound counter-clockwise seen from the front
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    // Mesh with smooth normals averaged from the triangles around each vertex
+    pub fn new(positions: Vec<Vector3>, indices: Vec<[usize; 3]>) -> Mesh {
+        let mut mesh = Mesh {
+            positions,
+            indices,
+            ..Default::default()
+        };
+        mesh.compute_normals();
+        mesh
+    }
+
+    // Gives every vertex the same color
+    pub fn with_color(mut self, color: Color) -> Mesh {
+        self.colors = vec![color; self.positions.len()];
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<Color>) -> Mesh {
+        self.colors = colors;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<[f64; 2]>) -> Mesh {
+        self.uvs = uvs;
+        self
+    }
+
+    // Box around the origin with flat faces, so each corner is repeated once per face it belongs to
+    pub fn cuboid(half_size: Vector3) -> Mesh {
+        let mut mesh = Mesh::default();
+        for axis in [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS] {
+            for sign in [1.0, -1.0] {
+                let normal = axis * sign;
+                // tangent1 x tangent2 = axis, and negating tangent1 turns the face around
+                let tangent1 = Vector3::new(axis.z, axis.x, axis.y);
+                let tangent2 = Vector3::new(axis.y, axis.z, axis.x);
+                let (u, v) = (tangent1 * sign, tangent2);
+
+                let first = mesh.positions.len();
+                for (corner_u, corner_v) in [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)] {
+                    mesh.positions.push((normal + u * corner_u + v * corner_v) * half_size);
+                    mesh.normals.push(normal);
+                    mesh.uvs.push([0.5 * (1.0 + corner_u), 0.5 * (1.0 - corner_v)]);
+                }
+                mesh.indices.push([first, first + 1, first + 2]);
+                mesh.indices.push([first + 2, first + 3, first]);
+            }
+        }
+        mesh
+    }
+
+    // Sphere of rings of latitude from pole to pole, each cut into segments of longitude
+    pub fn uv_sphere(radius: f64, segments: usize, rings: usize) -> Mesh {
+        Mesh::capsule(radius, 0.0, segments, rings)
+    }
+
+    // Sphere from an icosahedron whose triangles are each split into four the given number of times, which
+    // spreads the vertices more evenly than a UV sphere
+    pub fn icosphere(radius: f64, subdivisions: usize) -> Mesh {
+        let t = 0.5 * (1.0 + 5.0_f64.sqrt());
+        let mut directions: Vec<Vector3> = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .into_iter()
+        .map(|(x, y, z)| Vector3::new(x, y, z).unit())
+        .collect();
+        let mut indices = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            // Each edge is split once, by whichever of its two triangles gets to it first
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut midpoint = |a: usize, b: usize| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    directions.push((directions[a] + directions[b]).unit());
+                    directions.len() - 1
+                })
+            };
+
+            indices = indices
+                .into_iter()
+                .flat_map(|[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        Mesh {
+            positions: directions.iter().map(|&direction| direction * radius).collect(),
+            normals: directions,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    // Cylinder around the y axis from -half_height to half_height, with flat caps at both ends
+    pub fn cylinder(radius: f64, half_height: f64, segments: usize) -> Mesh {
+        let mut mesh = Mesh::default();
+        for (start, end, normal) in [
+            ((0.0, -half_height), (radius, -half_height), (0.0, -1.0)),
+            ((radius, -half_height), (radius, half_height), (1.0, 0.0)),
+            ((radius, half_height), (0.0, half_height), (0.0, 1.0)),
+        ] {
+            mesh.add_revolved_line(start, end, normal, segments);
+        }
+        mesh
+    }
+
+    // Cone around the y axis with its base at -half_height and its tip at half_height
+    pub fn cone(radius: f64, half_height: f64, segments: usize) -> Mesh {
+        let mut mesh = Mesh::default();
+        mesh.add_revolved_line((0.0, -half_height), (radius, -half_height), (0.0, -1.0), segments);
+        mesh.add_cone(radius, -half_height, half_height, segments);
+        mesh
+    }
+
+    // Ring around the y axis, where the major radius reaches the middle of the tube and the minor radius
+    // is the radius of the tube
+    pub fn torus(major_radius: f64, minor_radius: f64, major_segments: usize, minor_segments: usize) -> Mesh {
+        let minor_segments = minor_segments.max(3);
+        let profile: Vec<ProfilePoint> = (0..=minor_segments)
+            .map(|i| {
+                // Around the tube from its bottom, up its outside and down its inside
+                let angle = 2.0 * PI * i as f64 / minor_segments as f64 - 0.5 * PI;
+                ProfilePoint {
+                    radius: major_radius + minor_radius * angle.cos(),
+                    height: minor_radius * angle.sin(),
+                    normal: (angle.cos(), angle.sin()),
+                }
+            })
+            .collect();
+
+        let mut mesh = Mesh::default();
+        mesh.add_revolved_strip(&profile, major_segments);
+        mesh
+    }
+
+    // Capsule around the y axis whose core segment runs from -half_height to half_height, with the given
+    // number of rings of latitude over both hemispheres. A capsule without height is a sphere.
+    pub fn capsule(radius: f64, half_height: f64, segments: usize, rings: usize) -> Mesh {
+        let rings = rings.max(2);
+        let (lower, upper) = (rings / 2, rings - rings / 2);
+        let point = |latitude: f64, offset: f64| ProfilePoint {
+            radius: radius * latitude.cos(),
+            height: radius * latitude.sin() + offset,
+            normal: (latitude.cos(), latitude.sin()),
+        };
+
+        let mut profile: Vec<ProfilePoint> = (0..=lower)
+            .map(|ring| point(0.5 * PI * (ring as f64 / lower as f64 - 1.0), -half_height))
+            .collect();
+        // Without a core segment the equators of the two hemispheres are the same ring
+        let first_upper = if half_height == 0.0 { 1 } else { 0 };
+        let upper_ring = |ring: usize| point(0.5 * PI * ring as f64 / upper as f64, half_height);
+        profile.extend((first_upper..=upper).map(upper_ring));
+
+        let mut mesh = Mesh::default();
+        mesh.add_revolved_strip(&profile, segments);
+        mesh
+    }
+
+    // Flat square grid in the xz plane facing up, split into the given number of columns along x and rows
+    // along z
+    pub fn plane(half_width: f64, half_depth: f64, columns: usize, rows: usize) -> Mesh {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let mut mesh = Mesh::default();
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (u, v) = (column as f64 / columns as f64, row as f64 / rows as f64);
+                let (x, z) = (half_width * (2.0 * u - 1.0), half_depth * (1.0 - 2.0 * v));
+                mesh.positions.push(Vector3::new(x, 0.0, z));
+                mesh.normals.push(Vector3::Y_AXIS);
+                mesh.uvs.push([u, v]);
+            }
+        }
+
+        let index = |column: usize, row: usize| row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let quad = [
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ];
+                mesh.indices.push([quad[0], quad[1], quad[2]]);
+                mesh.indices.push([quad[2], quad[3], quad[0]]);
+            }
+        }
+        mesh
+    }
+
+    // Arrow from the origin along the y axis for debug gizmos, a cylindrical shaft topped by a cone
+    pub fn arrow(
+        length: f64,
+        shaft_radius: f64,
+        head_radius: f64,
+        head_length: f64,
+        segments: usize,
+    ) -> Mesh {
+        let neck = length - head_length;
+        let mut mesh = Mesh::default();
+        for (start, end, normal) in [
+            ((0.0, 0.0), (shaft_radius, 0.0), (0.0, -1.0)),
+            ((shaft_radius, 0.0), (shaft_radius, neck), (1.0, 0.0)),
+            ((shaft_radius, neck), (head_radius, neck), (0.0, -1.0)),
+        ] {
+            mesh.add_revolved_line(start, end, normal, segments);
+        }
+        mesh.add_cone(head_radius, neck, length, segments);
+        mesh
+    }
+
+    // Sloped side of a cone from a circle at the base height up to a tip at the tip height, without a
+    // cap on its base
+    fn add_cone(&mut self, radius: f64, base: f64, tip: f64, segments: usize) {
+        let slope = (tip - base).hypot(radius);
+        let normal = ((tip - base) / slope, radius / slope);
+        self.add_revolved_line((radius, base), (0.0, tip), normal, segments);
+    }
+
+    // Sweeps a straight line from one (radius, height) point to another around the y axis, making a flat
+    // surface with the same normal all along it
+    fn add_revolved_line(&mut self, start: (f64, f64), end: (f64, f64), normal: (f64, f64), segments: usize) {
+        let point = |(radius, height)| ProfilePoint {
+            radius,
+            height,
+            normal,
+        };
+        self.add_revolved_strip(&[point(start), point(end)], segments);
+    }
+
+    // Sweeps an outline around the y axis, adding a band of quads between each pair of neighbouring points.
+    // The outline runs from the bottom of the surface to its top as seen from outside, so the triangles
+    // face outwards. Points on the axis make a pole, where the triangles that would collapse are left out.
+    fn add_revolved_strip(&mut self, profile: &[ProfilePoint], segments: usize) {
+        let segments = segments.max(3);
+        let first = self.positions.len();
+        for (i, point) in profile.iter().enumerate() {
+            for segment in 0..=segments {
+                let longitude = 2.0 * PI * segment as f64 / segments as f64;
+                let (sin, cos) = (-longitude.sin(), longitude.cos());
+                self.positions.push(Vector3::new(point.radius * cos, point.height, point.radius * sin));
+                let normal = Vector3::new(point.normal.0 * cos, point.normal.1, point.normal.0 * sin);
+                self.normals.push(normal.unit());
+                let (u, v) = (segment as f64 / segments as f64, i as f64 / (profile.len() - 1) as f64);
+                self.uvs.push([u, 1.0 - v]);
+            }
+        }
+
+        let largest_radius = profile.iter().fold(0.0, |largest: f64, point| largest.max(point.radius.abs()));
+        let is_pole = |point: &ProfilePoint| point.radius.abs() <= 1e-9 * largest_radius;
+
+        let index = |i: usize, segment: usize| first + i * (segments + 1) + segment;
+        for (i, (lower, upper)) in profile.iter().zip(profile.iter().skip(1)).enumerate() {
+            for segment in 0..segments {
+                let quad = [
+                    index(i, segment),
+                    index(i, segment + 1),
+                    index(i + 1, segment + 1),
+                    index(i + 1, segment),
+                ];
+                if !is_pole(lower) {
+                    self.indices.push([quad[0], quad[1], quad[2]]);
+                }
+                if !is_pole(upper) {
+                    self.indices.push([quad[2], quad[3], quad[0]]);
+                }
+            }
+        }
+    }
+
+    // Mesh of separate triangles, merging the points that share both their position and normal so
+    // neighbouring triangles of a flat or smooth surface share vertices. Vertices take their color from
+    // the first triangle that uses them.
+    pub fn from_triangles(triangles: &[SceneTriangle]) -> Mesh {
+        let mut mesh = Mesh::default();
+        let mut vertices: HashMap<[u64; 6], usize> = HashMap::new();
+
+        for triangle in triangles {
+            let indices = triangle.points.map(|point| {
+                // Adding zero turns negative zeros positive so they weld with positive ones
+                let key = [point.x, point.y, point.z, triangle.normal.x, triangle.normal.y, triangle.normal.z]
+                    .map(|value| (value + 0.0).to_bits());
+                *vertices.entry(key).or_insert_with(|| {
+                    mesh.positions.push(point);
+                    mesh.normals.push(triangle.normal);
+                    mesh.colors.push(triangle.color);
+                    mesh.positions.len() - 1
+                })
+            });
+            mesh.indices.push(indices);
+        }
+
+        mesh
+    }
+
+    // Replaces the normals with the average of the normals of the triangles around each vertex, weighted
+    // by their area so small slivers don't skew the result
+    pub fn compute_normals(&mut self) {
+        self.normals = vec![Vector3::default(); self.positions.len()];
+        for &[a, b, c] in self.indices.iter() {
+            let (pa, pb, pc) = (self.positions[a], self.positions[b], self.positions[c]);
+            let area_normal = (pb - pa).cross(pc - pa);
+            for index in [a, b, c] {
+                self.normals[index] += area_normal;
+            }
+        }
+        for normal in self.normals.iter_mut() {
+            if normal.magnitude() > 0.0 {
+                *normal = normal.unit();
+            }
+        }
+    }
+
+    // Moves and rotates the mesh in place
+    pub fn transform(&mut self, transform: &Transform) {
+        for position in self.positions.iter_mut() {
+            *position = transform.point_to_world_space(*position);
+        }
+        for normal in self.normals.iter_mut() {
+            *normal = transform.rotation.vector_to_world_space(*normal);
+        }
+    }
+
+    pub fn transformed(&self, transform: &Transform) -> Mesh {
+        let mut mesh = self.clone();
+        mesh.transform(transform);
+        mesh
+    }
+
+    // Stretches the mesh along each axis. Normals are scaled by the inverse so they stay perpendicular
+    // to the stretched surface, through the cofactors of the scale so that flattening the mesh to zero
+    // along an axis doesn't divide by zero. Mirroring the mesh an odd number of times turns its
+    // triangles around so they keep facing out.
+    pub fn scale(&mut self, scale: Vector3) {
+        for position in self.positions.iter_mut() {
+            *position = *position * scale;
+        }
+
+        let determinant = scale.x * scale.y * scale.z;
+        let cofactors = Vector3::new(scale.y * scale.z, scale.x * scale.z, scale.x * scale.y);
+        let cofactors = if determinant < 0.0 { cofactors.inverse() } else { cofactors };
+        for normal in self.normals.iter_mut() {
+            *normal = *normal * cofactors;
+            if normal.magnitude() > 0.0 {
+                *normal = normal.unit();
+            }
+        }
+        if determinant < 0.0 {
+            for indices in self.indices.iter_mut() {
+                indices.swap(1, 2);
+            }
+        }
+    }
+
+    // Bounds of the vertex positions, empty at the origin for a mesh without vertices
+    pub fn aabb(&self) -> Aabb {
+        let mut positions = self.positions.iter();
+        let Some(&first) = positions.next() else {
+            return Aabb::default();
+        };
+        positions.fold(Aabb::new(first, first), |aabb, &position| aabb.union(&Aabb::new(position, position)))
+    }
+
+    // Appends the triangles of another mesh, keeping the vertex attributes that both meshes have
+    pub fn merge(&mut self, other: &Mesh) {
+        let offset = self.positions.len();
+        let keep_colors = self.colors.len() == offset && other.colors.len() == other.positions.len();
+        let keep_uvs = self.uvs.len() == offset && other.uvs.len() == other.positions.len();
+
+        self.positions.extend_from_slice(&other.positions);
+        self.normals.extend_from_slice(&other.normals);
+        if keep_colors {
+            self.colors.extend_from_slice(&other.colors);
+        } else {
+            self.colors.clear();
+        }
+        if keep_uvs {
+            self.uvs.extend_from_slice(&other.uvs);
+        } else {
+            self.uvs.clear();
+        }
+        self.indices.extend(other.indices.iter().map(|indices| indices.map(|index| index + offset)));
+    }
+
+    // Triangles of the mesh placed at a transform, ready to be added to a scene. The renderer draws each
+    // triangle in one color, so a triangle gets the average color of its vertices and the normal of its
+    // plane, which is what culling and depth testing need.
+    pub fn geometry<'a>(&'a self, transform: &'a Transform) -> impl Iterator<Item = SceneTriangle> + 'a {
+        self.indices.iter().filter_map(move |&indices| {
+            let points = indices.map(|index| transform.point_to_world_space(self.positions[index]));
+            let normal = (points[1] - points[0]).cross(points[2] - points[0]);
+            if normal.magnitude() <= f64::EPSILON {
+                return None;
+            }
+
+            Some(SceneTriangle {
+                points,
+                normal: normal.unit(),
+                color: self.triangle_color(indices),
+            })
+        })
+    }
+
+    fn triangle_color(&self, indices: [usize; 3]) -> Color {
+        if self.colors.len() != self.positions.len() {
+            return DEFAULT_MESH_COLOR;
+        }
+
+        let channels = indices.map(|index| match self.colors[index] {
+            Color::Rgb(r, g, b) => [r, g, b].map(f64::from),
+            _ => [255.0; 3],
+        });
+        let average = |channel: usize| {
+            let sum: f64 = channels.iter().map(|color| color[channel]).sum();
+            (sum / 3.0).round() as u8
+        };
+        Color::Rgb(average(0), average(1), average(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Normal of a triangle from its winding
+    fn winding_normal(mesh: &Mesh, [a, b, c]: [usize; 3]) -> Vector3 {
+        let (a, b, c) = (mesh.positions[a], mesh.positions[b], mesh.positions[c]);
+        (b - a).cross(c - a)
+    }
+
+    // Checks that every triangle faces away from the point inside the mesh that the reference function picks
+    // for its center, and that the vertex normals of each triangle lean the same way as its winding
+    fn assert_faces_out(name: &str, mesh: &Mesh, reference: impl Fn(Vector3) -> Vector3) {
+        assert!(!mesh.indices.is_empty(), "{name} has no triangles");
+        for &indices in mesh.indices.iter() {
+            let normal = winding_normal(mesh, indices);
+            assert!(normal.magnitude() > 1e-12, "{name} has a degenerate triangle {indices:?}");
+            let center = indices.iter().fold(Vector3::default(), |sum, &index| sum + mesh.positions[index]);
+            let center = center * (1.0 / 3.0);
+            assert!(normal.dot(center - reference(center)) > 0.0, "{name} triangle {indices:?} faces in");
+            for index in indices {
+                let agrees = mesh.normals[index].dot(normal) > 0.0;
+                assert!(agrees, "{name} vertex {index} disagrees with {indices:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn generated_meshes_face_out() {
+        let origin = |_| Vector3::default();
+        assert_faces_out("uv sphere", &Mesh::uv_sphere(2.0, 12, 8), origin);
+        assert_faces_out("icosphere", &Mesh::icosphere(2.0, 2), origin);
+        assert_faces_out("cylinder", &Mesh::cylinder(2.0, 3.0, 12), origin);
+        assert_faces_out("cone", &Mesh::cone(2.0, 3.0, 12), origin);
+        assert_faces_out("capsule", &Mesh::capsule(2.0, 3.0, 12, 8), origin);
+        assert_faces_out("cuboid", &Mesh::cuboid(Vector3::new(1.0, 2.0, 3.0)), origin);
+
+        // The torus is checked against the middle of its tube and the plane against the ground below it
+        assert_faces_out("torus", &Mesh::torus(4.0, 1.0, 16, 8), |center| {
+            Vector3::new(center.x, 0.0, center.z).unit() * 4.0
+        });
+        assert_faces_out("plane", &Mesh::plane(3.0, 2.0, 3, 2), |center| center - Vector3::Y_AXIS);
+
+        // The shaft and the head of the arrow are each convex, but not the arrow as a whole
+        let (length, head_length) = (10.0, 3.0);
+        let neck = length - head_length;
+        assert_faces_out("arrow", &Mesh::arrow(length, 0.5, 1.0, head_length, 12), |center| {
+            let height = if center.y < neck { 0.5 * neck } else { neck + 0.25 * head_length };
+            Vector3::Y_AXIS * height
+        });
+    }
+
+    #[test]
+    fn new_meshes_get_smooth_normals() {
+        let positions = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, -1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        ];
+        let mesh = Mesh::new(positions, vec![[0, 1, 2], [2, 3, 0]]);
+        for normal in mesh.normals {
+            assert!((normal - Vector3::Y_AXIS).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn triangles_weld_where_they_share_a_point_and_normal() {
+        let triangle = |points: [Vector3; 3]| SceneTriangle {
+            points,
+            normal: Vector3::Y_AXIS,
+            color: DEFAULT_MESH_COLOR,
+        };
+        let (a, b) = (Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let c = Vector3::new(1.0, 0.0, -1.0);
+        // The second triangle has negative zeros where the first one has positive ones
+        let (d, negative_a) = (Vector3::new(0.0, -0.0, -1.0), Vector3::new(-0.0, 0.0, -0.0));
+        let mesh = Mesh::from_triangles(&[triangle([a, b, c]), triangle([c, d, negative_a])]);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [2, 3, 0]]);
+    }
+
+    #[test]
+    fn scaled_normals_stay_perpendicular_to_the_surface() {
+        let scale = Vector3::new(2.0, 1.0, 0.5);
+        let mut ellipsoid = Mesh::uv_sphere(1.0, 8, 6);
+        ellipsoid.scale(scale);
+
+        // The normal of an ellipsoid at a point is the point divided by the square of the scale
+        for (&position, &normal) in ellipsoid.positions.iter().zip(ellipsoid.normals.iter()) {
+            let expected = (position * Vector3::new(0.25, 1.0, 4.0)).unit();
+            assert!((normal - expected).magnitude() < 1e-9);
+        }
+
+        let mut flat = Mesh::uv_sphere(1.0, 8, 6);
+        flat.scale(Vector3::new(1.0, 0.0, 1.0));
+        for (position, normal) in flat.positions.iter().zip(flat.normals.iter()) {
+            assert!(position.y == 0.0);
+            assert!(normal.x == 0.0 && normal.z == 0.0 && normal.y.is_finite());
+        }
+    }
+
+    #[test]
+    fn mirrored_meshes_keep_facing_out() {
+        let mut mesh = Mesh::cuboid(Vector3::new(1.0, 2.0, 3.0));
+        mesh.scale(Vector3::new(-1.0, 1.0, 1.0));
+        for &indices in mesh.indices.iter() {
+            let normal = winding_normal(&mesh, indices);
+            let center = indices.iter().fold(Vector3::default(), |sum, &index| sum + mesh.positions[index]);
+            assert!(normal.dot(center) > 0.0);
+            assert!(indices.iter().all(|&index| mesh.normals[index].dot(normal) > 0.0));
+        }
+    }
+}
@@ -0,0 +1,546 @@
+use crate::{mesh::Mesh, raster::SceneTriangle, transform::*};
+
+use tui::style::Color;
+
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+// Size of the header and of each triangle record of a binary STL file
+const STL_HEADER_SIZE: usize = 80;
+const STL_TRIANGLE_SIZE: usize = 50;
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(io::Error),
+    // The file extension isn't one of the formats that can be loaded
+    UnsupportedFormat(String),
+    // A line of a text format that couldn't be understood, numbered from one
+    Parse { line: usize, message: String },
+    // A face refers to a vertex, texture coordinate or normal that doesn't exist
+    InvalidIndex { line: usize, index: i64 },
+    // A binary STL file is shorter or longer than its triangle count says
+    Truncated { expected: usize, found: usize },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Io(error) => write!(f, "{error}"),
+            ModelError::UnsupportedFormat(extension) => write!(f, "unsupported model format \"{extension}\""),
+            ModelError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            ModelError::InvalidIndex { line, index } => {
+                write!(f, "line {line}: index {index} is out of range")
+            }
+            ModelError::Truncated { expected, found } => {
+                write!(f, "binary STL should be {expected} bytes long but is {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModelError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModelError {
+    fn from(error: io::Error) -> Self {
+        ModelError::Io(error)
+    }
+}
+
+// Distinct combination of position, texture coordinate, normal and color used by the faces of an OBJ
+// model, each of which becomes one vertex of the mesh
+type ObjVertex = (usize, Option<usize>, Option<usize>, Option<(u8, u8, u8)>);
+
+// Loads an OBJ or STL file into a mesh, picking the format by the file extension. Material libraries of
+// OBJ files are looked up next to the file.
+pub fn load_model(path: &Path) -> Result<Mesh, ModelError> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension.to_ascii_lowercase().as_str() {
+        "obj" => {
+            let directory = path.parent().unwrap_or(Path::new(""));
+            parse_obj(&fs::read_to_string(path)?, |library| {
+                Ok(fs::read_to_string(directory.join(library))?)
+            })
+        }
+        "stl" => parse_stl(&fs::read(path)?),
+        _ => Err(ModelError::UnsupportedFormat(extension.to_string())),
+    }
+}
+
+// Parses a Wavefront OBJ model. Polygons are split into fans of triangles, and faces take the diffuse
+// color of their material, read from the material libraries that read_library returns the contents of.
+// Without materials, the colors some exporters append to vertex positions are used instead. Normals are
+// computed from the faces unless every vertex has one.
+pub fn parse_obj(
+    source: &str,
+    mut read_library: impl FnMut(&str) -> Result<String, ModelError>,
+) -> Result<Mesh, ModelError> {
+    let mut positions = Vec::new();
+    let mut position_colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut materials: HashMap<String, Color> = HashMap::new();
+    let mut material = None;
+
+    let mut vertices: HashMap<ObjVertex, usize> = HashMap::new();
+    let mut vertex_positions: Vec<Vector3> = Vec::new();
+    let mut indices: Vec<[usize; 3]> = Vec::new();
+    let mut vertex_uvs: Vec<Option<[f64; 2]>> = Vec::new();
+    let mut vertex_normals: Vec<Option<Vector3>> = Vec::new();
+    let mut vertex_colors: Vec<Option<Color>> = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line_number = number + 1;
+        let mut words = line.split('#').next().unwrap_or_default().split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => {
+                let values = parse_numbers(&arguments, line_number)?;
+                match values.len() {
+                    3 | 4 => position_colors.push(None),
+                    6 => position_colors.push(Some(color_from_floats(&values[3..6]))),
+                    _ => return Err(parse_error(line_number, "vertex position needs 3 coordinates")),
+                }
+                positions.push(Vector3::new(values[0], values[1], values[2]));
+            }
+            "vt" => {
+                let values = parse_numbers(&arguments, line_number)?;
+                if values.is_empty() || values.len() > 3 {
+                    return Err(parse_error(line_number, "texture coordinate needs 1 to 3 values"));
+                }
+                uvs.push([values[0], values.get(1).copied().unwrap_or_default()]);
+            }
+            "vn" => {
+                let values = parse_numbers(&arguments, line_number)?;
+                if values.len() != 3 {
+                    return Err(parse_error(line_number, "normal needs 3 coordinates"));
+                }
+                // Some exporters write zero normals, which the vertices using them get from their faces
+                // instead
+                let normal = Vector3::new(values[0], values[1], values[2]);
+                normals.push((normal.magnitude() > f64::EPSILON).then(|| normal.unit()));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(parse_error(line_number, "face needs at least 3 vertices"));
+                }
+
+                let material_color = material.and_then(|material| materials.get(material).copied());
+                let mut face = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    let mut parts = argument.split('/');
+                    let position = parse_index(parts.next(), positions.len(), line_number)?
+                        .ok_or_else(|| parse_error(line_number, "face vertex needs a position"))?;
+                    let uv = parse_index(parts.next(), uvs.len(), line_number)?;
+                    let normal = parse_index(parts.next(), normals.len(), line_number)?;
+                    let color = material_color.or(position_colors[position]);
+
+                    let key = (position, uv, normal, color.map(rgb));
+                    let vertex = *vertices.entry(key).or_insert_with(|| {
+                        vertex_positions.push(positions[position]);
+                        vertex_uvs.push(uv.map(|uv| uvs[uv]));
+                        vertex_normals.push(normal.and_then(|normal| normals[normal]));
+                        vertex_colors.push(color);
+                        vertex_positions.len() - 1
+                    });
+                    face.push(vertex);
+                }
+
+                for i in 1..face.len() - 1 {
+                    indices.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            "mtllib" => {
+                for library in arguments {
+                    materials.extend(parse_mtl(&read_library(library)?)?);
+                }
+            }
+            "usemtl" => material = arguments.first().copied(),
+            // Objects, groups, smoothing groups and anything else don't change the geometry
+            _ => {}
+        }
+    }
+
+    let mut mesh = Mesh::new(vertex_positions, indices)
+        .with_uvs(vertex_uvs.into_iter().collect::<Option<Vec<[f64; 2]>>>().unwrap_or_default());
+    // Vertices without a usable normal of their own keep the one computed from the faces around them
+    for (normal, given) in mesh.normals.iter_mut().zip(vertex_normals) {
+        if let Some(given) = given {
+            *normal = given;
+        }
+    }
+    if vertex_colors.iter().any(Option::is_some) {
+        mesh = mesh.with_colors(
+            vertex_colors
+                .into_iter()
+                .map(|color| color.unwrap_or(Color::Rgb(255, 255, 255)))
+                .collect(),
+        );
+    }
+
+    Ok(mesh)
+}
+
+// Diffuse colors of the materials in an MTL material library, by name
+pub fn parse_mtl(source: &str) -> Result<HashMap<String, Color>, ModelError> {
+    let mut materials = HashMap::new();
+    let mut material = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let line_number = number + 1;
+        let mut words = line.split('#').next().unwrap_or_default().split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        match keyword {
+            "newmtl" => {
+                let name = arguments.join(" ");
+                if name.is_empty() {
+                    return Err(parse_error(line_number, "material needs a name"));
+                }
+                materials.insert(name.clone(), Color::Rgb(255, 255, 255));
+                material = Some(name);
+            }
+            "Kd" => {
+                let values = parse_numbers(&arguments, line_number)?;
+                if values.len() != 3 {
+                    return Err(parse_error(line_number, "diffuse color needs 3 values"));
+                }
+                let material = material
+                    .as_ref()
+                    .ok_or_else(|| parse_error(line_number, "diffuse color outside of a material"))?;
+                materials.insert(material.clone(), color_from_floats(&values));
+            }
+            // Every other property only matters to a renderer with lighting models and textures
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+// Parses an STL model in either the binary or the ASCII format. Binary files are told apart by their
+// length matching the triangle count in their header, since some of them start with "solid" too.
+pub fn parse_stl(bytes: &[u8]) -> Result<Mesh, ModelError> {
+    let triangles = match binary_stl_triangle_count(bytes) {
+        Some(count) if bytes.len() == STL_HEADER_SIZE + 4 + count * STL_TRIANGLE_SIZE => {
+            parse_binary_stl(bytes, count)
+        }
+        _ if bytes.trim_ascii_start().starts_with(b"solid") => {
+            let source =
+                std::str::from_utf8(bytes).map_err(|_| parse_error(1, "ASCII STL is not valid text"))?;
+            parse_ascii_stl(source)?
+        }
+        Some(count) => {
+            return Err(ModelError::Truncated {
+                expected: STL_HEADER_SIZE + 4 + count * STL_TRIANGLE_SIZE,
+                found: bytes.len(),
+            })
+        }
+        None => {
+            return Err(ModelError::Truncated {
+                expected: STL_HEADER_SIZE + 4,
+                found: bytes.len(),
+            })
+        }
+    };
+
+    Ok(Mesh::from_triangles(&triangles))
+}
+
+fn binary_stl_triangle_count(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(STL_HEADER_SIZE..STL_HEADER_SIZE + 4)?;
+    Some(u32::from_le_bytes(count.try_into().ok()?) as usize)
+}
+
+fn parse_binary_stl(bytes: &[u8], count: usize) -> Vec<SceneTriangle> {
+    let float = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as f64;
+    let vector = |offset: usize| Vector3::new(float(offset), float(offset + 4), float(offset + 8));
+
+    (0..count)
+        .map(|i| {
+            let offset = STL_HEADER_SIZE + 4 + i * STL_TRIANGLE_SIZE;
+            stl_triangle(vector(offset), [12, 24, 36].map(|corner| vector(offset + corner)))
+        })
+        .collect()
+}
+
+fn parse_ascii_stl(source: &str) -> Result<Vec<SceneTriangle>, ModelError> {
+    let mut triangles = Vec::new();
+    let mut normal = None;
+    let mut points = Vec::with_capacity(3);
+    let mut line_number = 0;
+
+    for (number, line) in source.lines().enumerate() {
+        line_number = number + 1;
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        match keyword {
+            "facet" => {
+                if normal.is_some() {
+                    return Err(parse_error(line_number, "facet starts before the last one ended"));
+                }
+                let values = match arguments.split_first() {
+                    Some((&"normal", values)) => parse_numbers(values, line_number)?,
+                    _ => return Err(parse_error(line_number, "facet needs a normal")),
+                };
+                if values.len() != 3 {
+                    return Err(parse_error(line_number, "facet normal needs 3 coordinates"));
+                }
+                normal = Some(Vector3::new(values[0], values[1], values[2]));
+            }
+            "vertex" => {
+                let values = parse_numbers(&arguments, line_number)?;
+                if values.len() != 3 || normal.is_none() || points.len() == 3 {
+                    return Err(parse_error(line_number, "facets need exactly 3 vertices of 3 coordinates"));
+                }
+                points.push(Vector3::new(values[0], values[1], values[2]));
+            }
+            "endfacet" => {
+                let Some(facet_normal) = normal.take() else {
+                    return Err(parse_error(line_number, "facet ends before it started"));
+                };
+                let Ok(facet_points) = <[Vector3; 3]>::try_from(std::mem::take(&mut points)) else {
+                    return Err(parse_error(line_number, "facets need exactly 3 vertices of 3 coordinates"));
+                };
+                triangles.push(stl_triangle(facet_normal, facet_points));
+            }
+            "solid" | "outer" | "endloop" | "endsolid" => {}
+            _ => return Err(parse_error(line_number, &format!("unexpected \"{keyword}\""))),
+        }
+    }
+
+    if normal.is_some() {
+        return Err(parse_error(line_number, "file ends inside a facet"));
+    }
+    Ok(triangles)
+}
+
+// Many exporters leave the facet normal zero, so it's worked out from the corners when it isn't usable
+fn stl_triangle(normal: Vector3, points: [Vector3; 3]) -> SceneTriangle {
+    let normal = if normal.magnitude() > f64::EPSILON {
+        normal.unit()
+    } else {
+        let normal = (points[1] - points[0]).cross(points[2] - points[0]);
+        if normal.magnitude() > 0.0 { normal.unit() } else { normal }
+    };
+
+    SceneTriangle {
+        points,
+        normal,
+        color: Color::Rgb(255, 255, 255),
+    }
+}
+
+fn parse_numbers(arguments: &[&str], line: usize) -> Result<Vec<f64>, ModelError> {
+    arguments
+        .iter()
+        .map(|argument| {
+            argument
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| parse_error(line, &format!("\"{argument}\" is not a number")))
+        })
+        .collect()
+}
+
+// Zero based index from a one based OBJ index, where negative indices count back from the last element
+// so far. Returns None for an empty or missing index, such as the texture coordinate in "1//1".
+fn parse_index(argument: Option<&str>, count: usize, line: usize) -> Result<Option<usize>, ModelError> {
+    let Some(argument) = argument.filter(|argument| !argument.is_empty()) else {
+        return Ok(None);
+    };
+    let index: i64 = argument
+        .parse()
+        .map_err(|_| parse_error(line, &format!("\"{argument}\" is not an index")))?;
+
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(ModelError::InvalidIndex { line, index });
+    }
+    Ok(Some(resolved as usize))
+}
+
+fn parse_error(line: usize, message: &str) -> ModelError {
+    ModelError::Parse {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn color_from_floats(values: &[f64]) -> Color {
+    let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Color::Rgb(channel(values[0]), channel(values[1]), channel(values[2]))
+}
+
+fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => (255, 255, 255),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_libraries(library: &str) -> Result<String, ModelError> {
+        panic!("unexpected material library {library}")
+    }
+
+    fn binary_stl(count: u32, triangles: usize) -> Vec<u8> {
+        let mut bytes = vec![0; STL_HEADER_SIZE];
+        bytes.extend(count.to_le_bytes());
+        for _ in 0..triangles {
+            let mut triangle = vec![0; STL_TRIANGLE_SIZE];
+            // Corners at (0, 0, 0), (1, 0, 0) and (0, 1, 0), leaving the normal zero
+            triangle[24..28].copy_from_slice(&1.0f32.to_le_bytes());
+            triangle[40..44].copy_from_slice(&1.0f32.to_le_bytes());
+            bytes.extend(triangle);
+        }
+        bytes
+    }
+
+    #[test]
+    fn obj_faces_take_the_colors_of_their_materials() {
+        let source = "mtllib colors.mtl\n\
+                      v 0 0 0\nv 1 0 0\nv 1 0 -1\nv 0 0 -1\n\
+                      vt 0 0\nvt 1 1\n\
+                      usemtl red\nf 1/1 2/2 3/1\n\
+                      usemtl blue\nf -4/1 -2/2 -1/1\n";
+        let mesh = parse_obj(source, |library| {
+            assert_eq!(library, "colors.mtl");
+            Ok("newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n".to_string())
+        })
+        .unwrap();
+
+        // The first and third corners are shared by position but not by color
+        assert_eq!(mesh.positions.len(), 6);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [3, 4, 5]]);
+        assert_eq!(mesh.colors[0], Color::Rgb(255, 0, 0));
+        assert_eq!(mesh.colors[5], Color::Rgb(0, 0, 255));
+        assert_eq!(mesh.uvs[1], [1.0, 1.0]);
+        assert!(mesh.normals.iter().all(|normal| (*normal - Vector3::Y_AXIS).magnitude() < 1e-12));
+    }
+
+    #[test]
+    fn obj_vertices_with_zero_normals_use_the_normals_of_their_faces() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 0 -1\n\
+                      vn 0 0 0\nvn 0 0 2\n\
+                      f 1//1 2//2 3//2\n";
+        let mesh = parse_obj(source, no_libraries).unwrap();
+
+        assert!((mesh.normals[0] - Vector3::Y_AXIS).magnitude() < 1e-12);
+        for normal in &mesh.normals[1..] {
+            assert!((*normal - Vector3::Z_AXIS).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn stl_normals_are_worked_out_when_missing() {
+        let binary = parse_stl(&binary_stl(1, 1)).unwrap();
+        let ascii = parse_stl(
+            b"solid triangle\n\
+              facet normal 0 0 0\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\n\
+              endsolid triangle\n",
+        )
+        .unwrap();
+
+        for mesh in [binary, ascii] {
+            assert_eq!(mesh.positions.len(), 3);
+            assert!((mesh.normals[0] - Vector3::Z_AXIS).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn unreadable_files_are_io_errors() {
+        let missing = std::env::temp_dir().join("d6-missing-model.obj");
+        assert!(matches!(load_model(&missing), Err(ModelError::Io(_))));
+
+        let library = parse_obj("mtllib missing.mtl", |_| {
+            Err(io::Error::new(io::ErrorKind::NotFound, "missing.mtl").into())
+        });
+        assert!(matches!(library, Err(ModelError::Io(_))));
+    }
+
+    #[test]
+    fn unknown_extensions_are_unsupported() {
+        let error = load_model(Path::new("model.ply")).unwrap_err();
+        assert!(matches!(error, ModelError::UnsupportedFormat(extension) if extension == "ply"));
+    }
+
+    #[test]
+    fn malformed_lines_are_parse_errors_on_their_line() {
+        let cases: [(&str, usize); 5] = [
+            ("v 0 0 0\nv 1 2\n", 2),
+            ("v 0 0 zero\n", 1),
+            ("v 0 0 0\nv 1 0 0\nf 1 2\n", 3),
+            ("vn 0 1\n", 1),
+            ("v 0 0 0\nf 1/one 1 1\n", 2),
+        ];
+        for (source, expected) in cases {
+            let error = parse_obj(source, no_libraries).unwrap_err();
+            let line = match error {
+                ModelError::Parse { line, .. } => line,
+                error => panic!("{source:?}: {error}"),
+            };
+            assert_eq!(line, expected, "{source:?}");
+        }
+
+        // Lines of a material library are numbered within the library
+        for library in ["\nKd 1 0 0\n", "# no name\nnewmtl\n", "newmtl red\nKd 1 0\n"] {
+            let error = parse_obj("mtllib colors.mtl", |_| Ok(library.to_string())).unwrap_err();
+            assert!(matches!(error, ModelError::Parse { line: 2, .. }), "{library:?}: {error}");
+        }
+
+        for source in ["solid\nfacet normal 0 0 1\nendsolid\n", "solid\nfacet 0 0 1\n", "solid\nsurface\n"] {
+            let error = parse_stl(source.as_bytes()).unwrap_err();
+            assert!(matches!(error, ModelError::Parse { .. }), "{source:?}: {error}");
+        }
+    }
+
+    #[test]
+    fn faces_referring_to_missing_vertices_are_invalid() {
+        let cases = [("v 0 0 0\nf 1 2 1\n", 2), ("v 0 0 0\nf 1 0 1\n", 0), ("v 0 0 0\nf 1 -2 1\n", -2)];
+        for (source, expected) in cases {
+            let error = parse_obj(source, no_libraries).unwrap_err();
+            assert!(
+                matches!(error, ModelError::InvalidIndex { line: 2, index } if index == expected),
+                "{source:?}: {error}"
+            );
+        }
+
+        let error = parse_obj("v 0 0 0\nf 1/1 1/1 1/1\n", no_libraries).unwrap_err();
+        assert!(matches!(error, ModelError::InvalidIndex { line: 2, index: 1 }));
+    }
+
+    #[test]
+    fn binary_stl_files_must_hold_every_triangle() {
+        let truncated = |bytes: &[u8]| match parse_stl(bytes) {
+            Err(ModelError::Truncated { expected, found }) => (expected, found),
+            result => panic!("expected a truncated file, got {result:?}"),
+        };
+
+        let full_length = STL_HEADER_SIZE + 4 + 2 * STL_TRIANGLE_SIZE;
+        assert_eq!(truncated(&binary_stl(2, 1)), (full_length, full_length - STL_TRIANGLE_SIZE));
+        assert_eq!(truncated(&binary_stl(2, 3)), (full_length, full_length + STL_TRIANGLE_SIZE));
+        assert_eq!(truncated(&[0; STL_HEADER_SIZE]), (STL_HEADER_SIZE + 4, STL_HEADER_SIZE));
+    }
+}
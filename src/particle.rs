@@ -0,0 +1,453 @@
+use crate::{
+    body::Body,
+    broadphase::Aabb,
+    collision::Plane,
+    gjk::{self, GjkResult},
+    raster::SceneTriangle,
+    shape::Shape,
+    transform::*,
+};
+
+use tui::style::Color;
+
+pub const DEFAULT_PARTICLE_RADIUS: f64 = 0.5;
+
+// Point mass moved by Verlet integration, where the velocity is implied by how far the particle moved
+// in the last step. A particle with zero inverse mass is pinned in place.
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub position: Vector3,
+    pub previous_position: Vector3,
+    // Position before the last step, for blending between steps when rendering. Unlike the previous
+    // position it isn't nudged by friction.
+    pub start_position: Vector3,
+    pub inverse_mass: f64,
+}
+
+impl Particle {
+    pub fn new(position: Vector3, mass: f64) -> Particle {
+        Particle {
+            position,
+            previous_position: position,
+            start_position: position,
+            inverse_mass: if mass > 0.0 && mass.is_finite() { 1.0 / mass } else { 0.0 },
+        }
+    }
+
+    // Moves the particle out along a normal and takes away part of the motion along the surface
+    fn push_out(&mut self, normal: Vector3, depth: f64, friction: f64) {
+        self.position += normal * depth;
+        let velocity = self.position - self.previous_position;
+        let sliding = velocity - normal * velocity.dot(normal);
+        self.previous_position += sliding * friction;
+    }
+}
+
+// Keeps two particles at their rest length. A stiffness below one only corrects part of the error each
+// iteration, which makes the link stretchy.
+#[derive(Clone, Copy, Debug)]
+pub struct DistanceConstraint {
+    pub particle1: usize,
+    pub particle2: usize,
+    pub rest_length: f64,
+    pub stiffness: f64,
+}
+
+// Particles held together by distance constraints, such as a rope or a sheet of cloth. The particles
+// collide with bodies and planes but don't push back on them, so they suit effects that are light
+// compared to what they touch.
+#[derive(Clone, Debug)]
+pub struct ParticleSystem {
+    pub particles: Vec<Particle>,
+    pub constraints: Vec<DistanceConstraint>,
+    // Triangles between particles that make up the surface of cloth, wound counter-clockwise. Systems
+    // without triangles render each particle on its own.
+    pub triangles: Vec<[usize; 3]>,
+    // Collision radius of every particle
+    pub radius: f64,
+    // Fraction of the velocity lost per second, like the damping of bodies
+    pub damping: f64,
+    // Fraction of the sliding motion removed when a particle touches something
+    pub friction: f64,
+    // Times the constraints and collisions are relaxed each step, more makes the system stiffer
+    pub iterations: usize,
+    pub color: Color,
+}
+
+impl Default for ParticleSystem {
+    fn default() -> Self {
+        ParticleSystem {
+            particles: Vec::new(),
+            constraints: Vec::new(),
+            triangles: Vec::new(),
+            radius: DEFAULT_PARTICLE_RADIUS,
+            damping: 0.6,
+            friction: 0.5,
+            iterations: 8,
+            color: Color::Rgb(255, 255, 255),
+        }
+    }
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Rope of segments between two points, with the start pinned in place
+    pub fn rope(start: Vector3, end: Vector3, segments: usize, mass: f64) -> Self {
+        let segments = segments.max(1);
+        let mut rope = ParticleSystem::new();
+        let particle_mass = mass / (segments + 1) as f64;
+        for i in 0..=segments {
+            let position = start.lerp(end, i as f64 / segments as f64);
+            rope.add_particle(position, particle_mass);
+        }
+        rope.pin(0);
+        for i in 0..segments {
+            rope.add_constraint(i, i + 1, 1.0);
+        }
+        rope
+    }
+
+    // Sheet of cloth spanning the parallelogram from the corner along both edges, with the given number of
+    // particles along each edge in row major order. Nothing is pinned, so it falls freely until some of its
+    // particles are pinned. Structural links along both edges keep the sheet together and softer links
+    // along the diagonals resist shearing.
+    pub fn cloth(
+        corner: Vector3,
+        edge1: Vector3,
+        edge2: Vector3,
+        columns: usize,
+        rows: usize,
+        mass: f64,
+    ) -> Self {
+        let (columns, rows) = (columns.max(2), rows.max(2));
+        let mut cloth = ParticleSystem::new();
+        let particle_mass = mass / (columns * rows) as f64;
+        let index = |column: usize, row: usize| row * columns + column;
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let position = corner
+                    + edge1 * (column as f64 / (columns - 1) as f64)
+                    + edge2 * (row as f64 / (rows - 1) as f64);
+                cloth.add_particle(position, particle_mass);
+            }
+        }
+
+        for row in 0..rows {
+            for column in 0..columns {
+                if column + 1 < columns {
+                    cloth.add_constraint(index(column, row), index(column + 1, row), 1.0);
+                }
+                if row + 1 < rows {
+                    cloth.add_constraint(index(column, row), index(column, row + 1), 1.0);
+                }
+                if column + 1 < columns && row + 1 < rows {
+                    cloth.add_constraint(index(column, row), index(column + 1, row + 1), 0.5);
+                    cloth.add_constraint(index(column + 1, row), index(column, row + 1), 0.5);
+
+                    let quad = [
+                        index(column, row),
+                        index(column + 1, row),
+                        index(column + 1, row + 1),
+                        index(column, row + 1),
+                    ];
+                    cloth.triangles.push([quad[0], quad[1], quad[2]]);
+                    cloth.triangles.push([quad[2], quad[3], quad[0]]);
+                }
+            }
+        }
+
+        cloth
+    }
+
+    pub fn add_particle(&mut self, position: Vector3, mass: f64) -> usize {
+        self.particles.push(Particle::new(position, mass));
+        self.particles.len() - 1
+    }
+
+    // Links two particles at their current distance
+    pub fn add_constraint(&mut self, particle1: usize, particle2: usize, stiffness: f64) -> usize {
+        let offset = self.particles[particle2].position - self.particles[particle1].position;
+        let rest_length = offset.magnitude();
+        self.constraints.push(DistanceConstraint {
+            particle1,
+            particle2,
+            rest_length,
+            stiffness,
+        });
+        self.constraints.len() - 1
+    }
+
+    pub fn pin(&mut self, particle: usize) {
+        self.particles[particle].inverse_mass = 0.0;
+    }
+
+    // Advances the particles by a fixed timestep, then relaxes the constraints and pushes the particles
+    // out of the bodies and planes a few times over. Verlet integration needs the same timestep every
+    // step to keep the implied velocities right.
+    pub fn step(&mut self, dt: f64, gravity: Vector3, bodies: &[Body], planes: &[Plane]) {
+        let damping = 1.0 / (1.0 + self.damping * dt);
+        for particle in self.particles.iter_mut() {
+            particle.start_position = particle.position;
+        }
+        for particle in self.particles.iter_mut().filter(|particle| particle.inverse_mass > 0.0) {
+            let velocity = (particle.position - particle.previous_position) * damping;
+            particle.previous_position = particle.position;
+            particle.position += velocity + gravity * (dt * dt);
+        }
+
+        // Only bodies near the particles can touch them
+        let bounds = self.aabb().expand(self.radius);
+        let bodies: Vec<&Body> = bodies
+            .iter()
+            .filter(|body| !body.is_sensor && body.aabb().overlaps(&bounds))
+            .collect();
+
+        for _ in 0..self.iterations {
+            for constraint in self.constraints.iter() {
+                solve_constraint(&mut self.particles, constraint);
+            }
+            for index in 0..self.particles.len() {
+                self.collide(index, &bodies, planes);
+            }
+        }
+    }
+
+    // Pushes a particle out of whatever it has sunk into, removing part of its sliding motion as friction
+    fn collide(&mut self, index: usize, bodies: &[&Body], planes: &[Plane]) {
+        let (radius, friction) = (self.radius, self.friction);
+        let particle = &mut self.particles[index];
+        if particle.inverse_mass == 0.0 {
+            return;
+        }
+
+        for plane in planes {
+            let depth = radius - plane.signed_distance(particle.position);
+            if depth > 0.0 {
+                particle.push_out(plane.normal, depth, friction);
+            }
+        }
+
+        for body in bodies {
+            let local = body.transform.point_to_local_space(particle.position);
+            let (distance, normal) = surface_distance(&body.shape, local);
+            if distance < radius {
+                let normal = body.transform.rotation.vector_to_world_space(normal);
+                particle.push_out(normal, radius - distance, friction);
+            }
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let mut particles = self.particles.iter();
+        let Some(first) = particles.next() else {
+            return Aabb::default();
+        };
+        particles.fold(Aabb::new(first.position, first.position), |aabb, particle| {
+            aabb.union(&Aabb::new(particle.position, particle.position))
+        })
+    }
+
+    // Both sides of every triangle of the surface, or a small diamond at each particle for systems
+    // without a surface such as ropes, with the particles blended from where the last step started by alpha
+    pub fn geometry(&self, alpha: f64) -> Vec<SceneTriangle> {
+        let positions: Vec<Vector3> = self
+            .particles
+            .iter()
+            .map(|particle| particle.start_position.lerp(particle.position, alpha))
+            .collect();
+        let triangle = |points: [Vector3; 3]| {
+            let normal = (points[1] - points[0]).cross(points[2] - points[0]).unit();
+            SceneTriangle {
+                points,
+                normal,
+                color: self.color,
+            }
+        };
+
+        if !self.triangles.is_empty() {
+            return self
+                .triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let [a, b, c] = [a, b, c].map(|index| positions[index]);
+                    [triangle([a, b, c]), triangle([c, b, a])]
+                })
+                .filter(|triangle| triangle.normal.magnitude() > 0.0)
+                .collect();
+        }
+
+        let axes = [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS].map(|axis| axis * self.radius);
+        positions
+            .iter()
+            .flat_map(|&center| {
+                let mut triangles = Vec::with_capacity(8);
+                for x in [1.0, -1.0] {
+                    for y in [1.0, -1.0] {
+                        for z in [1.0, -1.0] {
+                            let points = [center + axes[0] * x, center + axes[1] * y, center + axes[2] * z];
+                            // Keep the corners counter-clockwise seen from outside
+                            let points =
+                                if x * y * z > 0.0 { points } else { [points[0], points[2], points[1]] };
+                            triangles.push(triangle(points));
+                        }
+                    }
+                }
+                triangles
+            })
+            .collect()
+    }
+}
+
+// Moves both particles along the line between them by their share of the error, the lighter one further
+fn solve_constraint(particles: &mut [Particle], constraint: &DistanceConstraint) {
+    let (particle1, particle2) = (particles[constraint.particle1], particles[constraint.particle2]);
+    let inverse_mass = particle1.inverse_mass + particle2.inverse_mass;
+    let offset = particle2.position - particle1.position;
+    let distance = offset.magnitude();
+    if inverse_mass == 0.0 || distance <= f64::EPSILON {
+        return;
+    }
+
+    let error = distance - constraint.rest_length;
+    let correction = offset * (constraint.stiffness * error / (distance * inverse_mass));
+    particles[constraint.particle1].position += correction * particle1.inverse_mass;
+    particles[constraint.particle2].position -= correction * particle2.inverse_mass;
+}
+
+// Signed distance from a body space point to the surface of a shape and the direction out of the shape.
+// The distance is exact for boxes, spheres, capsules and custom shapes and a lower bound outside of
+// convex hulls.
+fn surface_distance(shape: &Shape, point: Vector3) -> (f64, Vector3) {
+    let direction = |offset: Vector3| {
+        if offset.magnitude() > f64::EPSILON { offset.unit() } else { Vector3::Y_AXIS }
+    };
+
+    match shape {
+        Shape::Box { half_size } => {
+            let closest = Vector3::new(
+                point.x.clamp(-half_size.x, half_size.x),
+                point.y.clamp(-half_size.y, half_size.y),
+                point.z.clamp(-half_size.z, half_size.z),
+            );
+            let offset = point - closest;
+            if offset.magnitude() > 0.0 {
+                return (offset.magnitude(), offset.unit());
+            }
+
+            // Inside, the closest face is the one the point is least deep below
+            let sign = |value: f64| if value < 0.0 { -1.0 } else { 1.0 };
+            [
+                (point.x.abs() - half_size.x, Vector3::X_AXIS * sign(point.x)),
+                (point.y.abs() - half_size.y, Vector3::Y_AXIS * sign(point.y)),
+                (point.z.abs() - half_size.z, Vector3::Z_AXIS * sign(point.z)),
+            ]
+            .into_iter()
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap()
+        }
+        Shape::Sphere { radius } => (point.magnitude() - radius, direction(point)),
+        Shape::Capsule {
+            radius,
+            half_height,
+        } => {
+            let offset = point - Vector3::new(0.0, point.y.clamp(-half_height, *half_height), 0.0);
+            (offset.magnitude() - radius, direction(offset))
+        }
+        Shape::ConvexHull(hull) => hull
+            .faces
+            .iter()
+            .map(|face| (face.normal.dot(point - hull.vertices[face.vertices[0]]), face.normal))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap_or((f64::INFINITY, Vector3::Y_AXIS)),
+        Shape::Custom(custom) => match gjk::gjk(&point, &**custom) {
+            GjkResult::Separated { point_b, .. } => {
+                ((point - point_b).magnitude(), direction(point - point_b))
+            }
+            GjkResult::Intersecting(simplex) => gjk::epa(&point, &**custom, simplex).map_or(
+                (0.0, Vector3::Y_AXIS),
+                |penetration| (-penetration.depth, penetration.normal.inverse()),
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 1.0 / 60.0;
+    const GRAVITY: Vector3 = Vector3 { x: 0.0, y: -98.1, z: 0.0 };
+
+    #[test]
+    fn damping_takes_away_a_fraction_of_the_velocity_per_second() {
+        let mut system = ParticleSystem::new();
+        system.damping = 0.6;
+        for dt in [DT, 0.5 * DT] {
+            system.particles.clear();
+            let particle = system.add_particle(Vector3::default(), 1.0);
+            system.particles[particle].previous_position = Vector3::new(-10.0 * dt, 0.0, 0.0);
+
+            system.step(dt, Vector3::default(), &[], &[]);
+            let velocity = system.particles[particle].position.x / dt;
+            assert!((velocity - 10.0 / (1.0 + 0.6 * dt)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn ropes_hang_from_their_pinned_start() {
+        let (start, end) = (Vector3::new(0.0, 10.0, 0.0), Vector3::new(10.0, 10.0, 0.0));
+        let mut rope = ParticleSystem::rope(start, end, 10, 1.0);
+        rope.iterations = 50;
+        for _ in 0..600 {
+            rope.step(DT, GRAVITY, &[], &[]);
+        }
+
+        let (first, last) = (rope.particles[0].position, rope.particles[10].position);
+        assert!((first - start).magnitude() == 0.0);
+        assert!(last.x.abs() < 1.0 && last.y < 1.0, "rope ends at {last:?}");
+        for constraint in rope.constraints.iter() {
+            let length = (rope.particles[constraint.particle2].position
+                - rope.particles[constraint.particle1].position)
+                .magnitude();
+            assert!((length - constraint.rest_length).abs() < 0.05 * constraint.rest_length);
+        }
+    }
+
+    #[test]
+    fn cloth_comes_to_rest_on_the_ground() {
+        let (edge1, edge2) = (Vector3::X_AXIS * 4.0, Vector3::Z_AXIS * 4.0);
+        let mut cloth = ParticleSystem::cloth(Vector3::new(0.0, 5.0, 0.0), edge1, edge2, 5, 5, 1.0);
+        let ground = Plane::new(Vector3::Y_AXIS, Vector3::default());
+        for _ in 0..300 {
+            cloth.step(DT, GRAVITY, &[], &[ground]);
+        }
+
+        for particle in cloth.particles.iter() {
+            assert!((particle.position.y - cloth.radius).abs() < 1e-3, "particle at {:?}", particle.position);
+        }
+    }
+
+    #[test]
+    fn geometry_is_blended_between_steps() {
+        let mut rope = ParticleSystem::rope(Vector3::default(), Vector3::X_AXIS, 1, 1.0);
+        rope.constraints.clear();
+        rope.step(DT, GRAVITY, &[], &[]);
+
+        let lowest = |alpha: f64| {
+            rope.geometry(alpha)
+                .iter()
+                .flat_map(|triangle| triangle.points)
+                .map(|point| point.y)
+                .fold(f64::INFINITY, f64::min)
+        };
+        let fall = rope.particles[1].position.y;
+        assert!(fall < 0.0);
+        assert!((lowest(0.0) + rope.radius).abs() < 1e-9);
+        assert!((lowest(0.5) - (0.5 * fall - rope.radius)).abs() < 1e-9);
+        assert!((lowest(1.0) - (fall - rope.radius)).abs() < 1e-9);
+    }
+}
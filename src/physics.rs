@@ -0,0 +1,450 @@
+use crate::{
+    body::Body,
+    body_set::{BodyHandle, BodySet},
+    broadphase::{Aabb, SweepAndPrune},
+    ccd::resolve_bullet_impacts,
+    collision::*,
+    event::*,
+    force::*,
+    island::*,
+    joint::Joint,
+    particle::ParticleSystem,
+    raycast::*,
+    solver::*,
+    transform::*,
+    trigger::*,
+};
+
+pub const DEFAULT_TIMESTEP: f64 = 1.0 / 60.0;
+pub const DEFAULT_GRAVITY: Vector3 = Vector3 { x: 0.0, y: -98.1, z: 0.0 };
+
+// Upper bound on the steps taken by a single update so a slow frame can't make the next frame slower
+// still (the "spiral of death"); time beyond it is dropped
+const MAX_STEPS_PER_UPDATE: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RaycastHit {
+    // Body hit, None when the ray hit static world geometry
+    pub body: Option<BodyHandle>,
+    pub hit: RayHit,
+}
+
+pub struct PhysicsWorld {
+    pub bodies: BodySet,
+    // Static world geometry that bodies collide with
+    pub planes: Vec<Plane>,
+    pub joints: Vec<Joint>,
+    // Forces applied to the awake dynamic bodies each sub-step on top of gravity
+    pub force_generators: Vec<ForceGenerator>,
+    // Ropes and cloth, stepped after the bodies and pushed out of them
+    pub particle_systems: Vec<ParticleSystem>,
+    pub gravity: Vector3,
+    pub timestep: f64,
+    pub sub_steps: usize,
+    pub solver: SolverSettings,
+    pub sleep: SleepSettings,
+    accumulator: f64,
+    broadphase: SweepAndPrune,
+    // Contacts and islands refer to bodies by their position in the body set. The contacts of the last
+    // sub-step are kept to warm start the solve.
+    contacts: Vec<Contact>,
+    previous_contacts: Vec<Contact>,
+    islands: Vec<Vec<usize>>,
+    // Fraction of each sub-step the bodies move, less than one for bullets stopped at an impact
+    fractions: Vec<f64>,
+    // (sensor, body) pairs overlapping at any sub-step of the current step by position, and of the last
+    // step by handle, sorted
+    overlaps: Vec<(usize, usize)>,
+    previous_overlaps: Vec<(BodyHandle, BodyHandle)>,
+    // Normal impulse of every contact of every sub-step of the current step by pair, and sorted pairs of
+    // bodies touching as of the last step
+    impulses: Vec<(BodyPair, f64)>,
+    touching: Vec<BodyPair>,
+    events: Vec<PhysicsEvent>,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        PhysicsWorld {
+            bodies: BodySet::new(),
+            planes: Vec::new(),
+            joints: Vec::new(),
+            force_generators: Vec::new(),
+            particle_systems: Vec::new(),
+            gravity: DEFAULT_GRAVITY,
+            timestep: DEFAULT_TIMESTEP,
+            sub_steps: 1,
+            solver: SolverSettings::default(),
+            sleep: SleepSettings::default(),
+            accumulator: 0.0,
+            broadphase: SweepAndPrune::new(),
+            contacts: Vec::new(),
+            previous_contacts: Vec::new(),
+            islands: Vec::new(),
+            fractions: Vec::new(),
+            overlaps: Vec::new(),
+            previous_overlaps: Vec::new(),
+            impulses: Vec::new(),
+            touching: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_body(&mut self, body: Body) -> BodyHandle {
+        self.bodies.insert(body)
+    }
+
+    // Removes a body along with the joints attached to it, ending its contacts and overlaps and waking
+    // the bodies it touched so nothing is left asleep resting on it in mid-air. Returns None if the body
+    // was already removed.
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<Body> {
+        let body = self.bodies.remove(handle)?;
+        self.joints.retain(|joint| joint.body1 != handle && joint.body2 != Some(handle));
+
+        let (bodies, events) = (&mut self.bodies, &mut self.events);
+        self.touching.retain(|&(body1, body2)| {
+            let is_kept = body1 != handle && body2 != Some(handle);
+            if !is_kept {
+                let other = if body1 == handle { body2 } else { Some(body1) };
+                if let Some(other) = other.and_then(|other| bodies.get_mut(other)) {
+                    other.wake();
+                }
+
+                let kind = ContactEventKind::Ended;
+                events.push(PhysicsEvent::Contact(ContactEvent { body1, body2, kind }));
+            }
+            is_kept
+        });
+        self.previous_overlaps.retain(|&(sensor, body)| {
+            let is_kept = sensor != handle && body != handle;
+            if !is_kept {
+                let kind = TriggerEventKind::Exit;
+                events.push(PhysicsEvent::Trigger(TriggerEvent { sensor, body, kind }));
+            }
+            is_kept
+        });
+
+        // Removing a body moved another into its position, so these are rebuilt by the next step
+        self.contacts.clear();
+        self.previous_contacts.clear();
+        self.islands.clear();
+        self.overlaps.clear();
+
+        Some(body)
+    }
+
+    pub fn add_plane(&mut self, plane: Plane) {
+        self.planes.push(plane);
+    }
+
+    pub fn add_joint(&mut self, joint: Joint) -> usize {
+        self.joints.push(joint);
+        self.joints.len() - 1
+    }
+
+    pub fn add_force_generator(&mut self, generator: ForceGenerator) -> usize {
+        self.force_generators.push(generator);
+        self.force_generators.len() - 1
+    }
+
+    pub fn add_particle_system(&mut self, system: ParticleSystem) -> usize {
+        self.particle_systems.push(system);
+        self.particle_systems.len() - 1
+    }
+
+    // Gives the dynamic bodies within the radius an impulse away from the center, waking them
+    pub fn explode(&mut self, center: Vector3, radius: f64, impulse: f64) {
+        explode(self.bodies.as_mut_slice(), center, radius, impulse);
+    }
+
+    // Advances the simulation by a variable frame time in fixed timesteps, carrying the remainder over
+    // to the next update. Returns the number of steps taken. A frame time that isn't positive is ignored
+    // rather than winding the accumulator back or filling it with NaN.
+    pub fn update(&mut self, dt: f64) -> usize {
+        if dt.is_nan() || dt <= 0.0 {
+            return 0;
+        }
+
+        self.accumulator += dt;
+
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            if steps == MAX_STEPS_PER_UPDATE {
+                self.accumulator = 0.0;
+                break;
+            }
+
+            self.step();
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+
+        steps
+    }
+
+    // Advances the simulation by exactly one timestep, split into sub-steps. A step is deterministic: it
+    // only depends on the state of the world, since every list it builds is sorted by body position or
+    // handle rather than left in an order that depends on hashing or memory addresses.
+    pub fn step(&mut self) {
+        for body in self.bodies.as_mut_slice() {
+            body.previous_transform = body.transform.clone();
+        }
+
+        // A body can hit something or pass through a sensor within a step, so the contacts and overlaps of
+        // every sub-step count
+        self.impulses.clear();
+        self.overlaps.clear();
+        let dt = self.timestep / self.sub_steps.max(1) as f64;
+        for _ in 0..self.sub_steps.max(1) {
+            self.sub_step(dt);
+        }
+
+        find_contact_events(&self.bodies, &self.impulses, &mut self.touching, &mut self.events);
+        let bodies = &self.bodies;
+        let mut overlaps: Vec<(BodyHandle, BodyHandle)> = self
+            .overlaps
+            .iter()
+            .map(|&(sensor, body)| (bodies.handle(sensor), bodies.handle(body)))
+            .collect();
+        overlaps.sort_unstable();
+        overlaps.dedup();
+        find_trigger_events(&self.previous_overlaps, &overlaps, &mut self.events);
+        self.previous_overlaps = overlaps;
+
+        find_islands(&self.bodies, &self.contacts, &self.touching, &self.joints, &mut self.islands);
+        if self.sleep.enabled {
+            update_sleep(self.bodies.as_mut_slice(), &self.islands, self.timestep, &self.sleep);
+        }
+
+        for system in self.particle_systems.iter_mut() {
+            system.step(self.timestep, self.gravity, self.bodies.as_slice(), &self.planes);
+        }
+    }
+
+    fn sub_step(&mut self, dt: f64) {
+        apply_force_generators(&self.force_generators, self.bodies.as_mut_slice(), self.gravity, dt);
+        for body in self.bodies.as_mut_slice() {
+            body.integrate_velocity(dt, self.gravity);
+        }
+
+        std::mem::swap(&mut self.contacts, &mut self.previous_contacts);
+        find_contacts(
+            self.bodies.as_slice(),
+            &self.planes,
+            &mut self.broadphase,
+            &mut self.contacts,
+            &mut self.overlaps,
+        );
+
+        // Jointed bodies pass through each other unless the joint asks otherwise, so that parts of a
+        // ragdoll or a hinge and its frame can overlap
+        let (joints, bodies) = (&self.joints, &self.bodies);
+        self.contacts.retain(|contact| {
+            contact.body2.is_none_or(|body2| {
+                let (body1, body2) = (bodies.handle(contact.body1), bodies.handle(body2));
+                !joints
+                    .iter()
+                    .any(|joint| !joint.collide_connected && joint.connects(body1, body2))
+            })
+        });
+
+        warm_start_contacts(&self.previous_contacts, &mut self.contacts);
+
+        wake_touched_by_kinematic(self.bodies.as_mut_slice(), &self.contacts);
+        solve_constraints(&mut self.bodies, &mut self.contacts, &self.joints, dt, &self.solver);
+        accumulate_contact_impulses(&self.bodies, &self.contacts, &mut self.impulses);
+
+        resolve_bullet_impacts(self.bodies.as_mut_slice(), &self.planes, dt, &mut self.fractions);
+        for (body, fraction) in self.bodies.as_mut_slice().iter_mut().zip(self.fractions.iter()) {
+            body.integrate_position(dt * fraction);
+        }
+    }
+
+    // How far the simulation time is between the last step and the next one, for blending the previous
+    // and current transforms of bodies when rendering
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.timestep).clamp(0.0, 1.0)
+    }
+
+    // Takes the contact and trigger events of every step since the events were last drained, in the
+    // order they happened. Events queue up until drained, so something has to drain them regularly.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, PhysicsEvent> {
+        self.events.drain(..)
+    }
+
+    // Closest body or plane hit by a ray within max_distance. Bodies are visited in the order the
+    // broadphase finds their bounding boxes along the ray, stopping once the next box starts beyond the
+    // closest hit so far. Sensors are skipped since there is nothing solid to hit.
+    pub fn raycast(&self, ray: &Ray, max_distance: f64) -> Option<RaycastHit> {
+        let mut closest = self
+            .planes
+            .iter()
+            .filter_map(|plane| ray_plane(ray, plane, max_distance))
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+            .map(|hit| RaycastHit { body: None, hit });
+
+        let aabbs: Vec<Aabb> = self.bodies.as_slice().iter().map(Body::aabb).collect();
+        let mut candidates = Vec::new();
+        self.broadphase.find_ray_hits(&aabbs, ray, max_distance, &mut candidates);
+
+        for (distance, index) in candidates {
+            let max_distance = closest.map_or(max_distance, |closest| closest.hit.distance);
+            if distance > max_distance {
+                break;
+            }
+
+            let body = &self.bodies.as_slice()[index];
+            if body.is_sensor {
+                continue;
+            }
+
+            if let Some(hit) = body.raycast(ray, max_distance) {
+                if closest.is_none_or(|closest| hit.distance < closest.hit.distance) {
+                    closest = Some(RaycastHit {
+                        body: Some(self.bodies.handle(index)),
+                        hit,
+                    });
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::shape::Shape;
+
+    #[test]
+    fn updates_take_whole_steps_and_carry_the_remainder_over() {
+        let mut world = PhysicsWorld::new();
+        let handle = world.add_body(Body::new(Transform::default(), Shape::Sphere { radius: 1.0 }, 1.0));
+        let timestep = world.timestep;
+
+        assert_eq!(world.update(2.5 * timestep), 2);
+        assert!((world.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(world.update(0.75 * timestep), 1);
+        assert!((world.alpha() - 0.25).abs() < 1e-9);
+
+        // Frame times that aren't positive don't step or change how far the world is towards the next step
+        for dt in [0.0, -timestep, f64::NAN] {
+            assert_eq!(world.update(dt), 0);
+            assert!((world.alpha() - 0.25).abs() < 1e-9);
+        }
+
+        // A long frame only takes as many steps as are allowed and drops the rest instead of catching up
+        // over the next updates
+        assert_eq!(world.update(20.0 * timestep), MAX_STEPS_PER_UPDATE);
+        assert_eq!(world.alpha(), 0.0);
+        assert_eq!(world.update(0.5 * timestep), 0);
+
+        // Every step counted was taken
+        let steps = (3 + MAX_STEPS_PER_UPDATE) as f64;
+        let velocity = world.bodies[handle].linear_velocity.y;
+        assert!((velocity - DEFAULT_GRAVITY.y * timestep * steps).abs() < 1e-9);
+    }
+
+    #[test]
+    fn raycast_finds_the_nearest_solid_body() {
+        let mut world = PhysicsWorld::new();
+        world.add_plane(Plane::new(Vector3::Y_AXIS, Vector3::default()));
+        let body_at = |x: f64, size: f64| {
+            Body::new(
+                Transform::new(Vector3::new(x, 5.0, 0.0), Quaternion::default()),
+                Shape::Box {
+                    half_size: Vector3::new(size, size, size),
+                },
+                1.0,
+            )
+        };
+        let far = world.add_body(body_at(40.0, 2.0));
+        let near = world.add_body(body_at(20.0, 2.0));
+        world.add_body(Body {
+            is_sensor: true,
+            ..body_at(10.0, 4.0)
+        });
+
+        let ray = Ray::new(Vector3::new(0.0, 5.0, 0.0), Vector3::X_AXIS);
+        let hit = world.raycast(&ray, 100.0).expect("ray along the row of boxes should hit one");
+        assert_eq!(hit.body, Some(near));
+        assert!((hit.hit.distance - 18.0).abs() < 1e-9);
+
+        world.remove_body(near);
+        assert_eq!(world.raycast(&ray, 100.0).and_then(|hit| hit.body), Some(far));
+        assert!(world.raycast(&ray, 30.0).is_none());
+
+        let down = Ray::new(Vector3::new(0.0, 5.0, 0.0), Vector3::Y_AXIS.inverse());
+        let hit = world.raycast(&down, 100.0).expect("ray down should hit the floor");
+        assert!(hit.body.is_none() && (hit.hit.distance - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn jointed_bodies_only_collide_when_the_joint_asks_for_it() {
+        for collide_connected in [false, true] {
+            let mut world = PhysicsWorld::new();
+            world.gravity = Vector3::default();
+            let cube = |x: f64| {
+                Body::new(
+                    Transform::new(Vector3::new(x, 0.0, 0.0), Quaternion::default()),
+                    Shape::Box {
+                        half_size: Vector3::new(1.0, 1.0, 1.0),
+                    },
+                    1.0,
+                )
+            };
+            let body1 = world.add_body(cube(0.0));
+            let body2 = world.add_body(cube(1.5));
+            let joint = Joint::ball_socket(&world.bodies, body1, Some(body2), Vector3::new(0.75, 0.0, 0.0));
+            world.add_joint(joint.with_collide_connected(collide_connected));
+
+            world.step();
+            assert_eq!(world.contacts.is_empty(), !collide_connected);
+        }
+    }
+
+    #[test]
+    fn removing_a_body_ends_its_contacts_and_wakes_what_rested_on_it() {
+        let mut world = PhysicsWorld::new();
+        world.add_plane(Plane::new(Vector3::Y_AXIS, Vector3::default()));
+        let cube = |y: f64| {
+            Body::new(
+                Transform::new(Vector3::new(0.0, y, 0.0), Quaternion::default()),
+                Shape::Box {
+                    half_size: Vector3::new(5.0, 5.0, 5.0),
+                },
+                1.0,
+            )
+        };
+        let bottom = world.add_body(cube(5.0));
+        let top = world.add_body(cube(15.0));
+        for _ in 0..300 {
+            world.step();
+        }
+        assert!(world.bodies[top].is_sleeping);
+        world.drain_events().count();
+
+        assert!(world.remove_body(bottom).is_some());
+        assert!(world.remove_body(bottom).is_none());
+        assert!(!world.bodies[top].is_sleeping);
+
+        let ended: Vec<BodyPair> = world
+            .drain_events()
+            .filter_map(|event| match event {
+                PhysicsEvent::Contact(ContactEvent { body1, body2, kind: ContactEventKind::Ended }) => {
+                    Some((body1, body2))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ended, [(bottom, None), (bottom.min(top), Some(bottom.max(top)))]);
+    }
+}
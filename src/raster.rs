@@ -20,12 +20,16 @@ pub struct SceneTriangle {
     pub color: Color,
 }
 
-pub trait Viewport: Transformable {
+pub trait Viewport {
     fn new(transform: Transform, width: f64, height: f64) -> Self;
     fn project_geometry<'a>(
         &'a self,
         geometry: &'a [SceneTriangle],
     ) -> BoxedIterator<'a, (&'a SceneTriangle, TriangleProjection)>;
+
+    // World space ray from the camera through a point in normalized screen coordinates, where (0, 0) is
+    // the top left corner and (1, 1) the bottom right, for picking what is under the cursor
+    fn screen_ray(&self, x: f64, y: f64) -> Ray;
 }
 
 #[derive(Clone)]
@@ -33,31 +37,26 @@ pub struct OrthographicCamera {
     pub transform: Transform,
     pub width: f64,
     pub height: f64,
+    pub near: f64,
 }
 
 impl OrthographicCamera {
+    pub const DEFAULT_NEAR: f64 = 0.1;
+
     fn point_to_projection_space(&self, mut point: Vector3) -> Vector3 {
-        point = self.transform.point_to_local_space(point);
         point.x = (point.x + 0.5 * self.width) / self.width;
         point.y = 1.0 - (point.y + 0.5 * self.height) / self.height;
         point
     }
 
-    fn are_projection_bounds_within_viewport_bounds(&self, projection_points: &[Vector3]) -> bool {
-        let x0 = projection_points
-            .iter()
-            .fold(f64::MAX, |x0, point| x0.min(point.x));
-        let y0 = projection_points
-            .iter()
-            .fold(f64::MAX, |y0, point| y0.min(point.y));
-        let x1 = projection_points
-            .iter()
-            .fold(f64::MIN, |x1, point| x1.max(point.x));
-        let y1 = projection_points
-            .iter()
-            .fold(f64::MIN, |y1, point| y1.max(point.y));
-
-        x0 < 1.0 && x1 > 0.0 && y0 < 1.0 && y1 > 0.0
+    fn clip_planes(&self) -> [ClipPlane; 5] {
+        [
+            ClipPlane::new(Vector3::new(0.0, 0.0, 1.0), -self.near),
+            ClipPlane::new(Vector3::new(1.0, 0.0, 0.0), 0.5 * self.width),
+            ClipPlane::new(Vector3::new(-1.0, 0.0, 0.0), 0.5 * self.width),
+            ClipPlane::new(Vector3::new(0.0, 1.0, 0.0), 0.5 * self.height),
+            ClipPlane::new(Vector3::new(0.0, -1.0, 0.0), 0.5 * self.height),
+        ]
     }
 }
 
@@ -67,6 +66,7 @@ impl Viewport for OrthographicCamera {
             transform,
             width,
             height,
+            near: Self::DEFAULT_NEAR,
         }
     }
 
@@ -74,22 +74,14 @@ impl Viewport for OrthographicCamera {
         &'a self,
         geometry: &'a [SceneTriangle],
     ) -> BoxedIterator<'a, (&'a SceneTriangle, TriangleProjection)> {
-        //log::info!("{:?}", self.transform);
         let (camera_right, camera_up, camera_look) = self.transform.rotation.basis_vectors();
-        Box::new(geometry.iter().filter_map(move |tri: &SceneTriangle| {
+        let clip_planes = self.clip_planes();
+        Box::new(geometry.iter().flat_map(move |tri: &'a SceneTriangle| {
+            let mut projections = Vec::new();
+
             // Backface culling
             if tri.normal.dot(camera_look) >= 0.0 {
-                return None;
-            }
-
-            let mut projection_points = tri.points;
-            for point in projection_points.iter_mut() {
-                *point = self.point_to_projection_space(*point);
-            }
-
-            // Cull triangles that are completely off screen
-            if !self.are_projection_bounds_within_viewport_bounds(&projection_points) {
-                return None;
+                return projections;
             }
 
             // Calculate the distance the plane containing the triangle recedes from the camera plane
@@ -100,25 +92,202 @@ impl Viewport for OrthographicCamera {
                 z: 0.0,
             };
 
-            Some((
-                tri,
-                TriangleProjection {
-                    projection_points,
-                    projection_distance_change,
-                },
-            ))
+            let camera_points = tri.points.map(|point| self.transform.point_to_local_space(point));
+            for camera_points in clip_triangle(camera_points, &clip_planes) {
+                projections.push((
+                    tri,
+                    TriangleProjection {
+                        projection_points: camera_points.map(|point| self.point_to_projection_space(point)),
+                        projection_distance_change,
+                    },
+                ));
+            }
+
+            projections
+        }))
+    }
+
+    fn screen_ray(&self, x: f64, y: f64) -> Ray {
+        // Every ray looks straight ahead, starting from where the point lies on the camera plane
+        let (camera_right, camera_up, camera_look) = self.transform.rotation.basis_vectors();
+        Ray::new(
+            self.transform.position
+                + camera_right * ((x - 0.5) * self.width)
+                + camera_up * ((0.5 - y) * self.height),
+            camera_look,
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct PerspectiveCamera {
+    pub transform: Transform,
+    pub field_of_view: f64,
+    pub aspect_ratio: f64,
+    pub near: f64,
+    pub far: f64,
+}
+
+impl PerspectiveCamera {
+    pub const DEFAULT_FIELD_OF_VIEW: f64 = 70.0 * std::f64::consts::PI / 180.0;
+    pub const DEFAULT_NEAR: f64 = 0.1;
+    pub const DEFAULT_FAR: f64 = 10_000.0;
+
+    // Maps a camera space point to projection space where x and y are normalized screen coordinates
+    // and z is a depth in [0, 1] between the near and far planes. Depth is stored as a function of 1/z
+    // so that it varies linearly across the screen and can be interpolated by the rasterizer.
+    fn point_to_projection_space(&self, point: Vector3) -> Vector3 {
+        let tan_half_fov = (0.5 * self.field_of_view).tan();
+        let x_ndc = point.x / (point.z * tan_half_fov * self.aspect_ratio);
+        let y_ndc = point.y / (point.z * tan_half_fov);
+        Vector3 {
+            x: 0.5 * (x_ndc + 1.0),
+            y: 0.5 * (1.0 - y_ndc),
+            z: self.depth_scale() * (1.0 - self.near / point.z),
+        }
+    }
+
+    fn depth_scale(&self) -> f64 {
+        self.far / (self.far - self.near)
+    }
+
+    fn clip_planes(&self) -> [ClipPlane; 6] {
+        let tan_half_fov = (0.5 * self.field_of_view).tan();
+        let tan_half_fov_x = tan_half_fov * self.aspect_ratio;
+        [
+            ClipPlane::new(Vector3::new(0.0, 0.0, 1.0), -self.near),
+            ClipPlane::new(Vector3::new(0.0, 0.0, -1.0), self.far),
+            ClipPlane::new(Vector3::new(1.0, 0.0, tan_half_fov_x), 0.0),
+            ClipPlane::new(Vector3::new(-1.0, 0.0, tan_half_fov_x), 0.0),
+            ClipPlane::new(Vector3::new(0.0, 1.0, tan_half_fov), 0.0),
+            ClipPlane::new(Vector3::new(0.0, -1.0, tan_half_fov), 0.0),
+        ]
+    }
+}
+
+impl Viewport for PerspectiveCamera {
+    fn new(transform: Transform, width: f64, height: f64) -> Self {
+        Self {
+            transform,
+            field_of_view: Self::DEFAULT_FIELD_OF_VIEW,
+            aspect_ratio: width / height,
+            near: Self::DEFAULT_NEAR,
+            far: Self::DEFAULT_FAR,
+        }
+    }
+
+    fn project_geometry<'a>(
+        &'a self,
+        geometry: &'a [SceneTriangle],
+    ) -> BoxedIterator<'a, (&'a SceneTriangle, TriangleProjection)> {
+        let tan_half_fov = (0.5 * self.field_of_view).tan();
+        let clip_planes = self.clip_planes();
+        Box::new(geometry.iter().flat_map(move |tri: &'a SceneTriangle| {
+            let mut projections = Vec::new();
+
+            // Backface culling against the direction from the camera to the triangle
+            let plane_distance = tri.normal.dot(tri.points[0] - self.transform.position);
+            if plane_distance >= 0.0 {
+                return projections;
+            }
+
+            // Depth is affine in 1/z, which for points on the plane n.p = c is affine in screen space,
+            // so the rate of change follows directly from the camera space plane of the triangle
+            let normal = self.transform.rotation.vector_to_local_space(tri.normal);
+            let depth_change_scale = 2.0 * self.depth_scale() * self.near * tan_half_fov / plane_distance;
+            let projection_distance_change = Vector3 {
+                x: -depth_change_scale * self.aspect_ratio * normal.x,
+                y: depth_change_scale * normal.y,
+                z: 0.0,
+            };
+
+            let camera_points = tri.points.map(|point| self.transform.point_to_local_space(point));
+            for camera_points in clip_triangle(camera_points, &clip_planes) {
+                projections.push((
+                    tri,
+                    TriangleProjection {
+                        projection_points: camera_points.map(|point| self.point_to_projection_space(point)),
+                        projection_distance_change,
+                    },
+                ));
+            }
+
+            projections
         }))
     }
+
+    fn screen_ray(&self, x: f64, y: f64) -> Ray {
+        let tan_half_fov = (0.5 * self.field_of_view).tan();
+        let direction = Vector3::new(
+            (2.0 * x - 1.0) * tan_half_fov * self.aspect_ratio,
+            (1.0 - 2.0 * y) * tan_half_fov,
+            1.0,
+        );
+        Ray::new(
+            self.transform.position,
+            self.transform.rotation.vector_to_world_space(direction),
+        )
+    }
+}
+
+// Camera space half space n.p + d >= 0 that geometry is clipped against
+#[derive(Clone, Copy)]
+struct ClipPlane {
+    normal: Vector3,
+    distance: f64,
+}
+
+impl ClipPlane {
+    fn new(normal: Vector3, distance: f64) -> Self {
+        Self { normal, distance }
+    }
+
+    fn signed_distance(&self, point: Vector3) -> f64 {
+        self.normal.dot(point) + self.distance
+    }
 }
 
-impl Transformable for OrthographicCamera {
-    fn transform(&self) -> &Transform {
-        &self.transform
+// Sutherland-Hodgman clipping of a triangle against every plane, fanned back into triangles
+fn clip_triangle(points: [Vector3; 3], planes: &[ClipPlane]) -> Vec<[Vector3; 3]> {
+    // Skip the polygon work entirely for the common case of a triangle that is inside every plane
+    if planes
+        .iter()
+        .all(|plane| points.iter().all(|&point| plane.signed_distance(point) >= 0.0))
+    {
+        return vec![points];
     }
 
-    fn transform_mut(&mut self) -> &mut Transform {
-        &mut self.transform
+    let mut polygon = points.to_vec();
+    let mut clipped = Vec::with_capacity(polygon.len() + planes.len());
+
+    for plane in planes {
+        clipped.clear();
+
+        for (i, &point) in polygon.iter().enumerate() {
+            let next = polygon[(i + 1) % polygon.len()];
+            let distance = plane.signed_distance(point);
+            let next_distance = plane.signed_distance(next);
+
+            if distance >= 0.0 {
+                clipped.push(point);
+            }
+
+            if (distance >= 0.0) != (next_distance >= 0.0) {
+                let t = distance / (distance - next_distance);
+                clipped.push(point + t * (next - point));
+            }
+        }
+
+        std::mem::swap(&mut polygon, &mut clipped);
+
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
     }
+
+    (1..polygon.len() - 1)
+        .map(|i| [polygon[0], polygon[i], polygon[i + 1]])
+        .collect()
 }
 
 #[derive(Debug)]
@@ -359,18 +528,41 @@ impl<'a, S: Scene, V: Viewport> RasterWidget<'a, S, V> {
     }
 }
 
+// Camera that shows the scene in a terminal area, where each cell is two pixels tall
+fn terminal_camera<V: Viewport>(transform: Transform, area: tui::layout::Rect) -> V {
+    V::new(
+        transform,
+        SCENE_WORLD_UNITS_PER_PIXEL * area.width as f64,
+        SCENE_WORLD_UNITS_PER_PIXEL * area.height as f64 * 2.0,
+    )
+}
+
+// Ray through the middle of a terminal cell from the camera a raster widget in the area draws with, so
+// clicks land on what is drawn under the cursor. None for cells outside the area.
+pub fn cell_ray<V: Viewport>(
+    area: tui::layout::Rect,
+    column: u16,
+    row: u16,
+    transform: &Transform,
+) -> Option<Ray> {
+    if column < area.x || column >= area.x + area.width || row < area.y || row >= area.y + area.height {
+        return None;
+    }
+
+    let camera: V = terminal_camera(transform.clone(), area);
+    Some(camera.screen_ray(
+        ((column - area.x) as f64 + 0.5) / area.width as f64,
+        ((row - area.y) as f64 + 0.5) / area.height as f64,
+    ))
+}
+
 impl<'a, S: Scene, V: Viewport> tui::widgets::Widget for RasterWidget<'a, S, V> {
     fn render(self, area: tui::layout::Rect, buf: &mut tui::buffer::Buffer) {
         if area.area() == 0 {
             return;
         }
 
-        let camera = V::new(
-            self.scene.camera_transform().clone(),
-            SCENE_WORLD_UNITS_PER_PIXEL * area.width as f64,
-            SCENE_WORLD_UNITS_PER_PIXEL * area.height as f64 * 2.0,
-        );
-
+        let camera: V = terminal_camera(self.scene.camera_transform().clone(), area);
         self.raster.rasterize(
             self.scene,
             camera,
@@ -413,14 +605,6 @@ impl<T> Buffer2D<T> {
         self.height = height;
     }
 
-    pub fn get(&self, x: usize, y: usize) -> &T {
-        &self.data[y * self.width + x]
-    }
-
-    pub fn get_mut(&mut self, x: usize, y: usize) -> &mut T {
-        &mut self.data[y * self.width + x]
-    }
-
     pub fn get_range(&self, y: usize, r: RangeInclusive<usize>) -> &[T] {
         &self.data[y * self.width + r.start()..=y * self.width + r.end()]
     }
@@ -439,3 +623,204 @@ impl<T> Buffer2D<T> {
         (y..y + height).map(move |y| self.get_range(y, x..=x + width - 1).iter())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle(points: [Vector3; 3]) -> SceneTriangle {
+        SceneTriangle {
+            points,
+            normal: Vector3::new(0.0, 0.0, -1.0),
+            color: Color::Rgb(255, 255, 255),
+        }
+    }
+
+    #[test]
+    fn orthographic_camera_clips_geometry_behind_the_near_plane() {
+        let camera = OrthographicCamera::new(Transform::default(), 100.0, 100.0);
+        let geometry = [
+            triangle([
+                Vector3::new(-10.0, -10.0, -5.0),
+                Vector3::new(10.0, -10.0, -5.0),
+                Vector3::new(0.0, 10.0, -5.0),
+            ]),
+            triangle([
+                Vector3::new(-10.0, -10.0, -5.0),
+                Vector3::new(10.0, -10.0, -5.0),
+                Vector3::new(0.0, 10.0, 5.0),
+            ]),
+        ];
+
+        let projections: Vec<_> = camera.project_geometry(&geometry).collect();
+        assert!(!projections.is_empty());
+        for (triangle, projection) in projections {
+            assert!(std::ptr::eq(triangle, &geometry[1]));
+            for point in projection.projection_points {
+                assert!(point.z >= camera.near - 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn perspective_camera_clips_geometry_to_the_view_frustum() {
+        let camera = PerspectiveCamera::new(Transform::default(), 100.0, 100.0);
+        let behind = SceneTriangle {
+            normal: Vector3::Z_AXIS,
+            ..triangle([
+                Vector3::new(-10.0, -10.0, -5.0),
+                Vector3::new(0.0, 10.0, -5.0),
+                Vector3::new(10.0, -10.0, -5.0),
+            ])
+        };
+        // Reaches from behind the camera to far past the edges of the view
+        let points = [
+            Vector3::new(-100.0, -100.0, -5.0),
+            Vector3::new(0.0, 100.0, 50.0),
+            Vector3::new(100.0, -100.0, 50.0),
+        ];
+        let crossing = SceneTriangle {
+            normal: (points[1] - points[0]).cross(points[2] - points[0]).unit(),
+            ..triangle(points)
+        };
+        let geometry = [behind, crossing];
+
+        let projections: Vec<_> = camera.project_geometry(&geometry).collect();
+        assert!(!projections.is_empty());
+        for (triangle, projection) in projections {
+            assert!(std::ptr::eq(triangle, &geometry[1]));
+            for point in projection.projection_points {
+                for coordinate in [point.x, point.y, point.z] {
+                    assert!((-1e-9..=1.0 + 1e-9).contains(&coordinate), "{point:?} is outside the view");
+                }
+            }
+        }
+    }
+
+    // Squares facing a camera at the origin, each at its own depth and in its own color
+    struct Squares {
+        camera: Transform,
+        squares: Vec<(f64, f64, Color)>,
+    }
+
+    impl Scene for Squares {
+        fn camera_transform(&self) -> &Transform {
+            &self.camera
+        }
+
+        fn update_geometry(&self, geometry_buffer: &mut Vec<SceneTriangle>) {
+            geometry_buffer.clear();
+            for &(depth, half_size, color) in self.squares.iter() {
+                let corner = |x: f64, y: f64| Vector3::new(x * half_size, y * half_size, depth);
+                let [a, b, c, d] =
+                    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| corner(x, y));
+                for points in [[a, b, c], [c, d, a]] {
+                    geometry_buffer.push(SceneTriangle {
+                        color,
+                        ..triangle(points)
+                    });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn perspective_depth_keeps_the_nearest_triangle_in_front() {
+        let camera = PerspectiveCamera::new(Transform::default(), 40.0, 40.0);
+        let (near, far) = (camera.near, camera.far);
+        let depth = |z: f64| camera.point_to_projection_space(Vector3::new(0.0, 0.0, z)).z;
+        assert!(depth(near).abs() < 1e-12 && (depth(far) - 1.0).abs() < 1e-12);
+        assert!(depth(10.0) < depth(10.5) && depth(1_000.0) < depth(1_001.0));
+
+        // The far square is larger so that it shows around the near one, whichever is drawn first
+        let (red, blue) = (Color::Rgb(255, 0, 0), Color::Rgb(0, 0, 255));
+        let (near_square, far_square) = ((10.0, 2.0, red), (20.0, 10.0, blue));
+        for squares in [vec![near_square, far_square], vec![far_square, near_square]] {
+            let scene = Squares {
+                camera: Transform::default(),
+                squares,
+            };
+            let mut raster = Raster::default();
+            raster.rasterize(&scene, camera.clone(), 40, 40);
+
+            let pixel = |x: usize, y: usize| raster.screen_buffer.data[y * 40 + x];
+            assert_eq!(pixel(20, 20), red);
+            assert_eq!(pixel(20, 8), blue);
+        }
+    }
+
+    // Screen rays must pass back through the points the camera projects onto those screen coordinates
+    fn assert_screen_rays_hit_projected_points(
+        camera: &impl Viewport,
+        transform: &Transform,
+        project: impl Fn(Vector3) -> Vector3,
+    ) {
+        for local in [
+            Vector3::new(0.0, 0.0, 30.0),
+            Vector3::new(-7.0, 4.0, 20.0),
+            Vector3::new(12.0, -9.0, 45.0),
+        ] {
+            let point = transform.point_to_world_space(local);
+            let screen = project(local);
+            let ray = camera.screen_ray(screen.x, screen.y);
+            let offset = point - ray.origin;
+            let miss = offset - ray.direction * offset.dot(ray.direction);
+            assert!(offset.dot(ray.direction) > 0.0);
+            assert!(miss.magnitude() < 1e-9, "ray misses by {}", miss.magnitude());
+        }
+    }
+
+    #[test]
+    fn screen_rays_pass_through_projected_points() {
+        let transform = Transform::new(
+            Vector3::new(3.0, -2.0, 5.0),
+            Quaternion::from_axis_angle(Vector3::new(1.0, 2.0, 0.5), 0.7),
+        );
+
+        let orthographic = OrthographicCamera::new(transform.clone(), 80.0, 60.0);
+        assert_screen_rays_hit_projected_points(&orthographic, &transform, |point| {
+            orthographic.point_to_projection_space(point)
+        });
+
+        let perspective = PerspectiveCamera::new(transform.clone(), 80.0, 60.0);
+        assert_screen_rays_hit_projected_points(&perspective, &transform, |point| {
+            perspective.point_to_projection_space(point)
+        });
+    }
+
+    #[test]
+    fn cell_rays_hit_what_the_raster_widget_draws_in_the_cell() {
+        use tui::{buffer::Buffer, layout::Rect, widgets::Widget};
+
+        let red = Color::Rgb(255, 0, 0);
+        let scene = Squares {
+            camera: Transform::new(Vector3::new(0.0, 0.0, -5.0), Quaternion::default()),
+            squares: vec![(10.0, 5.0, red)],
+        };
+        let area = Rect::new(0, 0, 40, 10);
+        let mut raster = Raster::default();
+        let mut buffer = Buffer::empty(area);
+        RasterWidget::<'_, _, OrthographicCamera>::new(&mut raster, &scene, 1).render(area, &mut buffer);
+
+        // Cells along the edges of the square are skipped, as the ray through the middle of a cell passes
+        // between its two pixels and pixels cut by an edge can go either way
+        let mut checked = 0;
+        for row in 0..area.height {
+            for column in 0..area.width {
+                let ray = cell_ray::<OrthographicCamera>(area, column, row, &scene.camera).unwrap();
+                let hit = ray.at((10.0 - ray.origin.z) / ray.direction.z);
+                let distance_inside = 5.0 - hit.x.abs().max(hit.y.abs());
+                if distance_inside.abs() < 1.5 {
+                    continue;
+                }
+
+                let cell = buffer.get(column, row);
+                let is_red = cell.bg == red && cell.fg == red;
+                assert_eq!(is_red, distance_inside > 0.0, "cell ({column}, {row}) and its ray disagree");
+                checked += 1;
+            }
+        }
+        assert!(checked > 300);
+        assert!(cell_ray::<OrthographicCamera>(area, 40, 0, &scene.camera).is_none());
+    }
+}
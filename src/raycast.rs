@@ -0,0 +1,456 @@
+use crate::{
+    collision::Plane,
+    gjk::{self, GjkResult, SupportMap},
+    raster::SceneTriangle,
+    shape::*,
+    transform::*,
+};
+
+// Rays closer to parallel with a surface than this never hit it
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+// Rays against shapes known only by their support function march towards the surface until they are
+// this close to it, giving up on rays that graze it for too long
+const SUPPORT_HIT_DISTANCE: f64 = 1e-6;
+const SUPPORT_MAX_ITERATIONS: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    // Distance along the ray, zero when the ray starts inside a solid
+    pub distance: f64,
+    pub point: Vector3,
+    // Unit surface normal facing back against the ray
+    pub normal: Vector3,
+}
+
+impl RayHit {
+    fn new(ray: &Ray, distance: f64, normal: Vector3) -> RayHit {
+        RayHit {
+            distance,
+            point: ray.at(distance),
+            normal,
+        }
+    }
+
+    // Hit for a ray that starts inside a solid
+    fn inside(ray: &Ray) -> RayHit {
+        RayHit::new(ray, 0.0, ray.direction.inverse())
+    }
+
+    pub fn to_world_space(self, transform: &Transform) -> RayHit {
+        RayHit {
+            distance: self.distance,
+            point: transform.point_to_world_space(self.point),
+            normal: transform.rotation.vector_to_world_space(self.normal),
+        }
+    }
+}
+
+// Slab test against a box centered on the origin and aligned to the axes
+pub fn ray_box(ray: &Ray, half_size: Vector3, max_distance: f64) -> Option<RayHit> {
+    let origin = [ray.origin.x, ray.origin.y, ray.origin.z];
+    let direction = [ray.direction.x, ray.direction.y, ray.direction.z];
+    let extents = [half_size.x, half_size.y, half_size.z];
+    let axes = [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS];
+
+    let (mut enter, mut exit) = (f64::MIN, f64::MAX);
+    let mut normal = Vector3::default();
+    for i in 0..3 {
+        if direction[i].abs() < PARALLEL_EPSILON {
+            if origin[i].abs() > extents[i] {
+                return None;
+            }
+            continue;
+        }
+
+        let near = (-extents[i] * direction[i].signum() - origin[i]) / direction[i];
+        let far = (extents[i] * direction[i].signum() - origin[i]) / direction[i];
+        if near > enter {
+            enter = near;
+            normal = axes[i] * -direction[i].signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if enter > exit || exit < 0.0 || enter > max_distance {
+        None
+    } else if enter < 0.0 {
+        Some(RayHit::inside(ray))
+    } else {
+        Some(RayHit::new(ray, enter, normal))
+    }
+}
+
+pub fn ray_sphere(ray: &Ray, center: Vector3, radius: f64, max_distance: f64) -> Option<RayHit> {
+    let offset = ray.origin - center;
+    let c = offset.dot(offset) - radius * radius;
+    if c <= 0.0 {
+        return Some(RayHit::inside(ray));
+    }
+
+    let b = offset.dot(ray.direction);
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let distance = -b - discriminant.sqrt();
+    (distance <= max_distance).then(|| {
+        let hit = RayHit::new(ray, distance, Vector3::default());
+        RayHit {
+            normal: (hit.point - center) * (1.0 / radius),
+            ..hit
+        }
+    })
+}
+
+// Capsule as the union of the spheres at both ends of its core and the cylinder between them
+pub fn ray_capsule(
+    ray: &Ray,
+    start: Vector3,
+    end: Vector3,
+    radius: f64,
+    max_distance: f64,
+) -> Option<RayHit> {
+    let core = end - start;
+    let length = core.magnitude();
+    if length <= 0.0 {
+        return ray_sphere(ray, start, radius, max_distance);
+    }
+
+    let axis = core * (1.0 / length);
+    let offset = ray.origin - start;
+    let along = offset.dot(axis).clamp(0.0, length);
+    if (offset - axis * along).magnitude() <= radius {
+        return Some(RayHit::inside(ray));
+    }
+
+    // Cylinder around the core, solved in the plane perpendicular to it
+    let perpendicular_direction = ray.direction - axis * ray.direction.dot(axis);
+    let perpendicular_offset = offset - axis * offset.dot(axis);
+    let a = perpendicular_direction.dot(perpendicular_direction);
+    let b = perpendicular_offset.dot(perpendicular_direction);
+    let c = perpendicular_offset.dot(perpendicular_offset) - radius * radius;
+    let discriminant = b * b - a * c;
+
+    let cylinder_hit = (a > PARALLEL_EPSILON && discriminant >= 0.0)
+        .then(|| (-b - discriminant.sqrt()) / a)
+        .filter(|&distance| {
+            let along = (offset + ray.direction * distance).dot(axis);
+            (0.0..=max_distance).contains(&distance) && (0.0..=length).contains(&along)
+        })
+        .map(|distance| {
+            RayHit::new(
+                ray,
+                distance,
+                (perpendicular_offset + perpendicular_direction * distance) * (1.0 / radius),
+            )
+        });
+
+    [
+        cylinder_hit,
+        ray_sphere(ray, start, radius, max_distance),
+        ray_sphere(ray, end, radius, max_distance),
+    ]
+    .into_iter()
+    .flatten()
+    .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+// Clips the ray against every face plane of a hull in its own space
+pub fn ray_convex_hull(ray: &Ray, hull: &ConvexHull, max_distance: f64) -> Option<RayHit> {
+    let (mut enter, mut exit) = (f64::MIN, f64::MAX);
+    let mut normal = Vector3::default();
+    for face in hull.faces.iter() {
+        let distance = face.normal.dot(ray.origin - hull.vertices[face.vertices[0]]);
+        let approach = face.normal.dot(ray.direction);
+        if approach.abs() < PARALLEL_EPSILON {
+            if distance > 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = -distance / approach;
+        if approach < 0.0 {
+            if t > enter {
+                enter = t;
+                normal = face.normal;
+            }
+        } else {
+            exit = exit.min(t);
+        }
+    }
+
+    if enter > exit || exit < 0.0 || enter > max_distance {
+        None
+    } else if enter < 0.0 {
+        Some(RayHit::inside(ray))
+    } else {
+        Some(RayHit::new(ray, enter, normal))
+    }
+}
+
+// Ray against a shape in its own space
+pub fn ray_shape(ray: &Ray, shape: &Shape, max_distance: f64) -> Option<RayHit> {
+    match shape {
+        Shape::Box { half_size } => ray_box(ray, *half_size, max_distance),
+        Shape::Sphere { radius } => ray_sphere(ray, Vector3::default(), *radius, max_distance),
+        Shape::Capsule { radius, half_height } => {
+            let half_core = Vector3::Y_AXIS * *half_height;
+            ray_capsule(ray, half_core.inverse(), half_core, *radius, max_distance)
+        }
+        Shape::ConvexHull(hull) => ray_convex_hull(ray, hull, max_distance),
+        Shape::Custom(custom) => ray_support_map(ray, &**custom, max_distance),
+    }
+}
+
+// Conservative advancement for any convex shape: GJK finds the closest point of the shape to the current
+// point on the ray, and the ray can safely move on by the distance to it divided by how fast the ray
+// closes in on it. The ray misses once it stops closing in before reaching the surface.
+pub fn ray_support_map(ray: &Ray, shape: &(impl SupportMap + ?Sized), max_distance: f64) -> Option<RayHit> {
+    let mut distance = 0.0;
+    for _ in 0..SUPPORT_MAX_ITERATIONS {
+        let point = ray.at(distance);
+        let GjkResult::Separated { point_b: closest, .. } = gjk::gjk(&point, shape) else {
+            return Some(if distance == 0.0 {
+                RayHit::inside(ray)
+            } else {
+                RayHit::new(ray, distance, ray.direction.inverse())
+            });
+        };
+
+        let offset = point - closest;
+        let gap = offset.magnitude();
+        let normal = offset * (1.0 / gap);
+        if gap <= SUPPORT_HIT_DISTANCE {
+            return Some(RayHit::new(ray, distance, normal));
+        }
+
+        let closing_speed = -normal.dot(ray.direction);
+        if closing_speed <= 0.0 {
+            return None;
+        }
+        distance += gap / closing_speed;
+        if distance > max_distance {
+            return None;
+        }
+    }
+    None
+}
+
+// Möller-Trumbore intersection, hitting the triangle from either side
+pub fn ray_triangle(ray: &Ray, [a, b, c]: [Vector3; 3], max_distance: f64) -> Option<RayHit> {
+    let (ab, ac) = (b - a, c - a);
+    let p = ray.direction.cross(ac);
+    let determinant = ab.dot(p);
+    if determinant.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let offset = ray.origin - a;
+    let u = offset.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = offset.cross(ab);
+    let v = ray.direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = ac.dot(q) * inverse_determinant;
+    if !(0.0..=max_distance).contains(&distance) {
+        return None;
+    }
+
+    let normal = ab.cross(ac).unit();
+    let normal = if normal.dot(ray.direction) > 0.0 { normal.inverse() } else { normal };
+    Some(RayHit::new(ray, distance, normal))
+}
+
+// Closest hit against a buffer of scene triangles along with the index of the triangle hit
+pub fn raycast_triangles(
+    ray: &Ray,
+    triangles: &[SceneTriangle],
+    max_distance: f64,
+) -> Option<(usize, RayHit)> {
+    let mut closest: Option<(usize, RayHit)> = None;
+    for (index, triangle) in triangles.iter().enumerate() {
+        let max_distance = closest.map_or(max_distance, |(_, hit)| hit.distance);
+        if let Some(hit) = ray_triangle(ray, triangle.points, max_distance) {
+            closest = Some((index, hit));
+        }
+    }
+
+    closest
+}
+
+// Planes are solid below their normal, so a ray starting below one hits it immediately
+pub fn ray_plane(ray: &Ray, plane: &Plane, max_distance: f64) -> Option<RayHit> {
+    let distance = plane.signed_distance(ray.origin);
+    if distance <= 0.0 {
+        return Some(RayHit::inside(ray));
+    }
+
+    let approach = plane.normal.dot(ray.direction);
+    if approach > -PARALLEL_EPSILON {
+        return None;
+    }
+
+    let t = -distance / approach;
+    (t <= max_distance).then(|| RayHit::new(ray, t, plane.normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::body::Body;
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-4, "{actual} is not {expected}");
+    }
+
+    fn assert_vector_near(actual: Vector3, expected: Vector3) {
+        assert!((actual - expected).magnitude() < 1e-4, "{actual:?} is not {expected:?}");
+    }
+
+    #[test]
+    fn box_slabs() {
+        let half_size = Vector3::new(1.0, 2.0, 3.0);
+
+        let hit = ray_box(&Ray::new(Vector3::new(-5.0, 0.5, 0.5), Vector3::X_AXIS), half_size, 10.0)
+            .expect("ray along x through the box should hit it");
+        assert_near(hit.distance, 4.0);
+        assert_vector_near(hit.normal, Vector3::X_AXIS.inverse());
+        assert_vector_near(hit.point, Vector3::new(-1.0, 0.5, 0.5));
+
+        let diagonal = Ray::new(Vector3::new(0.0, 5.0, 6.0), Vector3::new(0.0, -1.0, -1.0));
+        let hit = ray_box(&diagonal, half_size, 10.0).expect("diagonal ray should hit the top face");
+        assert_near(hit.distance, 3.0 * 2.0_f64.sqrt());
+        assert_vector_near(hit.normal, Vector3::Y_AXIS);
+
+        // Parallel to the x slab but outside of it, too short, and pointing away
+        assert!(ray_box(&Ray::new(Vector3::new(2.0, -5.0, 0.0), Vector3::Y_AXIS), half_size, 10.0).is_none());
+        assert!(ray_box(&Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::X_AXIS), half_size, 3.0).is_none());
+        let away = Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::X_AXIS.inverse());
+        assert!(ray_box(&away, half_size, 10.0).is_none());
+
+        let inside = ray_box(&Ray::new(Vector3::default(), Vector3::Z_AXIS), half_size, 10.0)
+            .expect("ray from inside the box should hit it");
+        assert_eq!(inside.distance, 0.0);
+    }
+
+    #[test]
+    fn spheres() {
+        let center = Vector3::new(0.0, 0.0, 10.0);
+
+        let through = Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::Z_AXIS);
+        let hit = ray_sphere(&through, center, 2.0, 100.0).expect("ray through the sphere should hit it");
+        assert_near(hit.distance, 10.0 - 3.0_f64.sqrt());
+        assert_vector_near(hit.normal, Vector3::new(0.0, 0.5, -0.75_f64.sqrt()));
+
+        let beside = Ray::new(Vector3::new(0.0, 2.5, 0.0), Vector3::Z_AXIS);
+        assert!(ray_sphere(&beside, center, 2.0, 100.0).is_none());
+        let away = Ray::new(Vector3::default(), Vector3::Z_AXIS.inverse());
+        assert!(ray_sphere(&away, center, 2.0, 100.0).is_none());
+        assert!(ray_sphere(&Ray::new(Vector3::default(), Vector3::Z_AXIS), center, 2.0, 7.0).is_none());
+
+        let inside = ray_sphere(&Ray::new(center, Vector3::X_AXIS), center, 2.0, 100.0)
+            .expect("ray from inside the sphere should hit it");
+        assert_eq!(inside.distance, 0.0);
+    }
+
+    #[test]
+    fn capsules() {
+        let (start, end) = (Vector3::new(0.0, -3.0, 0.0), Vector3::new(0.0, 3.0, 0.0));
+
+        // Side of the cylinder between the ends of the core
+        let side = Ray::new(Vector3::new(-10.0, 1.0, 0.0), Vector3::X_AXIS);
+        let hit = ray_capsule(&side, start, end, 2.0, 100.0)
+            .expect("ray at the side of the capsule should hit it");
+        assert_near(hit.distance, 8.0);
+        assert_vector_near(hit.normal, Vector3::X_AXIS.inverse());
+
+        // Rounded cap at the top end of the core
+        let top = Ray::new(Vector3::new(0.0, 10.0, 1.0), Vector3::Y_AXIS.inverse());
+        let hit = ray_capsule(&top, start, end, 2.0, 100.0)
+            .expect("ray at the top of the capsule should hit it");
+        assert_near(hit.distance, 7.0 - 3.0_f64.sqrt());
+        assert_vector_near(hit.normal, Vector3::new(0.0, 3.0_f64.sqrt() / 2.0, 0.5));
+
+        // Past the cap, beside the cylinder, and short of the surface
+        let past = Ray::new(Vector3::new(-10.0, 5.5, 0.0), Vector3::X_AXIS);
+        assert!(ray_capsule(&past, start, end, 2.0, 100.0).is_none());
+        let beside = Ray::new(Vector3::new(-10.0, 0.0, 2.5), Vector3::X_AXIS);
+        assert!(ray_capsule(&beside, start, end, 2.0, 100.0).is_none());
+        let short = Ray::new(Vector3::new(-10.0, 0.0, 0.0), Vector3::X_AXIS);
+        assert!(ray_capsule(&short, start, end, 2.0, 7.0).is_none());
+
+        let inside = Ray::new(Vector3::new(0.0, 4.0, 0.0), Vector3::X_AXIS);
+        let inside = ray_capsule(&inside, start, end, 2.0, 100.0)
+            .expect("ray from inside the capsule should hit it");
+        assert_eq!(inside.distance, 0.0);
+    }
+
+    #[test]
+    fn triangles() {
+        let triangle = [
+            Vector3::new(0.0, 0.0, 5.0),
+            Vector3::new(2.0, 0.0, 5.0),
+            Vector3::new(0.0, 2.0, 5.0),
+        ];
+
+        let hit = ray_triangle(&Ray::new(Vector3::new(0.5, 0.5, 0.0), Vector3::Z_AXIS), triangle, 10.0)
+            .expect("ray through the triangle should hit it");
+        assert_near(hit.distance, 5.0);
+        assert_vector_near(hit.normal, Vector3::new(0.0, 0.0, -1.0));
+
+        // From behind, the normal still faces back along the ray
+        let behind = Ray::new(Vector3::new(0.5, 0.5, 10.0), Vector3::Z_AXIS.inverse());
+        let hit = ray_triangle(&behind, triangle, 10.0)
+            .expect("ray through the back of the triangle should hit it");
+        assert_near(hit.distance, 5.0);
+        assert_vector_near(hit.normal, Vector3::Z_AXIS);
+
+        // Outside of the edges, parallel to the triangle, and short of it
+        let misses = [
+            (Ray::new(Vector3::new(1.5, 1.5, 0.0), Vector3::Z_AXIS), 10.0),
+            (Ray::new(Vector3::new(-0.5, 0.5, 0.0), Vector3::Z_AXIS), 10.0),
+            (Ray::new(Vector3::new(0.0, 0.0, 5.0), Vector3::X_AXIS), 10.0),
+            (Ray::new(Vector3::new(0.5, 0.5, 0.0), Vector3::Z_AXIS), 4.0),
+        ];
+        for (ray, max_distance) in misses {
+            assert!(ray_triangle(&ray, triangle, max_distance).is_none());
+        }
+    }
+
+    #[test]
+    fn support_map_rays_march_onto_the_surface() {
+        let sphere = Body::new(
+            Transform::new(Vector3::new(0.0, 0.0, 10.0), Quaternion::default()),
+            Shape::Sphere { radius: 2.0 },
+            1.0,
+        );
+
+        let hit = ray_support_map(&Ray::new(Vector3::default(), Vector3::Z_AXIS), &sphere, 100.0)
+            .expect("ray straight at the sphere should hit it");
+        assert_near(hit.distance, 8.0);
+        assert_near(hit.normal.z, -1.0);
+
+        let offset = Ray::new(Vector3::new(1.0, 0.0, 0.0), Vector3::Z_AXIS);
+        let hit = ray_support_map(&offset, &sphere, 100.0).expect("ray through the sphere should hit it");
+        assert_near(hit.distance, 10.0 - 3.0_f64.sqrt());
+
+        let beside = Ray::new(Vector3::new(3.0, 0.0, 0.0), Vector3::Z_AXIS);
+        assert!(ray_support_map(&beside, &sphere, 100.0).is_none());
+        assert!(ray_support_map(&Ray::new(Vector3::default(), Vector3::Z_AXIS), &sphere, 5.0).is_none());
+
+        let inside = ray_support_map(&Ray::new(Vector3::new(0.0, 0.0, 10.0), Vector3::X_AXIS), &sphere, 100.0)
+            .expect("ray from inside the sphere should hit it");
+        assert_eq!(inside.distance, 0.0);
+    }
+}
@@ -0,0 +1,489 @@
+use crate::{
+    gjk::{self, SupportMap},
+    mesh::Mesh,
+    raster::SceneTriangle,
+    transform::*,
+};
+
+use tui::style::Color;
+
+use std::{f64::consts::PI, fmt, rc::Rc};
+
+// Tessellation of spheres and capsules for rendering
+const ROUND_SEGMENTS: usize = 16;
+const ROUND_RINGS: usize = 8;
+
+// Relative tolerance for deciding whether a point lies outside a hull face while building the hull
+const HULL_EPSILON: f64 = 1e-9;
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Box { half_size: Vector3 },
+    Sphere { radius: f64 },
+    // Capsule whose core segment runs along the local y axis from -half_height to half_height
+    Capsule { radius: f64, half_height: f64 },
+    ConvexHull(ConvexHull),
+    Custom(Rc<dyn CustomShape>),
+}
+
+// Convex shape defined outside of the engine by its support function, whose points are in body space,
+// for anything the other shapes can't describe. It collides with every other shape through GJK and EPA.
+pub trait CustomShape: SupportMap + fmt::Debug {
+    // Distance from the origin to the furthest point of the shape
+    fn bounding_radius(&self) -> f64;
+    fn volume(&self) -> f64;
+    // Body space inertia tensor for a mass of one
+    fn unit_inertia(&self) -> Matrix3;
+    // Body space mesh for rendering
+    fn mesh(&self) -> Mesh;
+}
+
+impl Default for Shape {
+    fn default() -> Shape {
+        Shape::Box {
+            half_size: Vector3::default(),
+        }
+    }
+}
+
+impl Shape {
+    // Point of the shape furthest along a local space direction
+    pub fn support(&self, direction: Vector3) -> Vector3 {
+        let sign = |value: f64| if value < 0.0 { -1.0 } else { 1.0 };
+        match self {
+            Shape::Box { half_size } => Vector3::new(
+                sign(direction.x) * half_size.x,
+                sign(direction.y) * half_size.y,
+                sign(direction.z) * half_size.z,
+            ),
+            Shape::Sphere { radius } => unit_or_x_axis(direction) * *radius,
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => {
+                Vector3::new(0.0, sign(direction.y) * half_height, 0.0)
+                    + unit_or_x_axis(direction) * *radius
+            }
+            Shape::ConvexHull(hull) => hull.support(direction),
+            Shape::Custom(custom) => custom.support(direction),
+        }
+    }
+
+    // Distance from the origin to the furthest point of the shape
+    pub fn bounding_radius(&self) -> f64 {
+        match self {
+            Shape::Box { half_size } => half_size.magnitude(),
+            Shape::Sphere { radius } => *radius,
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => radius + half_height,
+            Shape::ConvexHull(hull) => hull
+                .vertices
+                .iter()
+                .fold(0.0, |radius, vertex| vertex.magnitude().max(radius)),
+            Shape::Custom(custom) => custom.bounding_radius(),
+        }
+    }
+
+    pub fn volume(&self) -> f64 {
+        match self {
+            Shape::Box { half_size } => 8.0 * half_size.x * half_size.y * half_size.z,
+            Shape::Sphere { radius } => 4.0 / 3.0 * PI * radius.powi(3),
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => PI * radius * radius * (2.0 * half_height + 4.0 / 3.0 * radius),
+            Shape::ConvexHull(hull) => hull.volume,
+            Shape::Custom(custom) => custom.volume(),
+        }
+    }
+
+    // Whether a body space point is inside the shape or on its surface
+    pub fn contains_point(&self, point: Vector3) -> bool {
+        match self {
+            Shape::Box { half_size } => {
+                point.x.abs() <= half_size.x && point.y.abs() <= half_size.y && point.z.abs() <= half_size.z
+            }
+            Shape::Sphere { radius } => point.magnitude() <= *radius,
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => {
+                let core = Vector3::new(0.0, point.y.clamp(-half_height, *half_height), 0.0);
+                (point - core).magnitude() <= *radius
+            }
+            Shape::ConvexHull(hull) => hull
+                .faces
+                .iter()
+                .all(|face| (point - hull.vertices[face.vertices[0]]).dot(face.normal) <= 0.0),
+            Shape::Custom(custom) => gjk::intersects(custom.as_ref(), &point),
+        }
+    }
+
+    // Body space inertia tensor of the shape as a solid of uniform density with the given mass
+    pub fn inertia(&self, mass: f64) -> Matrix3 {
+        match self {
+            Shape::Box { half_size } => {
+                let Vector3 { x, y, z } = *half_size;
+                Matrix3::from_diagonal(
+                    Vector3::new(y * y + z * z, x * x + z * z, x * x + y * y) * (mass / 3.0),
+                )
+            }
+            Shape::Sphere { radius } => {
+                let inertia = 0.4 * mass * radius * radius;
+                Matrix3::from_diagonal(Vector3::new(inertia, inertia, inertia))
+            }
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => {
+                // Mass is split between the cylinder and the two hemispheres by volume, with the
+                // hemispheres offset from the center by the parallel axis theorem
+                let (r, h) = (*radius, 2.0 * half_height);
+                let cylinder_volume = PI * r * r * h;
+                let sphere_volume = 4.0 / 3.0 * PI * r * r * r;
+                let cylinder_mass = mass * cylinder_volume / (cylinder_volume + sphere_volume);
+                let sphere_mass = mass - cylinder_mass;
+
+                let axial = 0.5 * cylinder_mass * r * r + 0.4 * sphere_mass * r * r;
+                let transverse = cylinder_mass * (r * r / 4.0 + h * h / 12.0)
+                    + sphere_mass * (0.4 * r * r + h * h / 4.0 + 3.0 * h * r / 8.0);
+                Matrix3::from_diagonal(Vector3::new(transverse, axial, transverse))
+            }
+            Shape::ConvexHull(hull) => hull.unit_inertia * mass,
+            Shape::Custom(custom) => custom.unit_inertia() * mass,
+        }
+    }
+
+    // Local space mesh for rendering the shape
+    pub fn mesh(&self) -> Mesh {
+        match self {
+            Shape::Box { half_size } => Mesh::cuboid(*half_size),
+            Shape::Sphere { radius } => Mesh::uv_sphere(*radius, ROUND_SEGMENTS, ROUND_RINGS),
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => Mesh::capsule(*radius, *half_height, ROUND_SEGMENTS, ROUND_RINGS),
+            Shape::ConvexHull(hull) => Mesh::from_triangles(&hull.geometry()),
+            Shape::Custom(custom) => custom.mesh(),
+        }
+    }
+}
+
+fn unit_or_x_axis(direction: Vector3) -> Vector3 {
+    let magnitude = direction.magnitude();
+    if magnitude > 0.0 {
+        direction * (1.0 / magnitude)
+    } else {
+        Vector3::X_AXIS
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HullFace {
+    pub normal: Vector3,
+    // Indices of the face vertices wound counter-clockwise about the normal
+    pub vertices: Vec<usize>,
+}
+
+// Convex polyhedron in body space, recentered so its center of mass is at the origin
+#[derive(Clone, Debug)]
+pub struct ConvexHull {
+    pub vertices: Vec<Vector3>,
+    pub faces: Vec<HullFace>,
+    // Undirected edges as pairs of vertex indices
+    pub edges: Vec<(usize, usize)>,
+    pub volume: f64,
+    // Inertia tensor for a mass of one
+    unit_inertia: Matrix3,
+}
+
+impl ConvexHull {
+    // Builds the convex hull of a point cloud with an incremental algorithm. Returns None when the points
+    // don't span a volume. The hull is shifted so its center of mass is at the origin; the offset that
+    // was applied is returned alongside it so callers can keep the hull in place.
+    pub fn new(points: &[Vector3]) -> Option<(ConvexHull, Vector3)> {
+        let triangles = hull_triangles(points)?;
+
+        // Volume, center of mass and covariance from the tetrahedra between each face and the origin
+        let mut volume = 0.0;
+        let mut weighted_centroid = Vector3::default();
+        for &[a, b, c] in triangles.iter() {
+            let (a, b, c) = (points[a], points[b], points[c]);
+            let tetrahedron_volume = a.dot(b.cross(c)) / 6.0;
+            volume += tetrahedron_volume;
+            weighted_centroid += (a + b + c) * (tetrahedron_volume / 4.0);
+        }
+
+        if volume <= 0.0 {
+            return None;
+        }
+
+        let center_of_mass = weighted_centroid * (1.0 / volume);
+
+        // Keep only the points on the hull, renumbered and moved to the center of mass
+        let mut remap = vec![usize::MAX; points.len()];
+        let mut vertices = Vec::new();
+        for &index in triangles.iter().flatten() {
+            if remap[index] == usize::MAX {
+                remap[index] = vertices.len();
+                vertices.push(points[index] - center_of_mass);
+            }
+        }
+        let triangles: Vec<[usize; 3]> = triangles
+            .into_iter()
+            .map(|triangle| triangle.map(|index| remap[index]))
+            .collect();
+
+        let mut covariance = Matrix3::ZERO;
+        for &[a, b, c] in triangles.iter() {
+            let (a, b, c) = (vertices[a], vertices[b], vertices[c]);
+            let determinant = a.dot(b.cross(c));
+            let sum = a + b + c;
+            covariance = covariance
+                + (outer_product(a, a) + outer_product(b, b) + outer_product(c, c) + outer_product(sum, sum))
+                    * (determinant / 120.0);
+        }
+
+        // Inertia is trace(C) * I - C, divided by the volume so that it is for a mass of one
+        let trace = covariance.rows[0].x + covariance.rows[1].y + covariance.rows[2].z;
+        let unit_inertia =
+            (Matrix3::from_diagonal(Vector3::new(trace, trace, trace)) + covariance * -1.0) * (1.0 / volume);
+
+        let faces = merge_coplanar_triangles(&vertices, &triangles);
+        let mut edges = Vec::new();
+        for face in faces.iter() {
+            for (i, &a) in face.vertices.iter().enumerate() {
+                let b = face.vertices[(i + 1) % face.vertices.len()];
+                if a < b {
+                    edges.push((a, b));
+                }
+            }
+        }
+
+        Some((
+            ConvexHull {
+                vertices,
+                faces,
+                edges,
+                volume,
+                unit_inertia,
+            },
+            center_of_mass,
+        ))
+    }
+
+    pub fn support(&self, direction: Vector3) -> Vector3 {
+        self.vertices
+            .iter()
+            .copied()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap_or_default()
+    }
+
+    pub fn geometry(&self) -> Vec<SceneTriangle> {
+        self.faces
+            .iter()
+            .flat_map(|face| {
+                (1..face.vertices.len() - 1).map(move |i| SceneTriangle {
+                    normal: face.normal,
+                    points: [
+                        self.vertices[face.vertices[0]],
+                        self.vertices[face.vertices[i]],
+                        self.vertices[face.vertices[i + 1]],
+                    ],
+                    color: Color::Rgb(255, 255, 255),
+                })
+            })
+            .collect()
+    }
+}
+
+fn outer_product(a: Vector3, b: Vector3) -> Matrix3 {
+    Matrix3::new(b * a.x, b * a.y, b * a.z)
+}
+
+// Outward wound triangles of the convex hull of the points, added one point at a time by replacing
+// the faces the point can see with a fan of faces to the horizon around them
+fn hull_triangles(points: &[Vector3]) -> Option<Vec<[usize; 3]>> {
+    if points.len() < 4 {
+        return None;
+    }
+
+    let scale = points
+        .iter()
+        .fold(0.0_f64, |scale, point| scale.max(point.x.abs()).max(point.y.abs()).max(point.z.abs()));
+    let epsilon = HULL_EPSILON * scale.max(1.0);
+
+    // Seed with a tetrahedron of extreme points
+    let furthest = |from: &dyn Fn(Vector3) -> f64| {
+        (0..points.len())
+            .max_by(|&i, &j| from(points[i]).total_cmp(&from(points[j])))
+            .unwrap()
+    };
+    let p0 = furthest(&|point| -point.x);
+    let p1 = furthest(&|point| (point - points[p0]).magnitude());
+    let line = (points[p1] - points[p0]).unit();
+    let p2 = furthest(&|point| (point - points[p0]).cross(line).magnitude());
+    let plane_normal = (points[p1] - points[p0]).cross(points[p2] - points[p0]).unit();
+    let p3 = furthest(&|point| (point - points[p0]).dot(plane_normal).abs());
+
+    if (points[p3] - points[p0]).dot(plane_normal).abs() <= epsilon
+        || (points[p2] - points[p0]).cross(line).magnitude() <= epsilon
+    {
+        return None;
+    }
+
+    let mut faces = if (points[p3] - points[p0]).dot(plane_normal) > 0.0 {
+        vec![[p0, p2, p1], [p0, p1, p3], [p1, p2, p3], [p2, p0, p3]]
+    } else {
+        vec![[p0, p1, p2], [p0, p3, p1], [p1, p3, p2], [p2, p3, p0]]
+    };
+
+    let face_distance = |face: &[usize; 3], point: Vector3| {
+        let [a, b, c] = face.map(|index| points[index]);
+        (b - a).cross(c - a).unit().dot(point - a)
+    };
+
+    for (index, &point) in points.iter().enumerate() {
+        if [p0, p1, p2, p3].contains(&index) {
+            continue;
+        }
+
+        let (visible, hidden): (Vec<[usize; 3]>, Vec<[usize; 3]>) = faces
+            .iter()
+            .partition(|face| face_distance(face, point) > epsilon);
+        if visible.is_empty() {
+            continue;
+        }
+
+        // Horizon edges are the edges of visible faces whose reverse isn't also on a visible face
+        let visible_edges: Vec<(usize, usize)> = visible
+            .iter()
+            .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+            .collect();
+
+        faces = hidden;
+        for &(a, b) in visible_edges.iter() {
+            if !visible_edges.contains(&(b, a)) {
+                faces.push([a, b, index]);
+            }
+        }
+    }
+
+    Some(faces)
+}
+
+// Groups hull triangles that share a plane into polygon faces
+fn merge_coplanar_triangles(vertices: &[Vector3], triangles: &[[usize; 3]]) -> Vec<HullFace> {
+    let mut faces: Vec<HullFace> = Vec::new();
+
+    for &[a, b, c] in triangles {
+        let normal = (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]);
+        if normal.magnitude() < f64::EPSILON {
+            continue;
+        }
+        let normal = normal.unit();
+
+        // A convex polyhedron has at most one face with a given outward normal
+        match faces
+            .iter_mut()
+            .find(|face| face.normal.dot(normal) > 1.0 - 1e-6)
+        {
+            Some(face) => {
+                for index in [a, b, c] {
+                    if !face.vertices.contains(&index) {
+                        face.vertices.push(index);
+                    }
+                }
+            }
+            None => faces.push(HullFace {
+                normal,
+                vertices: vec![a, b, c],
+            }),
+        }
+    }
+
+    // Order the vertices of each face by angle around its centroid
+    for face in faces.iter_mut() {
+        let centroid = face
+            .vertices
+            .iter()
+            .fold(Vector3::default(), |sum, &index| sum + vertices[index])
+            * (1.0 / face.vertices.len() as f64);
+        let u = (vertices[face.vertices[0]] - centroid).unit();
+        let v = face.normal.cross(u);
+        face.vertices.sort_by(|&i, &j| {
+            let angle = |index: usize| {
+                let offset = vertices[index] - centroid;
+                offset.dot(v).atan2(offset.dot(u))
+            };
+            angle(i).total_cmp(&angle(j))
+        });
+    }
+
+    faces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix_near(actual: Matrix3, expected: Matrix3) {
+        for (actual, expected) in actual.rows.iter().zip(expected.rows.iter()) {
+            assert!((*actual - *expected).magnitude() < 1e-9, "{actual:?} instead of {expected:?}");
+        }
+    }
+
+    #[test]
+    fn hulls_of_boxes_weigh_and_turn_like_boxes() {
+        // Corners of a box away from the origin, with points inside it that aren't on the hull
+        let (center, half_size) = (Vector3::new(3.0, -1.0, 2.0), Vector3::new(1.0, 2.0, 0.5));
+        let mut points = vec![center, center + half_size * 0.5];
+        for i in 0..8 {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            points.push(center + Vector3::new(sign(1), sign(2), sign(4)) * half_size);
+        }
+
+        let (hull, offset) = ConvexHull::new(&points).expect("the corners of a box span a volume");
+        assert!((offset - center).magnitude() < 1e-9);
+        assert_eq!((hull.vertices.len(), hull.faces.len(), hull.edges.len()), (8, 6, 12));
+
+        let cuboid = Shape::Box { half_size };
+        let hull = Shape::ConvexHull(hull);
+        assert!((hull.volume() - cuboid.volume()).abs() < 1e-9);
+        assert_matrix_near(hull.inertia(3.0), cuboid.inertia(3.0));
+    }
+
+    #[test]
+    fn hulls_of_octahedra_have_their_volume_and_inertia() {
+        // Points with |x| + |y| + |z| = a make a solid of volume 4a^3/3 whose squared distance from each
+        // coordinate plane averages a^2/10, so the inertia about each axis is 2a^2/10 per unit of mass
+        let a = 2.0;
+        let rotation = Quaternion::from_axis_angle(Vector3::new(1.0, 2.0, 3.0), 0.8);
+        let points = [Vector3::X_AXIS, Vector3::Y_AXIS, Vector3::Z_AXIS]
+            .into_iter()
+            .flat_map(|axis| [axis * a, axis * -a])
+            .map(|point| rotation.vector_to_world_space(point))
+            .collect::<Vec<_>>();
+
+        let (hull, offset) = ConvexHull::new(&points).expect("an octahedron spans a volume");
+        assert!(offset.magnitude() < 1e-9);
+        assert_eq!((hull.vertices.len(), hull.faces.len(), hull.edges.len()), (6, 8, 12));
+
+        let hull = Shape::ConvexHull(hull);
+        assert!((hull.volume() - 4.0 / 3.0 * a.powi(3)).abs() < 1e-9);
+        let inertia = 0.2 * a * a * 5.0;
+        let expected = Matrix3::from_diagonal(Vector3::new(inertia, inertia, inertia));
+        assert_matrix_near(hull.inertia(5.0), expected);
+    }
+
+    #[test]
+    fn flat_point_clouds_have_no_hull() {
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.5, 0.5)];
+        let points: Vec<Vector3> = square.iter().map(|&(x, z)| Vector3::new(x, 2.0, z)).collect();
+        assert!(ConvexHull::new(&points).is_none());
+        assert!(ConvexHull::new(&points[..3]).is_none());
+    }
+}
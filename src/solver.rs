@@ -0,0 +1,495 @@
+use crate::{body::Body, body_set::BodySet, collision::ContactManifold, joint::Joint, transform::*};
+
+use std::collections::HashMap;
+
+// Furthest a contact point can move between sub-steps and still start from the impulses of the point it
+// was, and the least cosine between the old and new normal of a contact for any of its points to
+const WARM_START_DISTANCE: f64 = 1.0;
+const WARM_START_NORMAL_COSINE: f64 = 0.99;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SolverSettings {
+    pub iterations: usize,
+    // Fraction of the penetration that is corrected each step (Baumgarte stabilization)
+    pub baumgarte: f64,
+    // Penetration that is allowed to remain so resting contacts don't jitter in and out of contact
+    pub penetration_slop: f64,
+    // Closing speed below which contacts don't bounce, so resting bodies settle instead of vibrating
+    pub restitution_threshold: f64,
+}
+
+impl Default for SolverSettings {
+    fn default() -> Self {
+        SolverSettings {
+            iterations: 10,
+            baumgarte: 0.2,
+            penetration_slop: 0.1,
+            restitution_threshold: 10.0,
+        }
+    }
+}
+
+// Manifold between two bodies, referenced by their index in the body slice given to the solver. The
+// second body is None when the first body touches static world geometry.
+#[derive(Clone, Debug)]
+pub struct Contact {
+    pub body1: usize,
+    pub body2: Option<usize>,
+    pub manifold: ContactManifold,
+    pub restitution: f64,
+    pub friction: f64,
+    // Total normal impulse the solver applied over the points of the manifold, filled in by the solve
+    pub normal_impulse: f64,
+    // Impulses applied at each point of the manifold, which the solve starts from and fills in. Empty
+    // for a new contact.
+    pub point_impulses: Vec<PointImpulse>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PointImpulse {
+    pub normal: f64,
+    pub tangent: [f64; 2],
+}
+
+// Bounciness of a contact is that of the bouncier surface, friction is the geometric mean
+pub fn combine_materials(restitution1: f64, friction1: f64, restitution2: f64, friction2: f64) -> (f64, f64) {
+    (restitution1.max(restitution2), (friction1 * friction2).sqrt())
+}
+
+// Scalar velocity constraint on two bodies, used to build joints. The constraint velocity is
+// linear.(v2 - v1) + angular2.w2 - angular1.w1 and the solver drives it towards the bias, with the
+// accumulated impulse clamped to a range. The second body is None for static world geometry.
+#[derive(Clone, Copy, Debug)]
+pub struct ConstraintRow {
+    pub body1: usize,
+    pub body2: Option<usize>,
+    pub linear: Vector3,
+    pub angular1: Vector3,
+    pub angular2: Vector3,
+    pub bias: f64,
+    // Inverse stiffness of a soft constraint, zero for rigid constraints
+    pub softness: f64,
+    pub min_impulse: f64,
+    pub max_impulse: f64,
+}
+
+// Constraint row with its body indices resolved and the data that stays constant over the iterations
+struct RowConstraint {
+    row: ConstraintRow,
+    body2: usize,
+    mass: f64,
+    impulse: f64,
+}
+
+// Per contact point data that stays constant over the solver iterations
+struct ContactConstraint {
+    // Index of the contact the point belongs to and of the point in its manifold
+    contact: usize,
+    point: usize,
+    body1: usize,
+    body2: usize,
+    normal: Vector3,
+    tangents: [Vector3; 2],
+    r1: Vector3,
+    r2: Vector3,
+    normal_mass: f64,
+    tangent_mass: [f64; 2],
+    velocity_bias: f64,
+    friction: f64,
+    normal_impulse: f64,
+    tangent_impulse: [f64; 2],
+}
+
+// Body velocities and inverse mass properties copied out of the bodies for the duration of a solve
+#[derive(Clone, Copy)]
+struct VelocityState {
+    linear_velocity: Vector3,
+    angular_velocity: Vector3,
+    inverse_mass: f64,
+    inverse_inertia: Matrix3,
+}
+
+impl VelocityState {
+    const STATIC: VelocityState = VelocityState {
+        linear_velocity: Vector3::new(0.0, 0.0, 0.0),
+        angular_velocity: Vector3::new(0.0, 0.0, 0.0),
+        inverse_mass: 0.0,
+        inverse_inertia: Matrix3::ZERO,
+    };
+
+    // Sleeping bodies are held in place like static geometry
+    fn new(body: &Body) -> VelocityState {
+        if body.is_sleeping {
+            return VelocityState::STATIC;
+        }
+
+        VelocityState {
+            linear_velocity: body.linear_velocity,
+            angular_velocity: body.angular_velocity,
+            inverse_mass: body.inverse_mass,
+            inverse_inertia: body.inverse_inertia_world(),
+        }
+    }
+
+    fn velocity_at(&self, r: Vector3) -> Vector3 {
+        self.linear_velocity + self.angular_velocity.cross(r)
+    }
+
+    fn apply_impulse(&mut self, impulse: Vector3, r: Vector3) {
+        self.linear_velocity += impulse * self.inverse_mass;
+        self.angular_velocity += self.inverse_inertia * r.cross(impulse);
+    }
+
+    fn apply_row_impulse(&mut self, linear: Vector3, angular: Vector3, impulse: f64) {
+        self.linear_velocity += linear * (self.inverse_mass * impulse);
+        self.angular_velocity += self.inverse_inertia * angular * impulse;
+    }
+
+    // Inverse of the effective mass felt along a direction when pushing at offset r
+    fn inverse_effective_mass(&self, r: Vector3, direction: Vector3) -> f64 {
+        let r_cross_direction = r.cross(direction);
+        self.inverse_mass + (self.inverse_inertia * r_cross_direction).cross(r).dot(direction)
+    }
+}
+
+// Sequential impulse solver: impulses are applied joint row by joint row and contact point by contact
+// point over several iterations, clamping the accumulated normal impulse to be non-negative and the
+// friction impulse to the Coulomb friction cone, so the result converges towards the simultaneous
+// solution
+pub fn solve_constraints(
+    body_set: &mut BodySet,
+    contacts: &mut [Contact],
+    joints: &[Joint],
+    dt: f64,
+    settings: &SolverSettings,
+) {
+    if contacts.is_empty() && joints.is_empty() {
+        return;
+    }
+
+    let mut rows = Vec::new();
+    for joint in joints {
+        joint.constraint_rows(body_set, dt, settings, &mut rows);
+    }
+
+    // Static world geometry is solved as one extra body at the end that nothing can move
+    let bodies = body_set.as_mut_slice();
+    let static_body = bodies.len();
+    let mut states: Vec<VelocityState> = bodies.iter().map(VelocityState::new).collect();
+    states.push(VelocityState::STATIC);
+
+    let mut row_constraints: Vec<RowConstraint> = rows
+        .into_iter()
+        .filter_map(|row| {
+            let body2 = row.body2.unwrap_or(static_body);
+            let (state1, state2) = (&states[row.body1], &states[body2]);
+            let inverse_mass = (state1.inverse_mass + state2.inverse_mass) * row.linear.dot(row.linear)
+                + (state1.inverse_inertia * row.angular1).dot(row.angular1)
+                + (state2.inverse_inertia * row.angular2).dot(row.angular2)
+                + row.softness;
+            (inverse_mass > 0.0).then(|| RowConstraint {
+                row,
+                body2,
+                mass: 1.0 / inverse_mass,
+                impulse: 0.0,
+            })
+        })
+        .collect();
+
+    let mut constraints = Vec::new();
+
+    for (index, contact) in contacts.iter().enumerate() {
+        let body2 = contact.body2.unwrap_or(static_body);
+        let (state1, state2) = (&states[contact.body1], &states[body2]);
+        let position1 = bodies[contact.body1].transform.position;
+        let position2 = contact
+            .body2
+            .map_or(Vector3::default(), |body2| bodies[body2].transform.position);
+        let normal = contact.manifold.normal;
+        let tangents = tangent_basis(normal);
+
+        for (point_index, point) in contact.manifold.points.iter().enumerate() {
+            let r1 = point.position - position1;
+            let r2 = point.position - position2;
+
+            let inverse_normal_mass =
+                state1.inverse_effective_mass(r1, normal) + state2.inverse_effective_mass(r2, normal);
+            if inverse_normal_mass <= 0.0 {
+                continue;
+            }
+
+            let tangent_mass = tangents.map(|tangent| {
+                let inverse_mass = state1.inverse_effective_mass(r1, tangent)
+                    + state2.inverse_effective_mass(r2, tangent);
+                if inverse_mass > 0.0 { 1.0 / inverse_mass } else { 0.0 }
+            });
+
+            // Push apart a fraction of the penetration and bounce if the bodies close fast enough
+            let closing_velocity = (state2.velocity_at(r2) - state1.velocity_at(r1)).dot(normal);
+            let position_bias =
+                settings.baumgarte / dt * (point.depth - settings.penetration_slop).max(0.0);
+            let restitution_bias = if closing_velocity < -settings.restitution_threshold {
+                -contact.restitution * closing_velocity
+            } else {
+                0.0
+            };
+
+            let impulse = contact.point_impulses.get(point_index).copied().unwrap_or_default();
+            constraints.push(ContactConstraint {
+                contact: index,
+                point: point_index,
+                body1: contact.body1,
+                body2,
+                normal,
+                tangents,
+                r1,
+                r2,
+                normal_mass: 1.0 / inverse_normal_mass,
+                tangent_mass,
+                velocity_bias: position_bias.max(restitution_bias),
+                friction: contact.friction,
+                normal_impulse: impulse.normal,
+                tangent_impulse: impulse.tangent,
+            });
+        }
+    }
+
+    // Resting contacts need about the same impulses every sub-step, so starting from the last ones lets
+    // the iterations converge on stacks and boxes lying on their faces instead of leaving them to creep
+    for constraint in constraints.iter() {
+        let [tangent1, tangent2] = constraint.tangents;
+        let impulse = constraint.normal * constraint.normal_impulse
+            + tangent1 * constraint.tangent_impulse[0]
+            + tangent2 * constraint.tangent_impulse[1];
+        states[constraint.body1].apply_impulse(impulse.inverse(), constraint.r1);
+        states[constraint.body2].apply_impulse(impulse, constraint.r2);
+    }
+
+    for _ in 0..settings.iterations {
+        for constraint in row_constraints.iter_mut() {
+            let row = &constraint.row;
+            let (mut state1, mut state2) = (states[row.body1], states[constraint.body2]);
+
+            let velocity = row.linear.dot(state2.linear_velocity - state1.linear_velocity)
+                + row.angular2.dot(state2.angular_velocity)
+                - row.angular1.dot(state1.angular_velocity);
+            let impulse = constraint.mass * (row.bias - velocity - row.softness * constraint.impulse);
+            let accumulated = (constraint.impulse + impulse).clamp(row.min_impulse, row.max_impulse);
+            let impulse = accumulated - constraint.impulse;
+            constraint.impulse = accumulated;
+
+            state1.apply_row_impulse(row.linear.inverse(), row.angular1.inverse(), impulse);
+            state2.apply_row_impulse(row.linear, row.angular2, impulse);
+
+            states[row.body1] = state1;
+            states[constraint.body2] = state2;
+        }
+
+        for constraint in constraints.iter_mut() {
+            let (mut state1, mut state2) = (states[constraint.body1], states[constraint.body2]);
+
+            // Friction is solved first since the normal impulse is the more important constraint to
+            // satisfy at the end of the iteration
+            for i in 0..2 {
+                let tangent = constraint.tangents[i];
+                let relative_velocity =
+                    state2.velocity_at(constraint.r2) - state1.velocity_at(constraint.r1);
+                let max_impulse = constraint.friction * constraint.normal_impulse;
+
+                let impulse = -constraint.tangent_mass[i] * relative_velocity.dot(tangent);
+                let accumulated =
+                    (constraint.tangent_impulse[i] + impulse).clamp(-max_impulse, max_impulse);
+                let impulse = accumulated - constraint.tangent_impulse[i];
+                constraint.tangent_impulse[i] = accumulated;
+
+                state1.apply_impulse(tangent * -impulse, constraint.r1);
+                state2.apply_impulse(tangent * impulse, constraint.r2);
+            }
+
+            let relative_velocity =
+                state2.velocity_at(constraint.r2) - state1.velocity_at(constraint.r1);
+            let impulse = constraint.normal_mass
+                * (constraint.velocity_bias - relative_velocity.dot(constraint.normal));
+            let accumulated = (constraint.normal_impulse + impulse).max(0.0);
+            let impulse = accumulated - constraint.normal_impulse;
+            constraint.normal_impulse = accumulated;
+
+            state1.apply_impulse(constraint.normal * -impulse, constraint.r1);
+            state2.apply_impulse(constraint.normal * impulse, constraint.r2);
+
+            states[constraint.body1] = state1;
+            states[constraint.body2] = state2;
+        }
+    }
+
+    for contact in contacts.iter_mut() {
+        contact.normal_impulse = 0.0;
+        contact.point_impulses.clear();
+        contact.point_impulses.resize(contact.manifold.points.len(), PointImpulse::default());
+    }
+    for constraint in constraints.iter() {
+        let contact = &mut contacts[constraint.contact];
+        contact.normal_impulse += constraint.normal_impulse;
+        contact.point_impulses[constraint.point] = PointImpulse {
+            normal: constraint.normal_impulse,
+            tangent: constraint.tangent_impulse,
+        };
+    }
+
+    for (body, state) in bodies.iter_mut().zip(states.iter()).filter(|(body, _)| !body.is_sleeping) {
+        body.linear_velocity = state.linear_velocity;
+        body.angular_velocity = state.angular_velocity;
+    }
+}
+
+// Gives the points of each contact the impulses of the nearest point of the same contact in the last
+// sub-step, so the solve is warm started. The same pair of bodies can touch more than one plane, so the
+// last contact also has to face the same way.
+pub fn warm_start_contacts(previous: &[Contact], contacts: &mut [Contact]) {
+    let mut by_pair: HashMap<(usize, Option<usize>), Vec<&Contact>> = HashMap::new();
+    for contact in previous {
+        by_pair.entry((contact.body1, contact.body2)).or_default().push(contact);
+    }
+
+    for contact in contacts.iter_mut() {
+        let normal = contact.manifold.normal;
+        let last = by_pair.get(&(contact.body1, contact.body2)).and_then(|candidates| {
+            candidates
+                .iter()
+                .find(|last| last.manifold.normal.dot(normal) > WARM_START_NORMAL_COSINE)
+        });
+        let Some(last) = last else {
+            continue;
+        };
+
+        contact.point_impulses = contact
+            .manifold
+            .points
+            .iter()
+            .map(|point| {
+                let distance =
+                    |index: &usize| (last.manifold.points[*index].position - point.position).magnitude();
+                (0..last.point_impulses.len())
+                    .filter(|index| distance(index) < WARM_START_DISTANCE)
+                    .min_by(|i, j| distance(i).total_cmp(&distance(j)))
+                    .map_or(PointImpulse::default(), |index| last.point_impulses[index])
+            })
+            .collect();
+    }
+}
+
+// Two unit vectors perpendicular to the normal and to each other
+pub fn tangent_basis(normal: Vector3) -> [Vector3; 2] {
+    let helper = if normal.x.abs() < 0.57735 {
+        Vector3::X_AXIS
+    } else {
+        Vector3::Y_AXIS
+    };
+    let tangent1 = normal.cross(helper).unit();
+    let tangent2 = normal.cross(tangent1);
+    [tangent1, tangent2]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{body::Body, collision::Plane, physics::PhysicsWorld, shape::Shape, transform::*};
+
+    // World without sleeping, so resting bodies keep being solved, and with a ground plane of the given
+    // material along the given normal through the origin
+    fn world_with_ground(normal: Vector3, restitution: f64, friction: f64) -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        world.sleep.enabled = false;
+        world.add_plane(Plane {
+            restitution,
+            friction,
+            ..Plane::new(normal, Vector3::default())
+        });
+        world
+    }
+
+    fn cube(transform: Transform, half_size: f64) -> Body {
+        let half_size = Vector3::new(half_size, half_size, half_size);
+        Body::new(transform, Shape::Box { half_size }, 1.0)
+    }
+
+    #[test]
+    fn resting_boxes_stay_put() {
+        let mut world = world_with_ground(Vector3::Y_AXIS, 0.0, 0.5);
+        let start = Transform::new(Vector3::new(0.0, 6.0, 0.0), Quaternion::default());
+        let handle = world.add_body(cube(start, 5.0));
+        for _ in 0..120 {
+            world.step();
+        }
+
+        // Once the landing has settled the box neither jitters nor creeps sideways or into the ground
+        let settled = world.bodies[handle].transform.clone();
+        for _ in 0..600 {
+            world.step();
+            let body = &world.bodies[handle];
+            assert!((body.transform.position - settled.position).magnitude() < 1e-9);
+            assert!(body.transform.rotation.dot(settled.rotation).abs() > 1.0 - 1e-12);
+        }
+
+        // Baumgarte only pushes the box out down to the penetration slop, and it lands level where it fell
+        let position = world.bodies[handle].transform.position;
+        let slop = world.solver.penetration_slop;
+        assert!(position.y > 5.0 - slop - 1e-6 && position.y < 5.0);
+        assert!(position.x.abs() < 1e-2 && position.z.abs() < 1e-2);
+        assert!(settled.rotation.dot(Quaternion::default()).abs() > 1.0 - 1e-6);
+    }
+
+    #[test]
+    fn bodies_bounce_back_at_their_restitution_times_the_impact_speed() {
+        for restitution in [0.0, 0.5, 1.0] {
+            let mut world = world_with_ground(Vector3::Y_AXIS, restitution, 0.5);
+            world.gravity = Vector3::default();
+            let mut ball = Body::new(
+                Transform::new(Vector3::new(0.0, 5.0, 0.0), Quaternion::default()),
+                Shape::Sphere { radius: 1.0 },
+                1.0,
+            );
+            ball.restitution = restitution;
+            ball.linear_velocity = Vector3::new(0.0, -60.0, 0.0);
+            let handle = world.add_body(ball);
+            for _ in 0..30 {
+                world.step();
+            }
+
+            let velocity = world.bodies[handle].linear_velocity.y;
+            let expected = 60.0 * restitution;
+            assert!((velocity - expected).abs() < 1.0, "bounced back at {velocity} with {restitution}");
+        }
+    }
+
+    #[test]
+    fn friction_holds_boxes_on_slopes_below_the_friction_angle() {
+        // Friction of 0.5 on both surfaces gives a friction angle of about 26.6 degrees
+        let friction = 0.5;
+        for degrees in [15.0_f64, 40.0] {
+            let angle = degrees.to_radians();
+            let rotation = Quaternion::from_axis_angle(Vector3::Z_AXIS, angle);
+            let normal = rotation.vector_to_world_space(Vector3::Y_AXIS);
+            let mut world = world_with_ground(normal, 0.0, friction);
+            let mut body = cube(Transform::new(normal, rotation), 1.0);
+            body.friction = friction;
+            body.restitution = 0.0;
+            let handle = world.add_body(body);
+
+            let steps = 120;
+            for _ in 0..steps {
+                world.step();
+            }
+
+            let downhill = rotation.vector_to_world_space(Vector3::X_AXIS).inverse();
+            let speed = world.bodies[handle].linear_velocity.dot(downhill);
+            let gravity = world.gravity.magnitude();
+            let expected = gravity * (angle.sin() - friction * angle.cos()) * (steps as f64 * world.timestep);
+            if expected < 0.0 {
+                assert!(speed.abs() < 0.1, "box slides at {speed} on a {degrees} degree slope");
+            } else {
+                let message = format!("box slides at {speed} instead of {expected}");
+                assert!((speed - expected).abs() < 0.05 * expected, "{message}");
+            }
+        }
+    }
+}
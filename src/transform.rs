@@ -32,18 +32,6 @@ impl Quaternion {
         }
     }
 
-    pub fn to_axis_angle(mut self) -> (Vector3, f64) {
-        self = self.unit();
-        let angle = 2.0 * self.w.acos();
-        let sin = (1.0 - self.w * self.w).sqrt();
-        let axis = Vector3 {
-            x: self.x / sin,
-            y: self.y / sin,
-            z: self.z / sin,
-        };
-        (axis.unit(), angle)
-    }
-
     pub fn magnitude(self) -> f64 {
         (self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w).sqrt()
     }
@@ -88,6 +76,41 @@ impl Quaternion {
         }
     }
 
+    // Advances the rotation by a world space angular velocity over a time step using dq/dt = 0.5 * w * q,
+    // renormalizing to counter the drift of the first order step
+    pub fn integrate(self, angular_velocity: Vector3, dt: f64) -> Quaternion {
+        let spin = Quaternion {
+            x: angular_velocity.x,
+            y: angular_velocity.y,
+            z: angular_velocity.z,
+            w: 0.0,
+        } * self;
+        (self + spin * (0.5 * dt)).unit()
+    }
+
+    pub fn dot(self, rhs: Quaternion) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    // Spherical interpolation along the shorter arc between two unit quaternions
+    pub fn slerp(self, mut rhs: Quaternion, alpha: f64) -> Quaternion {
+        let mut cos = self.dot(rhs);
+        if cos < 0.0 {
+            rhs = rhs * -1.0;
+            cos = -cos;
+        }
+
+        // Nearly identical rotations fall back to a normalized linear interpolation to avoid dividing by
+        // a vanishing sine
+        if cos > 0.9995 {
+            return (self * (1.0 - alpha) + rhs * alpha).unit();
+        }
+
+        let angle = cos.acos();
+        let sin = angle.sin();
+        (self * (((1.0 - alpha) * angle).sin() / sin) + rhs * ((alpha * angle).sin() / sin)).unit()
+    }
+
     pub fn vector_to_local_space(self, vector: Vector3) -> Vector3 {
         let q = self.inverse() * Quaternion {
             x: vector.x,
@@ -122,6 +145,32 @@ impl std::ops::MulAssign for Quaternion {
     }
 }
 
+impl std::ops::Mul<f64> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: f64) -> Quaternion {
+        Quaternion {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+            w: self.w * rhs,
+        }
+    }
+}
+
+impl std::ops::Add for Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, rhs: Quaternion) -> Quaternion {
+        Quaternion {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+            w: self.w + rhs.w,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Vector3 {
     pub x: f64,
@@ -171,6 +220,10 @@ impl Vector3 {
             z: -self.z,
         }
     }
+
+    pub fn lerp(self, rhs: Vector3, alpha: f64) -> Vector3 {
+        self + (rhs - self) * alpha
+    }
 }
 
 impl std::ops::Mul<f64> for Vector3 {
@@ -256,13 +309,6 @@ impl Transform {
         Transform { position, rotation: rotation.unit() }
     }
 
-    pub fn inverse(&self) -> Transform {
-        Transform {
-            position: self.rotation.vector_to_local_space(self.position.inverse()),
-            rotation: self.rotation.inverse().unit(),
-        }
-    }
-
     pub fn point_to_world_space(&self, point: Vector3) -> Vector3 {
         self.rotation.vector_to_world_space(point) + self.position
     }
@@ -270,6 +316,13 @@ impl Transform {
     pub fn point_to_local_space(&self, point: Vector3) -> Vector3 {
         self.rotation.vector_to_local_space(point - self.position)
     }
+
+    pub fn interpolate(&self, rhs: &Transform, alpha: f64) -> Transform {
+        Transform {
+            position: self.position.lerp(rhs.position, alpha),
+            rotation: self.rotation.slerp(rhs.rotation, alpha),
+        }
+    }
 }
 
 impl std::ops::Mul for Transform {
@@ -283,7 +336,136 @@ impl std::ops::Mul for Transform {
     }
 }
 
-pub trait Transformable {
-    fn transform(&self) -> &Transform;
-    fn transform_mut(&mut self) -> &mut Transform;
-}
\ No newline at end of file
+// Half-line starting at an origin and extending along a unit direction
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ray {
+    pub origin: Vector3,
+    pub direction: Vector3,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3, direction: Vector3) -> Ray {
+        Ray {
+            origin,
+            direction: direction.unit(),
+        }
+    }
+
+    pub fn at(self, distance: f64) -> Vector3 {
+        self.origin + self.direction * distance
+    }
+
+    pub fn to_local_space(self, transform: &Transform) -> Ray {
+        Ray {
+            origin: transform.point_to_local_space(self.origin),
+            direction: transform.rotation.vector_to_local_space(self.direction),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Matrix3 {
+    pub rows: [Vector3; 3],
+}
+
+impl Default for Matrix3 {
+    fn default() -> Matrix3 {
+        Matrix3::IDENTITY
+    }
+}
+
+impl Matrix3 {
+
+    pub const IDENTITY: Matrix3 = Matrix3::from_diagonal(Vector3::new(1.0, 1.0, 1.0));
+    pub const ZERO: Matrix3 = Matrix3::from_diagonal(Vector3::new(0.0, 0.0, 0.0));
+
+    pub const fn new(row_x: Vector3, row_y: Vector3, row_z: Vector3) -> Matrix3 {
+        Matrix3 { rows: [row_x, row_y, row_z] }
+    }
+
+    pub const fn from_diagonal(diagonal: Vector3) -> Matrix3 {
+        Matrix3::new(
+            Vector3::new(diagonal.x, 0.0, 0.0),
+            Vector3::new(0.0, diagonal.y, 0.0),
+            Vector3::new(0.0, 0.0, diagonal.z),
+        )
+    }
+
+    // Rotation matrix whose columns are the basis vectors of the rotation
+    pub fn from_rotation(rotation: Quaternion) -> Matrix3 {
+        let (x, y, z) = rotation.basis_vectors();
+        Matrix3::new(x, y, z).transpose()
+    }
+
+    pub fn transpose(self) -> Matrix3 {
+        let [x, y, z] = self.rows;
+        Matrix3::new(
+            Vector3::new(x.x, y.x, z.x),
+            Vector3::new(x.y, y.y, z.y),
+            Vector3::new(x.z, y.z, z.z),
+        )
+    }
+
+    pub fn determinant(self) -> f64 {
+        let [x, y, z] = self.rows;
+        x.dot(y.cross(z))
+    }
+
+    // Inverse of the matrix, or zero for a singular one. The determinant is compared against the product
+    // of the row lengths, its largest possible size, so that matrices of tiny or huge values still invert.
+    pub fn inverse(self) -> Matrix3 {
+        let [x, y, z] = self.rows;
+        let determinant = self.determinant();
+        if determinant.abs() <= f64::EPSILON * x.magnitude() * y.magnitude() * z.magnitude() {
+            return Matrix3::ZERO;
+        }
+
+        // The columns of the inverse are the cross products of the rows over the determinant
+        Matrix3::new(y.cross(z), z.cross(x), x.cross(y)).transpose() * (1.0 / determinant)
+    }
+}
+
+impl std::ops::Mul<Vector3> for Matrix3 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        Vector3 {
+            x: self.rows[0].dot(rhs),
+            y: self.rows[1].dot(rhs),
+            z: self.rows[2].dot(rhs),
+        }
+    }
+}
+
+impl std::ops::Mul for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, rhs: Matrix3) -> Matrix3 {
+        let columns = rhs.transpose();
+        Matrix3::new(
+            columns * self.rows[0],
+            columns * self.rows[1],
+            columns * self.rows[2],
+        )
+    }
+}
+
+impl std::ops::Mul<f64> for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, rhs: f64) -> Matrix3 {
+        Matrix3::new(self.rows[0] * rhs, self.rows[1] * rhs, self.rows[2] * rhs)
+    }
+}
+
+impl std::ops::Add for Matrix3 {
+    type Output = Matrix3;
+
+    fn add(self, rhs: Matrix3) -> Matrix3 {
+        Matrix3::new(
+            self.rows[0] + rhs.rows[0],
+            self.rows[1] + rhs.rows[1],
+            self.rows[2] + rhs.rows[2],
+        )
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{body_set::BodyHandle, event::*};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerEventKind {
+    // The body started overlapping the sensor this step
+    Enter,
+    // The body was already overlapping the sensor and still is
+    Stay,
+    // The body stopped overlapping the sensor this step, or one of them was removed
+    Exit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TriggerEvent {
+    pub sensor: BodyHandle,
+    pub body: BodyHandle,
+    pub kind: TriggerEventKind,
+}
+
+// Compares the (sensor, body) overlaps of this step with those of the last one, both sorted, and writes
+// an event for every pair in either, in pair order
+pub fn find_trigger_events(
+    previous: &[(BodyHandle, BodyHandle)],
+    current: &[(BodyHandle, BodyHandle)],
+    events: &mut Vec<PhysicsEvent>,
+) {
+    diff_sorted(previous, current, |(sensor, body), change| {
+        let kind = match change {
+            Change::Added(_) => TriggerEventKind::Enter,
+            Change::Kept(_) => TriggerEventKind::Stay,
+            Change::Removed => TriggerEventKind::Exit,
+        };
+        events.push(PhysicsEvent::Trigger(TriggerEvent { sensor, body, kind }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{body::Body, physics::PhysicsWorld, shape::Shape, transform::*};
+
+    #[test]
+    fn bodies_passing_through_a_sensor_within_a_step_enter_and_exit_it() {
+        let mut world = PhysicsWorld::new();
+        world.gravity = Vector3::default();
+        world.sub_steps = 4;
+        let sensor = world.add_body(Body {
+            is_sensor: true,
+            ..Body::new_static(
+                Transform::default(),
+                Shape::Box {
+                    half_size: Vector3::new(0.5, 0.5, 0.5),
+                },
+            )
+        });
+
+        // Two units per sub-step, so only the third sub-step of the first step finds the sphere inside
+        let body = world.add_body(Body {
+            linear_velocity: Vector3::new(480.0, 0.0, 0.0),
+            ..Body::new(
+                Transform::new(Vector3::new(-4.0, 0.0, 0.0), Quaternion::default()),
+                Shape::Sphere { radius: 1.0 },
+                1.0,
+            )
+        });
+
+        let trigger_events = |world: &mut PhysicsWorld| -> Vec<TriggerEventKind> {
+            world.step();
+            world
+                .drain_events()
+                .filter_map(|event| match event {
+                    PhysicsEvent::Trigger(event) if (event.sensor, event.body) == (sensor, body) => {
+                        Some(event.kind)
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(trigger_events(&mut world), [TriggerEventKind::Enter]);
+        assert_eq!(trigger_events(&mut world), [TriggerEventKind::Exit]);
+        assert!(trigger_events(&mut world).is_empty());
+    }
+}
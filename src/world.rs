@@ -1,7 +1,9 @@
-use crate::raster::Scene;
+use crate::{physics::PhysicsWorld, raster::Scene};
 
 pub trait World: Scene {
     fn update(&mut self, dt: f64);
+
+    fn physics(&self) -> &PhysicsWorld;
 }
 
 pub mod test_world {
@@ -9,28 +11,454 @@ pub mod test_world {
     use super::World;
 
     use crate::body::*;
+    use crate::body_set::*;
+    use crate::broadphase::*;
+    use crate::collision::*;
+    use crate::event::*;
+    use crate::force::*;
+    use crate::gjk::*;
+    use crate::joint::*;
+    use crate::mesh::*;
+    use crate::particle::*;
+    use crate::physics::*;
     use crate::raster::*;
+    use crate::raycast::*;
+    use crate::shape::*;
     use crate::transform::*;
+    use crate::trigger::*;
 
     use tui::style::Color;
 
+    use std::rc::Rc;
+
     const FLOOR_BOUND: f64 = 1_000_000.0;
+    const SLEEPING_TINT: Vector3 = Vector3 { x: 0.5, y: 0.5, z: 1.0 };
+    const ZONE_TINT: Vector3 = Vector3 { x: 1.0, y: 1.0, z: 0.4 };
+
+    // the floor flashes red for a moment whenever two bodies start touching this hard
+    const IMPACT_IMPULSE: f64 = 10.0;
+    const IMPACT_FLASH_TIME: f64 = 0.2;
 
     const ANGULAR_VELOCITY: f64 = -2.5;
     const ANGULAR_ROTATION_AXIS: Vector3 = Vector3 { x: 1.0, y: 1.0, z: 1.0 };
     const INITIAL_ANGLE_RADIANS: f64 = 2.0;
 
+    const BODY_MASS: f64 = 1.0;
+    const BODY_DROP_HEIGHTS: [(f64, f64); 2] = [(0.0, 30.0), (5.0, 60.0)];
+    const BODY_HALF_SIZE: Vector3 = Vector3 { x: 10.0, y: 10.0, z: 10.0 };
+
+    // one of each of the other shapes dropped in a row behind the boxes
+    const SHAPE_DROP_POSITIONS: [Vector3; 3] = [
+        Vector3 { x: -25.0, y: 40.0, z: 110.0 },
+        Vector3 { x: 5.0, y: 50.0, z: 110.0 },
+        Vector3 { x: 35.0, y: 40.0, z: 110.0 },
+    ];
+    const SPHERE_RADIUS: f64 = 8.0;
+    const CAPSULE_RADIUS: f64 = 5.0;
+    const CAPSULE_HALF_HEIGHT: f64 = 8.0;
+    const HULL_POINTS: [Vector3; 5] = [
+        Vector3 { x: -10.0, y: -8.0, z: -10.0 },
+        Vector3 { x: 10.0, y: -8.0, z: -10.0 },
+        Vector3 { x: 10.0, y: -8.0, z: 10.0 },
+        Vector3 { x: -10.0, y: -8.0, z: 10.0 },
+        Vector3 { x: 0.0, y: 12.0, z: 0.0 },
+    ];
+
+    // barrel dropped onto the tip of the hull, a shape the engine only knows through its support function
+    const BARREL_POSITION: Vector3 = Vector3 { x: 35.0, y: 90.0, z: 110.0 };
+    const BARREL_RADIUS: f64 = 5.0;
+    const BARREL_HALF_HEIGHT: f64 = 7.0;
+    const BARREL_SEGMENTS: usize = 16;
+
+    // chain of boxes hanging off a point in the world by ball sockets, starting out horizontal so it swings
+    const CHAIN_ANCHOR: Vector3 = Vector3 { x: -45.0, y: 45.0, z: 95.0 };
+    const CHAIN_LINKS: usize = 3;
+    const CHAIN_LINK_HALF_SIZE: Vector3 = Vector3 { x: 2.5, y: 1.0, z: 1.0 };
+
+    // row of jointed bodies behind the pool: a door on a hinge that only lets it swing so far, a shuttle
+    // that a slider motor drives back and forth between its limits, two boxes welded into one and a
+    // ball bouncing on a bungee under a beam it's allowed to hit
+    const DOOR_HINGE: Vector3 = Vector3 { x: -45.0, y: 10.0, z: 140.0 };
+    const DOOR_HALF_SIZE: Vector3 = Vector3 { x: 6.0, y: 9.0, z: 0.5 };
+    const DOOR_SWING_LIMIT: f64 = 1.2;
+    const DOOR_ANGULAR_VELOCITY: f64 = 3.0;
+    const SHUTTLE_POSITION: Vector3 = Vector3 { x: -10.0, y: 10.0, z: 140.0 };
+    const SHUTTLE_HALF_SIZE: Vector3 = Vector3 { x: 3.0, y: 2.0, z: 3.0 };
+    const SHUTTLE_TRAVEL: f64 = 10.0;
+    const SHUTTLE_SPEED: f64 = 8.0;
+    const SHUTTLE_MAX_FORCE: f64 = 1_000.0;
+    const WELDED_POSITIONS: [Vector3; 2] = [
+        Vector3 { x: 15.0, y: 40.0, z: 140.0 },
+        Vector3 { x: 19.0, y: 46.0, z: 140.0 },
+    ];
+    const WELDED_HALF_SIZE: Vector3 = Vector3 { x: 3.0, y: 3.0, z: 3.0 };
+    const BEAM_POSITION: Vector3 = Vector3 { x: 45.0, y: 45.0, z: 140.0 };
+    const BEAM_HALF_SIZE: Vector3 = Vector3 { x: 8.0, y: 1.0, z: 8.0 };
+    const BUNGEE_LENGTH: f64 = 12.0;
+    const BUNGEE_STIFFNESS: f64 = 20.0;
+    const BUNGEE_DAMPING: f64 = 0.5;
+    const BUNGEE_BALL_RADIUS: f64 = 3.0;
+    const BUNGEE_BALL_VELOCITY: Vector3 = Vector3 { x: 0.0, y: 80.0, z: 0.0 };
+
+    // small sphere fired down at a thin slab fast enough to pass through it between steps, which
+    // continuous collision detection stops
+    const SLAB_POSITION: Vector3 = Vector3 { x: 45.0, y: 15.0, z: 70.0 };
+    const SLAB_HALF_SIZE: Vector3 = Vector3 { x: 10.0, y: 0.5, z: 10.0 };
+    const BULLET_HEIGHT: f64 = 100.0;
+    const BULLET_RADIUS: f64 = 1.5;
+    const BULLET_VELOCITY: Vector3 = Vector3 { x: 0.0, y: -900.0, z: 0.0 };
+
+    // invisible sensor over the floor where the boxes land, tinting whatever is inside it
+    const ZONE_POSITION: Vector3 = Vector3 { x: 2.5, y: 5.0, z: 75.0 };
+    const ZONE_HALF_SIZE: Vector3 = Vector3 { x: 20.0, y: 5.0, z: 20.0 };
+
+    // kinematic platform under the sphere that lifts it up and down
+    const PLATFORM_POSITION: Vector3 = Vector3 { x: -25.0, y: 7.0, z: 110.0 };
+    const PLATFORM_HALF_SIZE: Vector3 = Vector3 { x: 12.0, y: 1.0, z: 12.0 };
+    const PLATFORM_AMPLITUDE: f64 = 5.0;
+    const PLATFORM_PERIOD: f64 = 4.0;
+
+    // pool of water off to the side with a box and a sphere floating in it
+    const POOL_POSITION: Vector3 = Vector3 { x: 75.0, y: 15.0, z: 110.0 };
+    const POOL_HALF_EXTENT: f64 = 20.0;
+    const POOL_HEIGHT: f64 = 60.0;
+    // dense enough for bodies of the usual mass to float about half submerged
+    const WATER_DENSITY: f64 = 0.002;
+    const WATER_LINEAR_DRAG: f64 = 2.0;
+    const WATER_ANGULAR_DRAG: f64 = 2.0;
+    const WATER_COLOR: Color = Color::Rgb(40, 90, 220);
+    const POOL_RIM_HEIGHT: f64 = 17.0;
+    const POOL_RIM_THICKNESS: f64 = 1.0;
+    const POOL_RIM_COLOR: Color = Color::Rgb(200, 190, 170);
+    const FLOATING_BOX_HALF_SIZE: Vector3 = Vector3 { x: 5.0, y: 5.0, z: 5.0 };
+    const FLOATING_SPHERE_RADIUS: f64 = 6.0;
+    const FLOATING_DROP_POSITIONS: [Vector3; 2] = [
+        Vector3 { x: 65.0, y: 50.0, z: 105.0 },
+        Vector3 { x: 85.0, y: 40.0, z: 115.0 },
+    ];
+
+    // current in the pool that gathers what floats in it towards the middle
+    const POOL_CURRENT_STRENGTH: f64 = 10.0;
+
+    // wind along the top of the slab that blows the bullet off it towards the pool once it lands
+    const WIND_HEIGHT: f64 = 5.0;
+    const WIND_VELOCITY: Vector3 = Vector3 { x: 20.0, y: 0.0, z: 30.0 };
+    const WIND_DRAG: f64 = 0.3;
+
+    // air resistance on everything, a fraction of the velocities per second
+    const AIR_DAMPING: f64 = 0.05;
+
+    // pressing x sets off an explosion on the floor where the boxes land
+    const EXPLOSION_CENTER: Vector3 = Vector3 { x: 2.5, y: 0.0, z: 75.0 };
+    const EXPLOSION_RADIUS: f64 = 50.0;
+    const EXPLOSION_IMPULSE: f64 = 100.0;
+    // cone ringed by a torus marking the explosion center
+    const EXPLOSION_MARKER_RADIUS: f64 = 2.0;
+    const EXPLOSION_MARKER_HEIGHT: f64 = 6.0;
+    const EXPLOSION_MARKER_COLOR: Color = Color::Rgb(255, 140, 0);
+    const MARKER_SEGMENTS: usize = 12;
+
+    // rope hanging off a point above the slab and a sheet of cloth dropped over the boxes
+    const ROPE_ANCHOR: Vector3 = Vector3 { x: 40.0, y: 45.0, z: 70.0 };
+    const ROPE_LENGTH: f64 = 30.0;
+    const ROPE_SEGMENTS: usize = 15;
+    const ROPE_TINT: Vector3 = Vector3 { x: 0.9, y: 0.7, z: 0.4 };
+    const ROPE_ANCHOR_RADIUS: f64 = 1.5;
+    const ROPE_ANCHOR_COLOR: Color = Color::Rgb(160, 160, 170);
+    const CLOTH_CORNER: Vector3 = Vector3 { x: -12.5, y: 80.0, z: 60.0 };
+    const CLOTH_SIZE: f64 = 30.0;
+    const CLOTH_PARTICLES: usize = 13;
+    const CLOTH_TINT: Vector3 = Vector3 { x: 0.9, y: 0.4, z: 0.9 };
+
+    // spot on the floor in front of the boxes where a loaded model is shown, scaled to this size
+    const MODEL_POSITION: Vector3 = Vector3 { x: -30.0, y: 0.0, z: 55.0 };
+    const MODEL_SIZE: f64 = 25.0;
+
+    // arrows along the world axes, colored red, green and blue for x, y and z
+    const GIZMO_POSITION: Vector3 = Vector3 { x: 45.0, y: 0.0, z: 45.0 };
+    const GIZMO_LENGTH: f64 = 12.0;
+    const GIZMO_SEGMENTS: usize = 8;
+
+    // left clicking a body pushes it away from the camera with this impulse, right clicking removes it
+    const PICK_DISTANCE: f64 = 1_000.0;
+    const POKE_IMPULSE: f64 = 60.0;
+
     pub struct TestWorld {
         pub light: Vector3,
         pub camera_transform: Transform,
-        pub bodies: Vec<Body>,
-        pub is_colliding: bool,
+        pub physics: PhysicsWorld,
+        // time left to show the floor flashing for the last hard impact between bodies
+        pub impact_flash: f64,
+        // tint sleeping bodies blue to debug sleeping
+        pub show_sleeping: bool,
+        // bodies inside the zone sensor, kept up to date from its trigger events
+        pub zone: BodyHandle,
+        pub zone_occupants: Vec<BodyHandle>,
+        pub platform: BodyHandle,
+        // body on the slider, whose motor turns around at either end
+        pub shuttle: BodyHandle,
+        pub rope: usize,
+        pub cloth: usize,
+        // decoration that doesn't take part in the physics, already placed in world space
+        pub meshes: Vec<Mesh>,
+        // surface of the pool, drawn unshaded and left out of picking so clicks reach what floats in it
+        pub water: Mesh,
+        // time since the world was created, which drives the platform
+        pub time: f64,
     }
 
     impl TestWorld {
         pub fn new() -> Self {
+            let mut physics = PhysicsWorld::new();
+            physics.add_plane(Plane::new(Vector3::Y_AXIS, Vector3::default()));
+
+            for (x, y) in BODY_DROP_HEIGHTS {
+                physics.add_body(Body {
+                    angular_velocity: ANGULAR_VELOCITY * ANGULAR_ROTATION_AXIS.unit(),
+                    ..Body::new(
+                        Transform::new(
+                            Vector3 {
+                                x,
+                                y,
+                                z: 75.0,
+                            },
+                            Quaternion::from_axis_angle(
+                                ANGULAR_ROTATION_AXIS,
+                                INITIAL_ANGLE_RADIANS,
+                            ),
+                        ),
+                        Shape::Box {
+                            half_size: BODY_HALF_SIZE,
+                        },
+                        BODY_MASS,
+                    )
+                });
+            }
+
+            let mut shapes = vec![
+                Shape::Sphere {
+                    radius: SPHERE_RADIUS,
+                },
+                Shape::Capsule {
+                    radius: CAPSULE_RADIUS,
+                    half_height: CAPSULE_HALF_HEIGHT,
+                },
+            ];
+            if let Some((hull, _)) = ConvexHull::new(&HULL_POINTS) {
+                shapes.push(Shape::ConvexHull(hull));
+            }
+
+            for (position, shape) in SHAPE_DROP_POSITIONS.into_iter().zip(shapes) {
+                physics.add_body(Body::new(
+                    Transform::new(
+                        position,
+                        Quaternion::from_axis_angle(ANGULAR_ROTATION_AXIS, INITIAL_ANGLE_RADIANS),
+                    ),
+                    shape,
+                    BODY_MASS,
+                ));
+            }
+
+            physics.add_body(Body::new(
+                Transform::new(
+                    BARREL_POSITION,
+                    Quaternion::from_axis_angle(ANGULAR_ROTATION_AXIS, INITIAL_ANGLE_RADIANS),
+                ),
+                Shape::Custom(Rc::new(Barrel {
+                    radius: BARREL_RADIUS,
+                    half_height: BARREL_HALF_HEIGHT,
+                })),
+                BODY_MASS,
+            ));
+
+            let mut previous_link = None;
+            for i in 0..CHAIN_LINKS {
+                let joint_position =
+                    CHAIN_ANCHOR + Vector3::X_AXIS * (2.0 * CHAIN_LINK_HALF_SIZE.x * i as f64);
+                let link = physics.add_body(Body::new(
+                    Transform::new(
+                        joint_position + Vector3::X_AXIS * CHAIN_LINK_HALF_SIZE.x,
+                        Quaternion::default(),
+                    ),
+                    Shape::Box {
+                        half_size: CHAIN_LINK_HALF_SIZE,
+                    },
+                    BODY_MASS,
+                ));
+
+                let joint = Joint::ball_socket(&physics.bodies, link, previous_link, joint_position);
+                physics.add_joint(joint);
+                previous_link = Some(link);
+            }
+
+            let door = physics.add_body(Body {
+                angular_velocity: Vector3::Y_AXIS * DOOR_ANGULAR_VELOCITY,
+                ..Body::new(
+                    Transform::new(DOOR_HINGE + Vector3::X_AXIS * DOOR_HALF_SIZE.x, Quaternion::default()),
+                    Shape::Box {
+                        half_size: DOOR_HALF_SIZE,
+                    },
+                    BODY_MASS,
+                )
+            });
+            let hinge = Joint::hinge(&physics.bodies, door, None, DOOR_HINGE, Vector3::Y_AXIS)
+                .with_limit(-DOOR_SWING_LIMIT, DOOR_SWING_LIMIT);
+            physics.add_joint(hinge);
+
+            let shuttle = physics.add_body(Body::new(
+                Transform::new(SHUTTLE_POSITION, Quaternion::default()),
+                Shape::Box {
+                    half_size: SHUTTLE_HALF_SIZE,
+                },
+                BODY_MASS,
+            ));
+            let slider = Joint::slider(&physics.bodies, shuttle, None, Vector3::X_AXIS)
+                .with_limit(-SHUTTLE_TRAVEL, SHUTTLE_TRAVEL)
+                .with_motor(SHUTTLE_SPEED, SHUTTLE_MAX_FORCE);
+            physics.add_joint(slider);
+
+            let [welded1, welded2] = WELDED_POSITIONS.map(|position| {
+                physics.add_body(Body::new(
+                    Transform::new(position, Quaternion::default()),
+                    Shape::Box {
+                        half_size: WELDED_HALF_SIZE,
+                    },
+                    BODY_MASS,
+                ))
+            });
+            physics.add_joint(Joint::fixed(&physics.bodies, welded1, Some(welded2)));
+
+            let beam = physics.add_body(Body::new_static(
+                Transform::new(BEAM_POSITION, Quaternion::default()),
+                Shape::Box {
+                    half_size: BEAM_HALF_SIZE,
+                },
+            ));
+            let bungee_anchor = BEAM_POSITION - Vector3::Y_AXIS * BEAM_HALF_SIZE.y;
+            let ball_position = bungee_anchor - Vector3::Y_AXIS * BUNGEE_LENGTH;
+            let ball = physics.add_body(Body {
+                linear_velocity: BUNGEE_BALL_VELOCITY,
+                ..Body::new(
+                    Transform::new(ball_position, Quaternion::default()),
+                    Shape::Sphere {
+                        radius: BUNGEE_BALL_RADIUS,
+                    },
+                    BODY_MASS,
+                )
+            });
+            let bungee = Joint::distance(&physics.bodies, ball, Some(beam), ball_position, bungee_anchor)
+                .with_spring(BUNGEE_STIFFNESS, BUNGEE_DAMPING)
+                .with_collide_connected(true);
+            physics.add_joint(bungee);
+
+            physics.add_body(Body::new_static(
+                Transform::new(SLAB_POSITION, Quaternion::default()),
+                Shape::Box {
+                    half_size: SLAB_HALF_SIZE,
+                },
+            ));
+            physics.add_body(Body {
+                linear_velocity: BULLET_VELOCITY,
+                is_bullet: true,
+                ..Body::new(
+                    Transform::new(SLAB_POSITION + Vector3::Y_AXIS * BULLET_HEIGHT, Quaternion::default()),
+                    Shape::Sphere {
+                        radius: BULLET_RADIUS,
+                    },
+                    BODY_MASS,
+                )
+            });
+
+            let zone = physics.add_body(Body {
+                is_sensor: true,
+                ..Body::new_static(
+                    Transform::new(ZONE_POSITION, Quaternion::default()),
+                    Shape::Box {
+                        half_size: ZONE_HALF_SIZE,
+                    },
+                )
+            });
+
+            let platform = physics.add_body(Body::new_kinematic(
+                Transform::new(PLATFORM_POSITION, Quaternion::default()),
+                Shape::Box {
+                    half_size: PLATFORM_HALF_SIZE,
+                },
+            ));
+
+            let floating_shapes = [
+                Shape::Box {
+                    half_size: FLOATING_BOX_HALF_SIZE,
+                },
+                Shape::Sphere {
+                    radius: FLOATING_SPHERE_RADIUS,
+                },
+            ];
+            for (position, shape) in FLOATING_DROP_POSITIONS.into_iter().zip(floating_shapes) {
+                physics.add_body(Body::new(
+                    Transform::new(
+                        position,
+                        Quaternion::from_axis_angle(ANGULAR_ROTATION_AXIS, INITIAL_ANGLE_RADIANS),
+                    ),
+                    shape,
+                    BODY_MASS,
+                ));
+            }
+            physics.add_force_generator(ForceGenerator::Buoyancy {
+                volume: pool_volume(),
+                surface: Plane::new(Vector3::Y_AXIS, POOL_POSITION),
+                density: WATER_DENSITY,
+                linear_drag: WATER_LINEAR_DRAG,
+                angular_drag: WATER_ANGULAR_DRAG,
+            });
+
+            physics.add_force_generator(ForceGenerator::Radial {
+                center: POOL_POSITION,
+                radius: POOL_HALF_EXTENT,
+                strength: POOL_CURRENT_STRENGTH,
+            });
+            physics.add_force_generator(ForceGenerator::Wind {
+                volume: Aabb::new(
+                    SLAB_POSITION - Vector3::new(SLAB_HALF_SIZE.x, -SLAB_HALF_SIZE.y, SLAB_HALF_SIZE.z),
+                    SLAB_POSITION + SLAB_HALF_SIZE + Vector3::Y_AXIS * WIND_HEIGHT,
+                ),
+                velocity: WIND_VELOCITY,
+                drag: WIND_DRAG,
+            });
+            physics.add_force_generator(ForceGenerator::Damping {
+                linear: AIR_DAMPING,
+                angular: AIR_DAMPING,
+            });
+
+            let rope = physics.add_particle_system(ParticleSystem::rope(
+                ROPE_ANCHOR,
+                ROPE_ANCHOR + Vector3::X_AXIS * ROPE_LENGTH,
+                ROPE_SEGMENTS,
+                BODY_MASS,
+            ));
+            let cloth = physics.add_particle_system(ParticleSystem::cloth(
+                CLOTH_CORNER,
+                Vector3::X_AXIS * CLOTH_SIZE,
+                Vector3::Z_AXIS * CLOTH_SIZE,
+                CLOTH_PARTICLES,
+                CLOTH_PARTICLES,
+                BODY_MASS,
+            ));
+
             TestWorld {
-                is_colliding: false,
+                impact_flash: 0.0,
+                zone,
+                zone_occupants: Vec::new(),
+                platform,
+                shuttle,
+                rope,
+                cloth,
+                meshes: vec![pool_rim(), axis_gizmo(), explosion_marker(), rope_anchor()],
+                water: Mesh::plane(POOL_HALF_EXTENT, POOL_HALF_EXTENT, 1, 1)
+                    .with_color(WATER_COLOR)
+                    .transformed(&Transform::new(POOL_POSITION, Quaternion::default())),
+                time: 0.0,
+                show_sleeping: true,
                 light: Vector3 { 
                     x: 0.0, 
                     y: -1.0, 
@@ -38,9 +466,9 @@ pub mod test_world {
                 }.unit(),
                 camera_transform: Transform::new(
                     Vector3 {
-                        x: 0.0,
-                        y: 10.0 + 256.0,
-                        z: 0.0 - 256.0/1.0_f64.to_radians().tan(),
+                        x: 12.5,
+                        y: 40.0,
+                        z: 0.0,
                     },
                     Quaternion::from_axis_angle(
                         Vector3 {
@@ -48,55 +476,253 @@ pub mod test_world {
                             y: 0.0,
                             z: 0.0,
                         },
-                        1.0_f64.to_radians(),
+                        25.0_f64.to_radians(),
                     ),
                 ),
-                bodies: vec![
-                    Body {
-                        transform: Transform::new(
-                            Vector3 {
-                                x: 0.0,
-                                y: 0.0,
-                                z: 75.0,
-                            },
-                            Quaternion::from_axis_angle(
-                                ANGULAR_ROTATION_AXIS,
-                                INITIAL_ANGLE_RADIANS,
-                            ),
-                        ),
-                        half_size: Vector3::new(10.0, 10.0, 10.0),
-                        ..Default::default()
-                    },
-                    Body {
-                        transform: Transform::new(
-                            Vector3 {
-                                x: 25.0,
-                                y: 0.0,
-                                z: 75.0,
-                            },
-                            Quaternion::from_axis_angle(
-                                ANGULAR_ROTATION_AXIS,
-                                INITIAL_ANGLE_RADIANS,
-                            ),
-                        ),
-                        half_size: Vector3::new(10.0, 10.0, 10.0),
-                        ..Default::default()
+                physics,
+            }
+        }
+
+        // Shows a model standing on the floor in front of the boxes, scaled so its longest side fits
+        pub fn add_model(&mut self, mut mesh: Mesh) {
+            let bounds = mesh.aabb();
+            let size = bounds.max - bounds.min;
+            let center = (bounds.min + bounds.max) * 0.5;
+            let base = Vector3::new(center.x, bounds.min.y, center.z);
+
+            mesh.transform(&Transform::new(base.inverse(), Quaternion::default()));
+            let longest = size.x.max(size.y).max(size.z);
+            if longest > 0.0 {
+                mesh.scale(Vector3::new(1.0, 1.0, 1.0) * (MODEL_SIZE / longest));
+            }
+            mesh.transform(&Transform::new(MODEL_POSITION, Quaternion::default()));
+            self.meshes.push(mesh);
+        }
+
+        // Pushes the body under the cursor along the ray, or reports the decoration or floor it hits
+        pub fn poke(&mut self, ray: &Ray) {
+            let hit = self.physics.raycast(ray, PICK_DISTANCE);
+            let max_distance = hit.map_or(PICK_DISTANCE, |hit| hit.hit.distance);
+            let decoration = self
+                .meshes
+                .iter()
+                .enumerate()
+                .filter_map(|(index, mesh)| {
+                    let triangles: Vec<SceneTriangle> = mesh.geometry(&Transform::default()).collect();
+                    raycast_triangles(ray, &triangles, max_distance).map(|(_, hit)| (index, hit))
+                })
+                .min_by(|a, b| a.1.distance.total_cmp(&b.1.distance));
+
+            match (decoration, hit) {
+                (Some((index, hit)), _) => log::info!("Clicked decoration {index} at {:.1?}", hit.point),
+                (None, Some(RaycastHit { body: Some(handle), hit })) => {
+                    let body = &mut self.physics.bodies[handle];
+                    if body.body_type == BodyType::Dynamic {
+                        body.apply_impulse(ray.direction * POKE_IMPULSE, hit.point);
+                        log::info!("Poked body {handle:?} at {:.1?}", hit.point);
+                    } else {
+                        log::info!("Clicked immovable body {handle:?} at {:.1?}", hit.point);
                     }
-                ],
+                }
+                (None, Some(RaycastHit { body: None, hit })) => {
+                    log::info!("Clicked the floor at {:.1?}", hit.point)
+                }
+                (None, None) => {}
+            }
+        }
+
+        pub fn explode(&mut self) {
+            self.physics.explode(EXPLOSION_CENTER, EXPLOSION_RADIUS, EXPLOSION_IMPULSE);
+            log::info!("Explosion at {EXPLOSION_CENTER:.1?}");
+        }
+
+        // Removes the body under the cursor from the world, along with its joints
+        pub fn remove(&mut self, ray: &Ray) {
+            let Some(RaycastHit { body: Some(handle), .. }) = self.physics.raycast(ray, PICK_DISTANCE) else {
+                return;
+            };
+            if self.physics.remove_body(handle).is_some() {
+                log::info!("Removed body {handle:?}");
             }
         }
+
+        // Colors a triangle by how directly it faces the light
+        fn shade(&self, mut geometry: SceneTriangle, tint: Vector3) -> SceneTriangle {
+            let brightness = (0.5*(1.0 - geometry.normal.dot(self.light))).clamp(0.0, 1.0);
+            geometry.color = Color::Rgb(
+                (255.0*brightness*tint.x).round() as u8, 
+                (255.0*brightness*tint.y).round() as u8, 
+                (255.0*brightness*tint.z).round() as u8
+            );
+            geometry
+        }
+    }
+
+    // Cylinder standing on its end, with the core along y like a capsule
+    #[derive(Debug)]
+    struct Barrel {
+        radius: f64,
+        half_height: f64,
+    }
+
+    impl SupportMap for Barrel {
+        fn support(&self, direction: Vector3) -> Vector3 {
+            let across = Vector3::new(direction.x, 0.0, direction.z);
+            let rim = if across.magnitude() > 0.0 { across.unit() * self.radius } else { Vector3::default() };
+            let cap = if direction.y < 0.0 { -self.half_height } else { self.half_height };
+            rim + Vector3::Y_AXIS * cap
+        }
+    }
+
+    impl CustomShape for Barrel {
+        fn bounding_radius(&self) -> f64 {
+            (self.radius * self.radius + self.half_height * self.half_height).sqrt()
+        }
+
+        fn volume(&self) -> f64 {
+            2.0 * std::f64::consts::PI * self.radius * self.radius * self.half_height
+        }
+
+        fn unit_inertia(&self) -> Matrix3 {
+            let (r, h) = (self.radius, 2.0 * self.half_height);
+            let transverse = (3.0 * r * r + h * h) / 12.0;
+            Matrix3::from_diagonal(Vector3::new(transverse, 0.5 * r * r, transverse))
+        }
+
+        fn mesh(&self) -> Mesh {
+            Mesh::cylinder(self.radius, self.half_height, BARREL_SEGMENTS)
+        }
+    }
+
+    // Space above the floor of the pool where bodies feel the water
+    fn pool_volume() -> Aabb {
+        let extent = Vector3::new(POOL_HALF_EXTENT, 0.0, POOL_HALF_EXTENT);
+        Aabb::new(
+            Vector3::new(POOL_POSITION.x, 0.0, POOL_POSITION.z) - extent,
+            Vector3::new(POOL_POSITION.x, POOL_HEIGHT, POOL_POSITION.z) + extent,
+        )
+    }
+
+    // Low wall around the edge of the pool, from four boxes merged into one mesh
+    fn pool_rim() -> Mesh {
+        let (extent, thickness) = (POOL_HALF_EXTENT + POOL_RIM_THICKNESS, POOL_RIM_THICKNESS);
+        let center = Vector3::new(POOL_POSITION.x, 0.5 * POOL_RIM_HEIGHT, POOL_POSITION.z);
+        let mut rim = Mesh::default();
+        for (offset, half_size) in [
+            (Vector3::new(0.0, 0.0, extent), Vector3::new(extent, 0.5 * POOL_RIM_HEIGHT, thickness)),
+            (Vector3::new(0.0, 0.0, -extent), Vector3::new(extent, 0.5 * POOL_RIM_HEIGHT, thickness)),
+            (Vector3::new(extent, 0.0, 0.0), Vector3::new(thickness, 0.5 * POOL_RIM_HEIGHT, extent)),
+            (Vector3::new(-extent, 0.0, 0.0), Vector3::new(thickness, 0.5 * POOL_RIM_HEIGHT, extent)),
+        ] {
+            let wall = Mesh::cuboid(half_size).with_color(POOL_RIM_COLOR);
+            rim.merge(&wall.transformed(&Transform::new(center + offset, Quaternion::default())));
+        }
+        rim
+    }
+
+    // Cone standing on the floor at the explosion center inside a ring lying around its base
+    fn explosion_marker() -> Mesh {
+        let (radius, half_height) = (EXPLOSION_MARKER_RADIUS, 0.5 * EXPLOSION_MARKER_HEIGHT);
+        let mut marker = Mesh::cone(radius, half_height, MARKER_SEGMENTS)
+            .with_color(EXPLOSION_MARKER_COLOR)
+            .transformed(&Transform::new(
+                EXPLOSION_CENTER + Vector3::Y_AXIS * half_height,
+                Quaternion::default(),
+            ));
+        let ring = Mesh::torus(2.0 * radius, 0.25 * radius, 2 * MARKER_SEGMENTS, MARKER_SEGMENTS / 2)
+            .with_color(EXPLOSION_MARKER_COLOR);
+        marker.merge(&ring.transformed(&Transform::new(
+            EXPLOSION_CENTER + Vector3::Y_AXIS * (0.25 * radius),
+            Quaternion::default(),
+        )));
+        marker
+    }
+
+    // Ball the rope hangs from, since nothing else holds it up
+    fn rope_anchor() -> Mesh {
+        Mesh::icosphere(ROPE_ANCHOR_RADIUS, 1)
+            .with_color(ROPE_ANCHOR_COLOR)
+            .transformed(&Transform::new(ROPE_ANCHOR, Quaternion::default()))
+    }
+
+    // Arrows are built along y, so the x and z arrows are turned over onto their axes
+    fn axis_gizmo() -> Mesh {
+        let mut gizmo = Mesh::default();
+        let quarter_turn = 0.5 * std::f64::consts::PI;
+        for (rotation, color) in [
+            (Quaternion::from_axis_angle(Vector3::Z_AXIS, -quarter_turn), Color::Rgb(255, 60, 60)),
+            (Quaternion::default(), Color::Rgb(60, 255, 60)),
+            (Quaternion::from_axis_angle(Vector3::X_AXIS, quarter_turn), Color::Rgb(60, 60, 255)),
+        ] {
+            let (shaft_radius, head_radius, head_length) =
+                (0.1 * GIZMO_LENGTH, 0.2 * GIZMO_LENGTH, 0.3 * GIZMO_LENGTH);
+            let arrow = Mesh::arrow(GIZMO_LENGTH, shaft_radius, head_radius, head_length, GIZMO_SEGMENTS)
+                .with_color(color);
+            gizmo.merge(&arrow.transformed(&Transform::new(GIZMO_POSITION, rotation)));
+        }
+        gizmo
     }
 
     impl World for TestWorld {
         fn update(&mut self, dt: f64) {
-            for body in self.bodies.iter_mut() {
-                body.transform.rotation *= Quaternion::from_axis_angle(
-                    ANGULAR_ROTATION_AXIS,
-                    ANGULAR_VELOCITY * dt,
-                );
+            // the platform heads for where a sine wave puts it by the end of the frame, so it rises and sinks
+            // smoothly without drifting from the steps not lining up with frames
+            self.time += dt;
+            let frequency = 2.0 * std::f64::consts::PI / PLATFORM_PERIOD;
+            let target = Transform::new(
+                PLATFORM_POSITION + Vector3::Y_AXIS * (PLATFORM_AMPLITUDE * (frequency * self.time).sin()),
+                Quaternion::default(),
+            );
+            if let Some(platform) = self.physics.bodies.get_mut(self.platform) {
+                platform.move_kinematic(&target, dt);
+            }
+
+            // the shuttle turns around whenever it reaches either end of its slider
+            let PhysicsWorld { joints, bodies, .. } = &mut self.physics;
+            if let Some(joint) = joints.iter_mut().find(|joint| joint.body1 == self.shuttle) {
+                let translation = joint.translation(bodies);
+                if let JointKind::Slider {
+                    limit: Some(limit),
+                    motor: Some(motor),
+                } = &mut joint.kind
+                {
+                    let speed = if translation <= limit.lower {
+                        motor.speed.abs()
+                    } else if translation >= limit.upper {
+                        -motor.speed.abs()
+                    } else {
+                        motor.speed
+                    };
+                    if speed != motor.speed {
+                        motor.speed = speed;
+                        bodies[self.shuttle].wake();
+                    }
+                }
+            }
+
+            self.physics.update(dt);
+
+            self.impact_flash = (self.impact_flash - dt).max(0.0);
+
+            for event in self.physics.drain_events() {
+                match event {
+                    PhysicsEvent::Contact(ContactEvent {
+                        body2: Some(_),
+                        kind: ContactEventKind::Began { impulse },
+                        ..
+                    }) if impulse >= IMPACT_IMPULSE => self.impact_flash = IMPACT_FLASH_TIME,
+                    PhysicsEvent::Trigger(event) if event.sensor == self.zone => match event.kind {
+                        TriggerEventKind::Enter => self.zone_occupants.push(event.body),
+                        TriggerEventKind::Exit => self.zone_occupants.retain(|&body| body != event.body),
+                        TriggerEventKind::Stay => {}
+                    },
+                    _ => {}
+                }
             }
+        }
 
-            self.is_colliding = is_colliding(&self.bodies[0], &self.bodies[1]);
+        fn physics(&self) -> &PhysicsWorld {
+            &self.physics
         }
     }
     
@@ -108,46 +734,59 @@ pub mod test_world {
         fn update_geometry(&self, buf: &mut Vec<SceneTriangle>) {
             buf.clear();
 
-            let floor_color = if self.is_colliding {
+            let floor_color = if self.impact_flash > 0.0 {
                 Color::Rgb(255, 0, 0)
             } else {
                 Color::Rgb(0, 255, 0)
             };
 
             // green "floor"
-            buf.push(SceneTriangle {
-                normal: Vector3::Y_AXIS,
-                points: [
-                    Vector3::new(FLOOR_BOUND, 0.0, FLOOR_BOUND - FLOOR_BOUND + 1000.0), 
-                    Vector3::new(-FLOOR_BOUND, 0.0, FLOOR_BOUND - FLOOR_BOUND + 1000.0), 
-                    Vector3::new(-FLOOR_BOUND, 0.0, -FLOOR_BOUND - FLOOR_BOUND + 1000.0)
-                ],
-                color: floor_color
-            });
-            buf.push(SceneTriangle {
-                normal: Vector3::Y_AXIS,
-                points: [
-                    Vector3::new(-FLOOR_BOUND, 0.0, -FLOOR_BOUND - FLOOR_BOUND + 1000.0), 
-                    Vector3::new(FLOOR_BOUND, 0.0, -FLOOR_BOUND - FLOOR_BOUND + 1000.0), 
-                    Vector3::new(FLOOR_BOUND, 0.0, FLOOR_BOUND - FLOOR_BOUND + 1000.0)
-                ],
-                color: floor_color
-            });
+            for plane in self.physics.planes.iter() {
+                buf.extend(plane.geometry(FLOOR_BOUND, floor_color));
+            }
 
-            // bodies
+            // blue water surface over the pool
+            buf.extend(self.water.geometry(&Transform::default()));
+
+            // bodies, blended between the last two physics steps
+            let alpha = self.physics.alpha();
             buf.extend(
-                self.bodies.iter().flat_map(|body| {
-                    body.geometry().map(|mut geometry| {
-                        let brightness = (0.5*(1.0 - geometry.normal.dot(self.light))).clamp(0.0, 1.0);
-                        geometry.color = Color::Rgb(
-                            (255.0*brightness).round() as u8, 
-                            (255.0*brightness).round() as u8, 
-                            (255.0*brightness).round() as u8
-                        );
-                        geometry
-                    })
+                self.physics.bodies.iter().filter(|(_, body)| !body.is_sensor).flat_map(|(handle, body)| {
+                    let tint = if self.show_sleeping && body.is_sleeping {
+                        SLEEPING_TINT
+                    } else if self.zone_occupants.contains(&handle) {
+                        ZONE_TINT
+                    } else {
+                        Vector3::new(1.0, 1.0, 1.0)
+                    };
+                    let transform = body.interpolated_transform(alpha);
+                    body.geometry_at(&transform)
+                        .map(move |geometry| self.shade(geometry, tint))
+                        .collect::<Vec<_>>()
                 })
             );
+
+            // decoration, tinted by its own colors
+            let origin = Transform::default();
+            for mesh in self.meshes.iter() {
+                buf.extend(mesh.geometry(&origin).map(|geometry| {
+                    let Color::Rgb(r, g, b) = geometry.color else {
+                        return self.shade(geometry, Vector3::new(1.0, 1.0, 1.0));
+                    };
+                    let tint = Vector3::new(r as f64, g as f64, b as f64) * (1.0 / 255.0);
+                    self.shade(geometry, tint)
+                }));
+            }
+
+            // rope and cloth
+            for (system, tint) in [(self.rope, ROPE_TINT), (self.cloth, CLOTH_TINT)] {
+                buf.extend(
+                    self.physics.particle_systems[system]
+                        .geometry(alpha)
+                        .into_iter()
+                        .map(|geometry| self.shade(geometry, tint)),
+                );
+            }
         }
     }
 }
\ No newline at end of file